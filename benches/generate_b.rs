@@ -1,3 +1,5 @@
+#![allow(clippy::unit_arg, clippy::needless_range_loop, clippy::ptr_arg)]
+
 #[macro_use]
 extern crate bencher;
 extern crate ragbooster;
@@ -5,28 +7,93 @@ extern crate ragbooster;
 use bencher::Bencher;
 use ragbooster::mle::tensors::DenseTensor;
 use ragbooster::mle::prob as prob;
+use ragbooster::mle::simd::InstructionSet;
 
 benchmark_group!(generate_b, generate_b__no_opt, generate_b__from_tensor,
-    generate_b__from_tensor_predicated, generate_b__from_tensor_predicated_simd);
+    generate_b__from_tensor_predicated, generate_b__from_tensor_predicated_simd,
+    generate_b__portable, generate_b__avx2, generate_b__avx512,
+    generate_b__portable_wide, generate_b__avx2_wide, generate_b__avx512_wide);
 benchmark_main!(generate_b);
 
 const BENCH_M: usize = 1000;
 const BENCH_K: usize = 50;
 const BENCH_E: usize = 5;
+const BENCH_E_WIDE: usize = 64;
 
 fn generate_bench_data() -> (Vec<f64>, Vec<f64>, Vec<f64>,) {
+    generate_bench_data_with(BENCH_E)
+}
+
+fn generate_bench_data_with(num_distinct: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>,) {
     let p = vec![0.5_f64; BENCH_M];
     let retrieved_costs: Vec<_> = (0..BENCH_M)
-        .map(|i| (i % BENCH_E) as f64 * 1.0 / BENCH_E as f64)
+        .map(|i| (i % num_distinct) as f64 * 1.0 / num_distinct as f64)
         .collect();
 
-    let distinct_costs: Vec<_> = (0..BENCH_E)
-        .map(|i| i as f64 * 1.0 / BENCH_E as f64)
+    let distinct_costs: Vec<_> = (0..num_distinct)
+        .map(|i| i as f64 * 1.0 / num_distinct as f64)
         .collect();
 
     (p, retrieved_costs, distinct_costs)
 }
 
+// Benchmarks the kernels of an instruction set, unsupported instruction sets are skipped
+#[allow(non_snake_case)]
+fn generate_b__with_instruction_set(
+    bench: &mut Bencher,
+    instruction_set: InstructionSet,
+    num_distinct: usize,
+) {
+    if instruction_set > InstructionSet::detect() {
+        return;
+    }
+
+    let (p, retrieved_costs, distinct_costs) = generate_bench_data_with(num_distinct);
+    let mut B = DenseTensor::new(BENCH_K + 1, BENCH_M + 2, num_distinct);
+
+    bench.iter(|| {
+        bencher::black_box(prob::compute_boundary_set_prob_any_loss_with_instruction_set(
+            instruction_set,
+            &retrieved_costs,
+            &distinct_costs,
+            &p,
+            BENCH_K,
+            BENCH_M,
+            &mut B
+        ));
+    })
+}
+
+#[allow(non_snake_case)]
+fn generate_b__portable(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Portable, BENCH_E);
+}
+
+#[allow(non_snake_case)]
+fn generate_b__avx2(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Avx2, BENCH_E);
+}
+
+#[allow(non_snake_case)]
+fn generate_b__avx512(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Avx512, BENCH_E);
+}
+
+#[allow(non_snake_case)]
+fn generate_b__portable_wide(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Portable, BENCH_E_WIDE);
+}
+
+#[allow(non_snake_case)]
+fn generate_b__avx2_wide(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Avx2, BENCH_E_WIDE);
+}
+
+#[allow(non_snake_case)]
+fn generate_b__avx512_wide(bench: &mut Bencher) {
+    generate_b__with_instruction_set(bench, InstructionSet::Avx512, BENCH_E_WIDE);
+}
+
 #[allow(non_snake_case)]
 fn generate_b__no_opt(bench: &mut Bencher) {
 
@@ -1,3 +1,5 @@
+#![allow(clippy::unit_arg, clippy::needless_range_loop, clippy::large_const_arrays)]
+
 #[macro_use]
 extern crate bencher;
 extern crate ragbooster;
@@ -5,11 +7,14 @@ extern crate ragbooster;
 use bencher::Bencher;
 use ragbooster::mle::tensors::DenseMatrix;
 use ragbooster::mle::prob as prob;
+use ragbooster::mle::simd::InstructionSet;
 
 benchmark_group!(generate_iprp,
     generate_iprp__no_opt_tiny, generate_iprp__tensors_tiny, generate_iprp__from_tensors_tiny,
     generate_iprp__no_opt, generate_iprp__tensors, generate_iprp__from_tensors,
-    generate_iprp__no_opt_larger, generate_iprp__tensors_larger, generate_iprp__from_tensors_larger
+    generate_iprp__no_opt_larger, generate_iprp__tensors_larger, generate_iprp__from_tensors_larger,
+    generate_iprp__portable, generate_iprp__avx2, generate_iprp__avx512,
+    generate_iprp__portable_larger, generate_iprp__avx2_larger, generate_iprp__avx512_larger
 );
 benchmark_main!(generate_iprp);
 
@@ -110,6 +115,64 @@ fn generate_iprp__from_tensors_larger(bench: &mut Bencher) {
 }
 
 
+// Benchmarks the transposed IP and RP on the kernels of an instruction set, unsupported
+// instruction sets are skipped
+#[allow(non_snake_case)]
+fn generate_iprp__with_instruction_set(
+    bench: &mut Bencher,
+    instruction_set: InstructionSet,
+    p: &[f64],
+    K: usize,
+    M: usize,
+) {
+    if instruction_set > InstructionSet::detect() {
+        return;
+    }
+
+    let mut IP = DenseMatrix::new(M + 2, K + 1);
+    let mut RP = DenseMatrix::new(M + 2, K + 1);
+
+    bench.iter(|| {
+        bencher::black_box(prob::compute_transposed_prob_with_instruction_set(
+            instruction_set, p, K, M, &mut IP, &mut RP));
+    })
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__portable(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(
+        bench, InstructionSet::Portable, &MEDIUM_P, MEDIUM_K, MEDIUM_M);
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__avx2(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(bench, InstructionSet::Avx2, &MEDIUM_P, MEDIUM_K, MEDIUM_M);
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__avx512(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(
+        bench, InstructionSet::Avx512, &MEDIUM_P, MEDIUM_K, MEDIUM_M);
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__portable_larger(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(
+        bench, InstructionSet::Portable, &LARGE_P, LARGE_K, LARGE_M);
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__avx2_larger(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(bench, InstructionSet::Avx2, &LARGE_P, LARGE_K, LARGE_M);
+}
+
+#[allow(non_snake_case)]
+fn generate_iprp__avx512_larger(bench: &mut Bencher) {
+    generate_iprp__with_instruction_set(
+        bench, InstructionSet::Avx512, &LARGE_P, LARGE_K, LARGE_M);
+}
+
+
 #[allow(non_snake_case)]
 fn compute_prob(
     p: &[f64],
@@ -1,5 +1,7 @@
 from .core import score, Question
-from .ragbooster import learn_importance
+from .ragbooster import learn_importance, learn_group_importance, learn_importance_with_statistics, \
+    tune_pruning_thresholds, tune_pruning_thresholds_constrained, cross_validate_pruning, expected_utility, rerank, \
+    evaluate_reranking, learn_model, save_model, load_model, update_model
 from .generator import Generator, HuggingfaceQAGenerator
 from .retriever import BingRetriever
 from .rag import RetrievalAugmentedModel, RAGBooster
@@ -7,8 +9,18 @@ from .rag import RetrievalAugmentedModel, RAGBooster
 
 __all__ = [
     'score', 'Question',
-    'learn_importance',
+    'learn_importance', 'learn_group_importance', 'learn_importance_with_statistics', 'tune_pruning_thresholds',
+    'tune_pruning_thresholds_constrained', 'cross_validate_pruning',
+    'expected_utility', 'rerank', 'evaluate_reranking',
+    'learn_model', 'save_model', 'load_model', 'update_model',
     'Generator', 'HuggingfaceQAGenerator',
     'BingRetriever',
     'RetrievalAugmentedModel', 'RAGBooster',
 ]
+
+# Only available if the extension was built with the arrow feature
+try:
+    from .ragbooster import read_retrievals_parquet, read_grouping_parquet, save_weights_parquet
+    __all__ += ['read_retrievals_parquet', 'read_grouping_parquet', 'save_weights_parquet']
+except ImportError:
+    pass
@@ -45,9 +45,14 @@ def encode_retrievals(retrievals, retrieved_key, prediction_key, utility):
     for retrieval in retrievals:
         retrieveds = [mapping[name] for name in retrieval[retrieved_key]]
         utilities = [utility(retrieval, prediction) for prediction in retrieval[prediction_key]]
+        # Predictions only need to be distinguishable within a retrieval for majority voting
+        prediction_ids = {}
+        predictions = [prediction_ids.setdefault(prediction, len(prediction_ids))
+                       for prediction in retrieval[prediction_key]]
         encoded_retrievals.append({
             "retrieved": retrieveds,
-            "utility_contributions": utilities
+            "utility_contributions": utilities,
+            "predictions": predictions,
         })
 
     return encoded_retrievals, mapping
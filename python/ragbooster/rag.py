@@ -1,7 +1,7 @@
 from tqdm.notebook import tqdm
-from ragbooster import learn_importance
+from ragbooster import learn_importance, cross_validate_pruning
 from .core import grouped_weights, encode_groups, encode_retrievals, mode
-from .tuning import tune_pruning_threshold
+from .tuning import tune_pruning_threshold_encoded
 
 import logging
 
@@ -35,11 +35,13 @@ class RetrievalAugmentedModel:
 
 class RAGBooster:
 
-    def __init__(self, rag_model, validation_questions, learning_rate=10, num_epochs=100, n_jobs=-1):
+    def __init__(self, rag_model, validation_questions, learning_rate=10, num_epochs=100, n_jobs=-1, num_folds=None):
         self.rag_model = rag_model
         self.learning_rate = learning_rate
         self.num_epochs = num_epochs
         self.n_jobs = n_jobs
+        self.num_folds = num_folds
+        self.cross_validated_accuracies = None
         self._fit(validation_questions)
 
     def _utility(self, retrieved, prediction):
@@ -83,14 +85,23 @@ class RAGBooster:
         percentile_range = range(0, 100, 5)
 
         logger.info('Tuning threshold for corpus pruning...')
-        # grouping could be used here as well
-        tuning_result = tune_pruning_threshold(validation_corpus, domain_weights, percentile_range,
-                                               self._utility, self.rag_model.retriever.group,
-                                               self.rag_model.k, normalize=True)
+        tuning_result = tune_pruning_threshold_encoded(encoded_retrievals, weights, domain_weights, percentile_range,
+                                                       self.rag_model.k, normalize=True, n_jobs=self.n_jobs)
 
         logger.info(f'Achieved accuracy of {tuning_result.best_utility:.3f} with a pruning threshold ' +
                     f'of {tuning_result.best_threshold:.5f} on the validation set.')
 
+        if self.num_folds is not None:
+            logger.info(f'Estimating held-out accuracy with {self.num_folds}-fold cross-validation...')
+            _, mean_accuracies, best_index = cross_validate_pruning(
+                encoded_retrievals, k=self.rag_model.k, learning_rate=self.learning_rate,
+                num_epochs=self.num_epochs, percentiles=[float(p) for p in percentile_range],
+                num_folds=self.num_folds, n_jobs=self.n_jobs, grouping=grouping)
+
+            self.cross_validated_accuracies = mean_accuracies
+            logger.info(f'Achieved a held-out accuracy of {mean_accuracies[best_index]:.3f} with pruning at the ' +
+                        f'{percentile_range[best_index]}th percentile of the weights.')
+
         self.weights = domain_weights
         self.tuning_result = tuning_result
 
@@ -1,6 +1,7 @@
 from dataclasses import dataclass
 import numpy as np
 from .core import mode
+from .ragbooster import tune_pruning_thresholds
 
 
 # TODO use precomputed groups here for performance
@@ -61,3 +62,17 @@ def tune_pruning_threshold(samples, group_weights, percentile_range, utility, gr
             best_percentile = percentile
 
     return TuningResult(achieved_utilities, best_utility, best_threshold, best_percentile)
+
+
+def tune_pruning_threshold_encoded(encoded_retrievals, weights, group_weights, percentile_range, k,
+                                   normalize=False, n_jobs=-1):
+    """Same as tune_pruning_threshold, but evaluates all thresholds at once in Rust on the encoded retrievals
+    (with per-source weights) instead of looking up the group of every retrieved website."""
+
+    percentiles = list(percentile_range)
+    thresholds = [np.percentile(list(group_weights.values()), percentile) for percentile in percentiles]
+
+    achieved_utilities, best_utility, best_threshold, best_index = \
+        tune_pruning_thresholds(encoded_retrievals, weights, thresholds, k, normalize=normalize, n_jobs=n_jobs)
+
+    return TuningResult(achieved_utilities, best_utility, best_threshold, percentiles[best_index])
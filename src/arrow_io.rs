@@ -0,0 +1,253 @@
+//! Import and export of retrievals, groups and weights as Arrow record batches and Parquet files,
+//! e.g., for validation logs written by Spark. Only available with the `arrow` feature.
+//!
+//! A retrieval table has one row per retrieval, with the names of the retrieved sources and their
+//! utility contributions as list columns (in the order of retrieval), and optional weight and
+//! timestamp columns. A group table has one row per group, with its name and the names of its
+//! elements, like the group JSONL files.
+
+use crate::io::{encode_groups, Group, StringIndexer};
+use crate::mle::types::{Grouping, Retrieval};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, RecordBatch, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Float64Type, Int64Type, Schema, TimeUnit};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+/// Names of the columns of a retrieval table.
+#[derive(Debug, Clone)]
+pub struct RetrievalColumns {
+    pub sources: String,
+    pub utility_contributions: String,
+    /// Every retrieval has a weight of one if not given or if the table has no such column
+    pub weight: Option<String>,
+    /// Seconds since the Unix epoch or an Arrow timestamp, the retrievals have no timestamps if
+    /// not given or if the table has no such column
+    pub timestamp: Option<String>,
+}
+
+impl Default for RetrievalColumns {
+    fn default() -> Self {
+        Self {
+            sources: "sources".to_owned(),
+            utility_contributions: "utility_contributions".to_owned(),
+            weight: Some("weight".to_owned()),
+            timestamp: Some("timestamp".to_owned()),
+        }
+    }
+}
+
+fn invalid_data(message: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> io::Result<&'a ArrayRef> {
+    batch.column_by_name(name).ok_or_else(|| invalid_data(format!("missing column {name}")))
+}
+
+fn list_of(data_type: DataType) -> DataType {
+    DataType::List(Arc::new(Field::new_list_field(data_type, true)))
+}
+
+// Casts a column (e.g., a list of integers or a large list) to a list of the given type and
+// returns the values of every row
+fn list_values(array: &ArrayRef, data_type: DataType, name: &str) -> io::Result<Vec<ArrayRef>> {
+    let array = cast(array, &list_of(data_type)).map_err(invalid_data)?;
+    let list = array.as_list::<i32>();
+    if list.null_count() > 0 {
+        return Err(invalid_data(format!("column {name} contains nulls")));
+    }
+    Ok(list.iter().map(|row| row.unwrap()).collect())
+}
+
+fn strings(array: &ArrayRef, name: &str) -> io::Result<Vec<String>> {
+    let array = cast(array, &DataType::Utf8).map_err(invalid_data)?;
+    let strings = array.as_string::<i32>();
+    if strings.null_count() > 0 {
+        return Err(invalid_data(format!("column {name} contains nulls")));
+    }
+    Ok(strings.iter().map(|string| string.unwrap().to_owned()).collect())
+}
+
+fn floats(array: &ArrayRef, name: &str) -> io::Result<Vec<f64>> {
+    let array = cast(array, &DataType::Float64).map_err(invalid_data)?;
+    let floats = array.as_primitive::<Float64Type>();
+    if floats.null_count() > 0 {
+        return Err(invalid_data(format!("column {name} contains nulls")));
+    }
+    Ok(floats.values().to_vec())
+}
+
+fn timestamps(array: &ArrayRef, name: &str) -> io::Result<Vec<i64>> {
+    // Timestamps with a finer unit are converted to seconds, integers are taken as seconds
+    let array = match array.data_type() {
+        DataType::Timestamp(_, _) => {
+            cast(array, &DataType::Timestamp(TimeUnit::Second, None)).map_err(invalid_data)?
+        },
+        _ => array.clone(),
+    };
+    let array = cast(&array, &DataType::Int64).map_err(invalid_data)?;
+    let timestamps = array.as_primitive::<Int64Type>();
+    if timestamps.null_count() > 0 {
+        return Err(invalid_data(format!("column {name} contains nulls")));
+    }
+    Ok(timestamps.values().to_vec())
+}
+
+/// Encodes the retrievals of record batches, where the sources get ids in the lexicographic order
+/// of their names (like for the QA retrieval JSONL files).
+pub fn retrievals_from_batches(
+    batches: &[RecordBatch],
+    columns: &RetrievalColumns,
+) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+
+    let mut all_sources: Vec<Vec<String>> = Vec::new();
+    let mut all_utility_contributions: Vec<Vec<f64>> = Vec::new();
+    let mut weights: Vec<f64> = Vec::new();
+    let mut all_timestamps: Vec<Option<i64>> = Vec::new();
+
+    for batch in batches {
+        for sources in list_values(column(batch, &columns.sources)?, DataType::Utf8,
+                                   &columns.sources)? {
+            all_sources.push(strings(&sources, &columns.sources)?);
+        }
+        let name = &columns.utility_contributions;
+        for utility_contributions in list_values(column(batch, name)?, DataType::Float64, name)? {
+            all_utility_contributions.push(floats(&utility_contributions, name)?);
+        }
+        match columns.weight.as_ref().and_then(|weight| batch.column_by_name(weight)) {
+            Some(weight_column) => {
+                weights.extend(floats(weight_column, columns.weight.as_ref().unwrap())?)
+            },
+            None => weights.resize(weights.len() + batch.num_rows(), 1.0),
+        }
+        match columns.timestamp.as_ref().and_then(|timestamp| batch.column_by_name(timestamp)) {
+            Some(timestamp_column) => {
+                let name = columns.timestamp.as_ref().unwrap();
+                all_timestamps.extend(timestamps(timestamp_column, name)?.into_iter().map(Some))
+            },
+            None => all_timestamps.resize(all_timestamps.len() + batch.num_rows(), None),
+        }
+    }
+
+    let mut source_indexer = StringIndexer::new();
+    for (row, (sources, utility_contributions)) in
+        all_sources.iter().zip(all_utility_contributions.iter()).enumerate() {
+        if sources.len() != utility_contributions.len() {
+            return Err(invalid_data(format!(
+                "inconsistent number of sources and utility contributions in row {row}")));
+        }
+        if let Some(weight) = weights.get(row).filter(|w| !(**w >= 0.0 && w.is_finite())) {
+            return Err(invalid_data(format!("invalid weight {weight} in row {row}")));
+        }
+        source_indexer.observe_all(sources);
+    }
+
+    let source_index = source_indexer.create_index();
+
+    let retrievals = all_sources.into_iter()
+        .zip(all_utility_contributions)
+        .zip(weights)
+        .zip(all_timestamps)
+        .map(|(((sources, utility_contributions), weight), timestamp)| {
+            let retrieved = sources.iter().map(|source| source_index[source]).collect();
+            let retrieval = Retrieval::with_weight(retrieved, utility_contributions, weight);
+            match timestamp {
+                Some(timestamp) => retrieval.at(timestamp),
+                None => retrieval,
+            }
+        })
+        .collect();
+
+    Ok((retrievals, source_indexer))
+}
+
+/// Decodes the groups of record batches with a `name` and an `elements` column.
+pub fn groups_from_batches(batches: &[RecordBatch]) -> io::Result<Vec<Group>> {
+    let mut groups = Vec::new();
+
+    for batch in batches {
+        let names = strings(column(batch, "name")?, "name")?;
+        let all_elements = list_values(column(batch, "elements")?, DataType::Utf8, "elements")?;
+
+        for (name, elements) in names.into_iter().zip(all_elements) {
+            groups.push(Group { name, elements: strings(&elements, "elements")? });
+        }
+    }
+
+    Ok(groups)
+}
+
+/// A record batch with a `name` and a `weight` column.
+pub fn weights_to_batch(names: &[String], weights: &[f64]) -> RecordBatch {
+    assert_eq!(names.len(), weights.len(), "need one name per weight");
+
+    let schema = Schema::new(vec![
+        Field::new("name", DataType::Utf8, false),
+        Field::new("weight", DataType::Float64, false),
+    ]);
+
+    RecordBatch::try_new(Arc::new(schema), vec![
+        Arc::new(StringArray::from_iter_values(names)),
+        Arc::new(Float64Array::from(weights.to_vec())),
+    ])
+    .unwrap()
+}
+
+/// Decodes the names and weights of record batches written by `weights_to_batch`.
+pub fn weights_from_batches(batches: &[RecordBatch]) -> io::Result<(Vec<String>, Vec<f64>)> {
+    let mut names = Vec::new();
+    let mut weights = Vec::new();
+
+    for batch in batches {
+        names.extend(strings(column(batch, "name")?, "name")?);
+        weights.extend(floats(column(batch, "weight")?, "weight")?);
+    }
+
+    Ok((names, weights))
+}
+
+pub fn read_parquet(path: &str) -> io::Result<Vec<RecordBatch>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)
+        .map_err(invalid_data)?
+        .build()
+        .map_err(invalid_data)?;
+
+    reader.map(|batch| batch.map_err(invalid_data)).collect()
+}
+
+pub fn write_parquet(path: &str, batch: &RecordBatch) -> io::Result<()> {
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)
+        .map_err(invalid_data)?;
+    writer.write(batch).map_err(invalid_data)?;
+    writer.close().map_err(invalid_data)?;
+    Ok(())
+}
+
+pub fn read_retrievals_parquet(
+    path: &str,
+    columns: &RetrievalColumns,
+) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+    retrievals_from_batches(&read_parquet(path)?, columns)
+}
+
+pub fn read_groups_parquet(
+    path: &str,
+    element_index: &HashMap<String, usize>,
+) -> io::Result<(Grouping, StringIndexer)> {
+    encode_groups(groups_from_batches(&read_parquet(path)?)?, element_index)
+}
+
+pub fn write_weights_parquet(path: &str, names: &[String], weights: &[f64]) -> io::Result<()> {
+    write_parquet(path, &weights_to_batch(names, weights))
+}
+
+pub fn read_weights_parquet(path: &str) -> io::Result<(Vec<String>, Vec<f64>)> {
+    weights_from_batches(&read_parquet(path)?)
+}
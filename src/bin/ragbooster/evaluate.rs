@@ -0,0 +1,180 @@
+use ragbooster::io::{read_questions, QuestionAnswering};
+use ragbooster::mle::types::Grouping;
+use ragbooster::pruning::{self, Aggregation, CoverageConstraints};
+
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+
+use crate::weights::read_weights;
+use crate::{n_jobs, predictions_of, read_dataset, weights_of, EvaluateArgs, PruneArgs, TuneArgs};
+
+
+// Websites without a weight are never pruned
+fn pruning_weights(weights: &[Option<f64>]) -> Vec<f64> {
+    weights.iter().map(|weight| weight.unwrap_or(f64::INFINITY)).collect()
+}
+
+// Like the Python tuning, percentiles of grouped websites are taken over the groups, whose weight
+// is the mean of the known weights of their members
+fn known_group_weights(weights: &[Option<f64>], grouping: &Grouping) -> Vec<f64> {
+
+    let mut group_sums = vec![0.0; grouping.num_groups()];
+    let mut group_counts = vec![0usize; grouping.num_groups()];
+
+    for (group, weight) in grouping.group_assignments().iter().zip(weights.iter()) {
+        if let Some(weight) = weight {
+            group_sums[*group] += *weight;
+            group_counts[*group] += 1;
+        }
+    }
+
+    group_sums.iter().zip(group_counts.iter())
+        .filter(|(_, count)| **count > 0)
+        .map(|(sum, count)| sum / *count as f64)
+        .collect()
+}
+
+pub fn evaluate(args: &EvaluateArgs) -> io::Result<()> {
+
+    let dataset = read_dataset(&args.data)?;
+    let weights = pruning_weights(
+        &weights_of(&dataset, args.weights.as_deref(), &args.training)?);
+    let predictions = predictions_of(&dataset)?;
+    let thresholds = [args.threshold];
+
+    let accuracy = pruning::evaluate_pruned(
+        &dataset.retrievals,
+        Some(&predictions),
+        &weights,
+        args.training.k,
+        &thresholds,
+        Aggregation::MajorityVote,
+        true,
+        n_jobs(args.training.threads)
+    );
+
+    let coverage = pruning::evaluate_coverage(
+        &dataset.retrievals,
+        &weights,
+        args.training.k,
+        &thresholds,
+        None,
+        n_jobs(args.training.threads)
+    );
+
+    println!("threshold,accuracy,question_coverage,empty_questions,corpus_pruned");
+    println!("{},{},{},{},{}", args.threshold, accuracy[0], coverage[0].question_coverage,
+             coverage[0].empty_questions, coverage[0].corpus_pruned);
+
+    Ok(())
+}
+
+pub fn tune(args: &TuneArgs) -> io::Result<()> {
+
+    let dataset = read_dataset(&args.data)?;
+    let optional_weights = weights_of(&dataset, args.weights.as_deref(), &args.training)?;
+    let weights = pruning_weights(&optional_weights);
+    let predictions = predictions_of(&dataset)?;
+
+    let (optional_percentiles, thresholds) = match &args.thresholds {
+        Some(thresholds) => (None, thresholds.clone()),
+        None => {
+            let percentiles = args.percentiles.clone().unwrap_or_else(|| {
+                (0..100).step_by(5).map(|percentile| percentile as f64).collect()
+            });
+            let known_weights: Vec<f64> = match &dataset.grouping {
+                Some((grouping, _)) => known_group_weights(&optional_weights, grouping),
+                None => optional_weights.iter().flatten().copied().collect(),
+            };
+            if known_weights.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    "no website of the questions has a weight to take percentiles of"));
+            }
+            let thresholds = pruning::percentile_thresholds(&known_weights, &percentiles);
+            (Some(percentiles), thresholds)
+        },
+    };
+
+    let result = pruning::tune_pruning_threshold_constrained(
+        &dataset.retrievals,
+        Some(&predictions),
+        &weights,
+        args.training.k,
+        &thresholds,
+        Aggregation::MajorityVote,
+        &CoverageConstraints::new(args.min_question_coverage, args.max_corpus_pruned),
+        None,
+        n_jobs(args.training.threads)
+    );
+
+    println!("percentile,threshold,accuracy,question_coverage,empty_questions,corpus_pruned,\
+              feasible,pareto_optimal");
+    for (index, threshold) in thresholds.iter().enumerate() {
+        let percentile = optional_percentiles.as_ref()
+            .map(|percentiles| percentiles[index].to_string())
+            .unwrap_or_default();
+        let coverage = &result.coverages[index];
+
+        println!("{},{},{},{},{},{},{},{}", percentile, threshold, result.achieved_utilities[index],
+                 coverage.question_coverage, coverage.empty_questions, coverage.corpus_pruned,
+                 result.feasible[index], result.pareto_frontier.contains(&index));
+    }
+
+    match result.best_index {
+        Some(best_index) => eprintln!("Best threshold {} with accuracy {}", thresholds[best_index],
+                                      result.achieved_utilities[best_index]),
+        None => eprintln!("No threshold satisfies the constraints"),
+    }
+
+    Ok(())
+}
+
+pub fn prune(args: &PruneArgs) -> io::Result<()> {
+
+    let questions = read_questions(&args.questions)?;
+    let weight_per_name = read_weights(&args.weights)?;
+
+    let is_kept = |website: &String| {
+        weight_per_name.get(website).is_none_or(|weight| *weight >= args.threshold)
+    };
+
+    let mut writer: BufWriter<Box<dyn Write>> = match &args.output {
+        Some(path) => BufWriter::new(Box::new(File::create(path)?)),
+        None => BufWriter::new(Box::new(io::stdout())),
+    };
+
+    let mut num_pruned: usize = 0;
+    let mut num_emptied: usize = 0;
+
+    for question in questions {
+        let num_retrieved = question.retrieved_websites.len();
+        let (retrieved_websites, retrieved_answers): (Vec<String>, Vec<String>) = question
+            .retrieved_websites.into_iter()
+            .zip(question.retrieved_answers)
+            .filter(|(website, _)| is_kept(website))
+            .unzip();
+
+        num_pruned += num_retrieved - retrieved_websites.len();
+        if retrieved_websites.is_empty() {
+            num_emptied += 1;
+        }
+
+        let pruned_question = QuestionAnswering {
+            question: question.question,
+            correct_answers: question.correct_answers,
+            retrieved_websites,
+            retrieved_answers,
+            timestamp: question.timestamp,
+        };
+
+        serde_json::to_writer(&mut writer, &pruned_question)?;
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+
+    eprintln!("Pruned {} retrieved websites, {} questions have no retrieved websites left",
+              num_pruned, num_emptied);
+
+    Ok(())
+}
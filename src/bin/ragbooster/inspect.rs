@@ -0,0 +1,72 @@
+use ragbooster::mle::statistics::{group_statistics, source_statistics, SourceStatistics};
+
+use std::io;
+
+use crate::{read_dataset, weights_of, InspectArgs};
+
+
+pub fn inspect(args: &InspectArgs) -> io::Result<()> {
+
+    let dataset = read_dataset(&args.data)?;
+    let weights = weights_of(&dataset, args.weights.as_deref(), &args.training)?;
+    let k = args.training.k;
+
+    let statistics = source_statistics(&dataset.retrievals, dataset.corpus_size(), k);
+    print_ranking("websites", &dataset.website_indexer.strings(), &weights, &statistics, args.top);
+
+    if let Some((grouping, group_indexer)) = &dataset.grouping {
+        let num_groups = group_indexer.num_observed_strings();
+
+        // The weight of a group is the mean weight of its members with a weight
+        let mut weight_sums = vec![0.0_f64; num_groups];
+        let mut num_weighted = vec![0_usize; num_groups];
+        for (group, weight) in grouping.group_assignments().iter().zip(weights.iter()) {
+            if let Some(weight) = weight {
+                weight_sums[*group] += weight;
+                num_weighted[*group] += 1;
+            }
+        }
+        let group_weights: Vec<Option<f64>> = weight_sums.iter().zip(num_weighted.iter())
+            .map(|(sum, count)| if *count > 0 { Some(sum / *count as f64) } else { None })
+            .collect();
+
+        let statistics = group_statistics(&dataset.retrievals, grouping, k);
+        println!();
+        print_ranking("groups", &group_indexer.strings(), &group_weights, &statistics, args.top);
+    }
+
+    Ok(())
+}
+
+fn print_ranking(
+    kind: &str,
+    names: &[String],
+    weights: &[Option<f64>],
+    statistics: &[SourceStatistics],
+    top: usize,
+) {
+    let mut ranked: Vec<(usize, f64)> = weights.iter().enumerate()
+        .filter_map(|(index, weight)| weight.map(|weight| (index, weight)))
+        .collect();
+    // Most important first, ties keep the order of the names
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let num_shown = top.min(ranked.len());
+
+    println!("Most important {kind}:");
+    print_rows(&ranked[..num_shown], names, statistics);
+    println!();
+    println!("Least important {kind}:");
+    print_rows(&ranked[ranked.len() - num_shown..], names, statistics);
+}
+
+fn print_rows(ranked: &[(usize, f64)], names: &[String], statistics: &[SourceStatistics]) {
+    println!("  {:>8}  {:>10}  {:>8}  {:>12}  name",
+             "weight", "retrievals", "in top-k", "mean utility");
+    for (index, weight) in ranked {
+        let evidence = &statistics[*index];
+        println!("  {:>8.4}  {:>10}  {:>8}  {:>12.4}  {}", weight, evidence.num_retrievals,
+                 evidence.num_retrievals_in_top_k, evidence.mean_utility_contribution,
+                 names[*index]);
+    }
+}
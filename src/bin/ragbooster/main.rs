@@ -0,0 +1,431 @@
+mod evaluate;
+mod inspect;
+mod weights;
+
+#[cfg(feature = "arrow")]
+use ragbooster::arrow_io;
+use ragbooster::io::{
+    encode_predictions, encode_questions, read_group_json, read_questions, QuestionAnswering,
+    StringIndexer,
+};
+use ragbooster::mle::{self, Reduction};
+use ragbooster::mle::types::{Grouping, Retrieval, TimeDecay};
+use ragbooster::model::{learn_model, Hyperparameters, Model};
+use ragbooster::retrieval_log::convert_qa_json;
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Instant;
+
+use clap::{Args, Parser, Subcommand};
+use weights::{read_weights, write_weights, Format};
+
+
+#[derive(Parser)]
+#[command(name = "ragbooster", version,
+          about = "Learns the importance of the sources of a RAG model")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Learns importance weights from a QA retrieval JSONL file
+    Learn(LearnArgs),
+    /// Evaluates the top-k accuracy after pruning all websites whose weight is below a threshold
+    Evaluate(EvaluateArgs),
+    /// Evaluates the top-k accuracy and coverage for a range of pruning thresholds
+    Tune(TuneArgs),
+    /// Removes all websites whose weight is below a threshold from a QA retrieval JSONL file
+    Prune(PruneArgs),
+    /// Shows the most and least important websites (and groups) with their evidence
+    Inspect(InspectArgs),
+    /// Converts a QA retrieval JSONL file into the compact binary retrieval log format
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+struct DataArgs {
+    /// JSONL file with one question, its correct answers and its retrieved websites and answers
+    /// per line. With the `arrow` feature, `learn` also accepts a Parquet file (ending with
+    /// .parquet) with a `sources`, a `utility_contributions` and an optional `weight` column.
+    #[arg(long)]
+    questions: String,
+    /// JSONL file with one group (name and websites) per line, or a Parquet file with a `name`
+    /// and an `elements` column
+    #[arg(long)]
+    groups: Option<String>,
+}
+
+#[derive(Args)]
+struct TrainingArgs {
+    #[arg(long, default_value_t = 10)]
+    k: usize,
+    #[arg(long, default_value_t = 0.1)]
+    learning_rate: f64,
+    #[arg(long, default_value_t = 10)]
+    epochs: usize,
+    /// Number of threads to use, 0 uses all cores
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Strength of the L2 regularisation towards the prior
+    #[arg(long, default_value_t = 0.0, value_parser = non_negative)]
+    l2: f64,
+    /// Strength of the L1 regularisation
+    #[arg(long, default_value_t = 0.0, value_parser = non_negative)]
+    l1: f64,
+    /// Prior (and initial) weight of every website
+    #[arg(long, default_value_t = 0.5, value_parser = fraction)]
+    prior: f64,
+    /// Learns a single shared weight per group instead of averaging the weights of its members
+    #[arg(long, requires = "groups")]
+    tie_groups: bool,
+    /// Halves the weight of a question every this many days of its age, so that recent questions
+    /// dominate (every question needs a timestamp)
+    #[arg(long)]
+    half_life_days: Option<f64>,
+    /// Learns the weights as of this Unix timestamp (in seconds), ignoring all later questions,
+    /// e.g., for backtesting [default: the latest timestamp]
+    #[arg(long)]
+    as_of: Option<i64>,
+    /// Sums up the gradients in a fixed order, so that the weights are bit-identical for any
+    /// number of threads
+    #[arg(long)]
+    deterministic: bool,
+}
+
+#[derive(Args)]
+struct LearnArgs {
+    #[command(flatten)]
+    data: DataArgs,
+    #[command(flatten)]
+    training: TrainingArgs,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// File for the per-source weights, written to stdout if not given
+    #[arg(long)]
+    output: Option<String>,
+    /// File for the per-group weights
+    #[arg(long, requires = "groups")]
+    group_output: Option<String>,
+    /// File for the model with the weights, grouping, hyperparameters and training history, as
+    /// JSON if the file name ends with .json and in the compact binary format otherwise
+    #[arg(long)]
+    model: Option<String>,
+}
+
+#[derive(Args)]
+struct EvaluateArgs {
+    #[command(flatten)]
+    data: DataArgs,
+    /// Weights or model written by `learn`, which are learned on the questions if not given.
+    /// Websites without a weight are never pruned.
+    #[arg(long)]
+    weights: Option<String>,
+    #[command(flatten)]
+    training: TrainingArgs,
+    #[arg(long)]
+    threshold: f64,
+}
+
+#[derive(Args)]
+struct TuneArgs {
+    #[command(flatten)]
+    data: DataArgs,
+    /// Weights or model written by `learn`, which are learned on the questions if not given.
+    /// Websites without a weight are never pruned.
+    #[arg(long)]
+    weights: Option<String>,
+    #[command(flatten)]
+    training: TrainingArgs,
+    /// Comma-separated percentiles of the weights to use as thresholds [default: 0,5,...,95]
+    #[arg(long, value_delimiter = ',', conflicts_with = "thresholds", value_parser = percentile)]
+    percentiles: Option<Vec<f64>>,
+    /// Comma-separated thresholds
+    #[arg(long, value_delimiter = ',')]
+    thresholds: Option<Vec<f64>>,
+    /// Minimum fraction of the questions (of every group of questions) that keep k websites
+    #[arg(long, default_value_t = 0.0, value_parser = fraction)]
+    min_question_coverage: f64,
+    /// Maximum fraction of the websites to prune
+    #[arg(long, default_value_t = 1.0, value_parser = fraction)]
+    max_corpus_pruned: f64,
+}
+
+#[derive(Args)]
+struct PruneArgs {
+    /// JSONL file with one question, its correct answers and its retrieved websites and answers
+    /// per line
+    #[arg(long)]
+    questions: String,
+    /// Weights or model written by `learn`. Websites without a weight are never pruned.
+    #[arg(long)]
+    weights: String,
+    #[arg(long)]
+    threshold: f64,
+    /// JSONL file for the pruned questions, written to stdout if not given
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    #[command(flatten)]
+    data: DataArgs,
+    /// Weights or model written by `learn`, which are learned on the questions if not given
+    #[arg(long)]
+    weights: Option<String>,
+    #[command(flatten)]
+    training: TrainingArgs,
+    /// Number of most and least important websites (and groups) to show
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    data: DataArgs,
+    /// File for the retrieval log
+    #[arg(long)]
+    output: String,
+}
+
+/// The questions of a QA retrieval JSONL file, encoded for the library.
+struct Dataset {
+    questions: Vec<QuestionAnswering>,
+    retrievals: Vec<Retrieval>,
+    website_indexer: StringIndexer,
+    grouping: Option<(Grouping, StringIndexer)>,
+}
+
+impl Dataset {
+    fn corpus_size(&self) -> usize {
+        self.website_indexer.num_observed_strings()
+    }
+}
+
+fn read_dataset(data: &DataArgs) -> io::Result<Dataset> {
+    let (questions, retrievals, website_indexer) = if is_parquet(&data.questions) {
+        let (retrievals, website_indexer) = read_retrievals_parquet(&data.questions)?;
+        (Vec::new(), retrievals, website_indexer)
+    } else {
+        let questions = read_questions(&data.questions)?;
+        let (retrievals, website_indexer) = encode_questions(&questions)?;
+        (questions, retrievals, website_indexer)
+    };
+
+    eprintln!("Found {} questions and {} websites...",
+              retrievals.len(), website_indexer.num_observed_strings());
+
+    let grouping = match &data.groups {
+        Some(groups) if is_parquet(groups) => {
+            Some(read_groups_parquet(groups, &website_indexer.create_index())?)
+        },
+        Some(groups) => Some(read_group_json(groups, &website_indexer.create_index())?),
+        None => None,
+    };
+
+    Ok(Dataset { questions, retrievals, website_indexer, grouping })
+}
+
+fn is_parquet(path: &str) -> bool {
+    path.ends_with(".parquet")
+}
+
+#[cfg(feature = "arrow")]
+fn read_retrievals_parquet(path: &str) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+    arrow_io::read_retrievals_parquet(path, &arrow_io::RetrievalColumns::default())
+}
+
+#[cfg(feature = "arrow")]
+fn read_groups_parquet(
+    path: &str,
+    element_index: &HashMap<String, usize>,
+) -> io::Result<(Grouping, StringIndexer)> {
+    arrow_io::read_groups_parquet(path, element_index)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn read_retrievals_parquet(_path: &str) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+    Err(without_arrow())
+}
+
+#[cfg(not(feature = "arrow"))]
+fn read_groups_parquet(
+    _path: &str,
+    _element_index: &HashMap<String, usize>,
+) -> io::Result<(Grouping, StringIndexer)> {
+    Err(without_arrow())
+}
+
+#[cfg(not(feature = "arrow"))]
+fn without_arrow() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported,
+        "Parquet files are only supported when built with the `arrow` feature")
+}
+
+// The retrieved answers needed for majority voting are only available in QA retrieval JSONL files
+fn predictions_of(dataset: &Dataset) -> io::Result<Vec<Vec<usize>>> {
+    if dataset.questions.len() != dataset.retrievals.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "evaluating the accuracy needs the retrieved answers of a QA retrieval JSONL file"));
+    }
+    Ok(encode_predictions(&dataset.questions))
+}
+
+fn non_negative(value: &str) -> Result<f64, String> {
+    let number: f64 = value.parse().map_err(|_| format!("`{value}` is not a number"))?;
+    if !(number >= 0.0 && number.is_finite()) {
+        return Err(format!("`{value}` is not non-negative and finite"));
+    }
+    Ok(number)
+}
+
+fn fraction(value: &str) -> Result<f64, String> {
+    let fraction: f64 = value.parse().map_err(|_| format!("`{value}` is not a number"))?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(format!("`{value}` is not in [0, 1]"));
+    }
+    Ok(fraction)
+}
+
+fn percentile(value: &str) -> Result<f64, String> {
+    let percentile: f64 = value.parse().map_err(|_| format!("`{value}` is not a number"))?;
+    if !(0.0..=100.0).contains(&percentile) {
+        return Err(format!("`{value}` is not in [0, 100]"));
+    }
+    Ok(percentile)
+}
+
+fn n_jobs(threads: usize) -> usize {
+    if threads == 0 { num_cpus::get() } else { threads }
+}
+
+// The time decay of the weights of the questions, if requested
+fn time_decay_of(dataset: &Dataset, training: &TrainingArgs) -> io::Result<Option<TimeDecay>> {
+    if training.half_life_days.is_none() && training.as_of.is_none() {
+        return Ok(None);
+    }
+    if training.half_life_days.is_some_and(|days| days.is_nan() || days <= 0.0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the half life must be positive"));
+    }
+    if dataset.retrievals.iter().any(|retrieval| retrieval.timestamp().is_none()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "time decay needs a timestamp for every question"));
+    }
+    let half_life = training.half_life_days.map_or(f64::INFINITY, |days| days * 24.0 * 3600.0);
+    Ok(match training.as_of {
+        Some(as_of) => Some(TimeDecay::new(half_life, as_of)),
+        None => TimeDecay::as_of_latest(half_life, &dataset.retrievals),
+    })
+}
+
+// Learns the weights per website and, if the dataset is grouped, per group
+fn train(dataset: &Dataset, training: &TrainingArgs) -> io::Result<Model> {
+
+    let start_time = Instant::now();
+
+    let optional_time_decay = time_decay_of(dataset, training)?;
+
+    let hyperparameters = Hyperparameters {
+        k: training.k,
+        learning_rate: training.learning_rate,
+        num_epochs: training.epochs,
+        l2: training.l2,
+        l1: training.l1,
+        prior: training.prior,
+        tie_groups: training.tie_groups,
+        half_life_days: optional_time_decay.as_ref().and(training.half_life_days),
+        as_of: optional_time_decay.as_ref().map(TimeDecay::as_of),
+        reduction: if training.deterministic {
+            Reduction::Deterministic
+        } else {
+            Reduction::PerJob
+        },
+    };
+
+    let retrievals = match optional_time_decay {
+        Some(time_decay) => {
+            let retrievals = mle::decayed(dataset.retrievals.clone(), &time_decay)?;
+            eprintln!("Decayed the weights of {} questions as of {}...", retrievals.len(),
+                      time_decay.as_of());
+            retrievals
+        },
+        None => dataset.retrievals.clone(),
+    };
+
+    let model = learn_model(
+        retrievals,
+        dataset.website_indexer.strings(),
+        dataset.grouping.as_ref()
+            .map(|(grouping, group_indexer)| (grouping, group_indexer.strings())),
+        hyperparameters,
+        n_jobs(training.threads)
+    );
+
+    eprintln!("Computed importance for {} websites in {}ms", model.weights.len(),
+              start_time.elapsed().as_millis());
+
+    Ok(model)
+}
+
+// Reads the weights of the websites from a file (websites without a weight get None), or learns
+// them on the dataset
+fn weights_of(
+    dataset: &Dataset,
+    optional_weights_file: Option<&str>,
+    training: &TrainingArgs,
+) -> io::Result<Vec<Option<f64>>> {
+    match optional_weights_file {
+        Some(weights_file) => {
+            let weight_per_name = read_weights(weights_file)?;
+            Ok(dataset.website_indexer.strings().iter()
+                .map(|name| weight_per_name.get(name).copied())
+                .collect())
+        },
+        None => {
+            let model = train(dataset, training)?;
+            Ok(model.weights.into_iter().map(Some).collect())
+        },
+    }
+}
+
+fn learn(args: &LearnArgs) -> io::Result<()> {
+
+    let dataset = read_dataset(&args.data)?;
+    let model = train(&dataset, &args.training)?;
+
+    write_weights(&model.source_names, &model.weights, args.format, args.output.as_deref())?;
+
+    if let (Some(grouping), Some(group_output)) = (&model.grouping, &args.group_output) {
+        write_weights(&grouping.group_names, &grouping.group_weights, args.format,
+                      Some(group_output))?;
+    }
+
+    if let Some(model_file) = &args.model {
+        if model_file.ends_with(".json") {
+            model.save_json(model_file)?;
+        } else {
+            model.save_binary(model_file)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Learn(args) => learn(args),
+        Command::Evaluate(args) => evaluate::evaluate(args),
+        Command::Tune(args) => evaluate::tune(args),
+        Command::Prune(args) => evaluate::prune(args),
+        Command::Inspect(args) => inspect::inspect(args),
+        Command::Convert(args) => {
+            convert_qa_json(&args.data.questions, args.data.groups.as_deref(), &args.output)
+        },
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+use clap::ValueEnum;
+use ragbooster::model::Model;
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+    /// Requires an output file and the `arrow` feature
+    #[cfg(feature = "arrow")]
+    Parquet,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamedWeight {
+    name: String,
+    weight: f64,
+}
+
+pub fn write_weights(
+    names: &[String],
+    weights: &[f64],
+    format: Format,
+    optional_path: Option<&str>,
+) -> io::Result<()> {
+
+    #[cfg(feature = "arrow")]
+    if let Format::Parquet = format {
+        let path = optional_path.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+            "Parquet weights can only be written to a file"))?;
+        return ragbooster::arrow_io::write_weights_parquet(path, names, weights);
+    }
+
+    let mut writer: BufWriter<Box<dyn Write>> = match optional_path {
+        Some(path) => BufWriter::new(Box::new(File::create(path)?)),
+        None => BufWriter::new(Box::new(io::stdout())),
+    };
+
+    match format {
+        Format::Json => {
+            let named_weights: Vec<NamedWeight> = names.iter().zip(weights.iter())
+                .map(|(name, weight)| NamedWeight { name: name.clone(), weight: *weight })
+                .collect();
+            serde_json::to_writer_pretty(&mut writer, &named_weights)?;
+            writeln!(writer)?;
+        },
+        Format::Csv => {
+            writeln!(writer, "name,weight")?;
+            for (name, weight) in names.iter().zip(weights.iter()) {
+                writeln!(writer, "{},{}", csv_field(name), weight)?;
+            }
+        },
+        #[cfg(feature = "arrow")]
+        Format::Parquet => unreachable!(),
+    }
+
+    writer.flush()
+}
+
+/// Reads weights written by `write_weights` (the format is determined by the file extension) or
+/// the weights of a model.
+pub fn read_weights(path: &str) -> io::Result<HashMap<String, f64>> {
+    #[cfg(feature = "arrow")]
+    if path.ends_with(".parquet") {
+        let (names, weights) = ragbooster::arrow_io::read_weights_parquet(path)?;
+        return Ok(names.into_iter().zip(weights).collect());
+    }
+
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // Models are either binary or JSON objects, whereas weights are stored as JSON arrays
+    let first_byte = reader.fill_buf()?.iter().find(|byte| !byte.is_ascii_whitespace()).copied();
+    if !path.ends_with(".csv") && first_byte != Some(b'[') {
+        let model = Model::load(path)?;
+        return Ok(model.source_names.into_iter().zip(model.weights).collect());
+    }
+
+    let named_weights: Vec<NamedWeight> = if path.ends_with(".csv") {
+        let mut named_weights = Vec::new();
+        for (line_number, line) in reader.lines().enumerate().skip(1) {
+            let fields = parse_csv_record(&line?);
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData,
+                format!("expected a name and a weight in line {} of {}", line_number + 1, path));
+
+            if fields.len() != 2 {
+                return Err(invalid());
+            }
+            let weight: f64 = fields[1].parse().map_err(|_| invalid())?;
+            named_weights.push(NamedWeight { name: fields[0].clone(), weight });
+        }
+        named_weights
+    } else {
+        serde_json::from_reader(reader)?
+    };
+
+    Ok(named_weights.into_iter()
+        .map(|named_weight| (named_weight.name, named_weight.weight))
+        .collect())
+}
+
+// Quotes a CSV field if necessary, doubling any quotes within it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn parse_csv_record(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                fields.last_mut().unwrap().push('"');
+                chars.next();
+            },
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => fields.push(String::new()),
+            (c, _) => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_round_trip() {
+        for name in ["www.xe.com", "a,b", "say \"hi\"", ""] {
+            let line = format!("{},0.5", csv_field(name));
+            assert_eq!(parse_csv_record(&line), vec![name.to_owned(), "0.5".to_owned()]);
+        }
+    }
+}
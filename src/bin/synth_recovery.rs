@@ -0,0 +1,76 @@
+use ragbooster::mle as mle;
+use ragbooster::synthetic::{roc_auc, Synthetic};
+use std::time::Instant;
+
+
+const NUM_QUESTIONS: usize = 500;
+const CORPUS_SIZE: usize = 500;
+const RETRIEVAL_DEPTH: usize = 20;
+const NUM_GROUPS: usize = 50;
+const SEEDS: [u64; 3] = [1, 2, 3];
+
+// Fraction of bad sources among the sources with the lowest weights, where we prune as many
+// sources as there are bad ones
+fn precision_at_pruned(v: &[f64], is_bad: &[bool]) -> f64 {
+    let num_bad = is_bad.iter().filter(|is_bad| **is_bad).count();
+
+    let mut order: Vec<usize> = (0..v.len()).collect();
+    order.sort_by(|a, b| v[*a].total_cmp(&v[*b]));
+
+    let num_pruned_bad = order[..num_bad].iter().filter(|source| is_bad[**source]).count();
+    num_pruned_bad as f64 / num_bad as f64
+}
+
+// cargo run --release --bin synth_recovery > recovery.csv
+fn main() {
+
+    println!("seed,zipf_exponent,label_noise,k,learning_rate,num_epochs,grouped,roc_auc,\
+              precision_at_pruned,duration");
+
+    for seed in SEEDS {
+        for zipf_exponent in [0.0, 1.0, 1.5] {
+            for label_noise in [0.0, 0.1] {
+                let dataset = Synthetic::new(NUM_QUESTIONS, CORPUS_SIZE, RETRIEVAL_DEPTH, seed)
+                    .with_zipf_exponent(zipf_exponent)
+                    .with_label_noise(label_noise)
+                    .with_groups(NUM_GROUPS)
+                    .generate();
+
+                let is_bad = dataset.is_bad();
+                let is_good: Vec<bool> = is_bad.iter().map(|is_bad| !is_bad).collect();
+
+                for k in [1, 5, 10] {
+                    for learning_rate in [0.01, 0.1, 1.0] {
+                        for num_epochs in [10, 50] {
+                            for grouped in [false, true] {
+                                let optional_grouping = if grouped {
+                                    dataset.grouping.as_ref()
+                                } else {
+                                    None
+                                };
+
+                                let start_time = Instant::now();
+                                let v = mle::mle_importance(
+                                    dataset.retrievals.clone(),
+                                    CORPUS_SIZE,
+                                    optional_grouping,
+                                    None,
+                                    k,
+                                    learning_rate,
+                                    num_epochs,
+                                    1
+                                );
+                                let duration = (Instant::now() - start_time).as_millis();
+
+                                println!("{},{},{},{},{},{},{},{},{},{}", seed, zipf_exponent,
+                                         label_noise, k, learning_rate, num_epochs, grouped,
+                                         roc_auc(&v, &is_good), precision_at_pruned(&v, &is_bad),
+                                         duration);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
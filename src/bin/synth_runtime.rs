@@ -1,19 +1,14 @@
-extern crate rand;
-
 use ragbooster::mle as mle;
-use mle::types::Retrieval;
+use ragbooster::synthetic::Synthetic;
 use std::time::Instant;
-use rand::thread_rng;
-use rand::distributions::{Distribution, Bernoulli};
 
 const LEARNING_RATE: f64 = 0.1;
 const NUM_STEPS: usize = 10;
+const SEED: u64 = 42;
 
 fn main() {
 
-    let mut rng = thread_rng();
     let prob_of_right_answer = 0.25;
-    let bernoulli = Bernoulli::new(prob_of_right_answer).unwrap();
     let corpus_size = 1000;
     let num_repetitions = 7;
 
@@ -24,24 +19,13 @@ fn main() {
     //for N in [1_000, 10_000, 100_000, 1_000_000] {
     for N in [1_000_000] {
         for (k, d) in [(10, 50), (20, 100)] {
-            let all_retrieved: Vec<Retrieval> = (0..N)
-                .map(|_| {
-                    let retrieved: Vec<usize> =
-                        rand::seq::index::sample(&mut rng, corpus_size, d).into_vec();
-
-                    let utilities: Vec<f64> = (0..d)
-                        .map(|_| {
-                            if bernoulli.sample(&mut rng) {
-                                1.0
-                            } else {
-                                0.0
-                            }
-                        })
-                        .collect();
-
-                    Retrieval::new(retrieved, utilities)
-                })
-                .collect();
+            // Uniformly retrieved sources of the same quality
+            let all_retrieved = Synthetic::new(N, corpus_size, d, SEED)
+                .with_quality_mix(0.0, 0.0)
+                .with_qualities(prob_of_right_answer, prob_of_right_answer, prob_of_right_answer)
+                .with_zipf_exponent(0.0)
+                .generate()
+                .retrievals;
 
             for num_threads in [1, 2, 4] {
                 for _ in 0..num_repetitions {
@@ -52,6 +36,7 @@ fn main() {
                         all_retrieved_copy,
                         corpus_size,
                         None,
+                        None,
                         k,
                         LEARNING_RATE,
                         NUM_STEPS,
@@ -0,0 +1,282 @@
+use crate::mle::streaming::RetrievalStream;
+use crate::mle::types::{Grouping, Retrieval};
+
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A question with its correct answers and the answers derived from each retrieved website, as
+/// stored (one per line) in the QA retrieval JSONL files.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuestionAnswering {
+    pub question: String,
+    pub correct_answers: Vec<String>,
+    pub retrieved_websites: Vec<String>,
+    pub retrieved_answers: Vec<String>,
+    /// When the question was asked, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+}
+
+/// A named group of sources, as stored (one per line) in the group JSONL files.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Group {
+    pub name: String,
+    pub elements: Vec<String>,
+}
+
+/// Assigns ids to strings in their lexicographic order.
+#[derive(Default)]
+pub struct StringIndexer {
+    distinct_strings: BTreeSet<String>,
+}
+
+impl StringIndexer {
+    pub fn new() -> Self {
+        Self { distinct_strings: BTreeSet::new() }
+    }
+
+    pub fn observe_all(&mut self, strs: &[String]) {
+        for a_str in strs {
+            self.observe(a_str.clone());
+        }
+    }
+
+    pub fn num_observed_strings(&self) -> usize {
+        self.distinct_strings.len()
+    }
+
+    pub fn observe(&mut self, str: String) {
+        self.distinct_strings.insert(str);
+    }
+
+    pub fn create_index(&self) -> HashMap<String, usize> {
+        let mut index = HashMap::new();
+        for (id, website) in self.distinct_strings.iter().enumerate() {
+            index.insert((*website).to_owned(), id);
+        }
+        index
+    }
+
+    pub fn create_reverse_index(&self) -> HashMap<usize, String> {
+        let mut index = HashMap::new();
+        for (id, website) in self.distinct_strings.iter().enumerate() {
+            index.insert(id, (*website).to_owned());
+        }
+        index
+    }
+
+    /// The observed strings, ordered by their ids.
+    pub fn strings(&self) -> Vec<String> {
+        self.distinct_strings.iter().cloned().collect()
+    }
+}
+
+/// Reads the questions of a QA retrieval JSONL file, and fails with `InvalidData` (naming the
+/// line) for a line which is not a question.
+pub fn read_questions(path: &str) -> io::Result<Vec<QuestionAnswering>> {
+    questions_of(path)?.collect()
+}
+
+/// Reads a QA retrieval JSONL file, where the utility contribution of a retrieved website is 1.0
+/// if its answer is one of the correct answers and 0.0 otherwise.
+pub fn read_qa_json(path: &str) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+    let inputs = read_questions(path)?;
+    encode_questions(&inputs)
+}
+
+/// Encodes the questions as retrievals, and fails with `InvalidData` (naming the line) for a
+/// question without correct answers or with a different number of websites and answers.
+pub fn encode_questions(
+    inputs: &[QuestionAnswering]
+) -> io::Result<(Vec<Retrieval>, StringIndexer)> {
+
+    let mut website_indexer = StringIndexer::new();
+
+    for (line, input) in inputs.iter().enumerate() {
+        check_question(line, input)?;
+        website_indexer.observe_all(&input.retrieved_websites);
+    }
+
+    let website_index = website_indexer.create_index();
+
+    let all_retrieved = inputs.iter()
+        .map(|input| encode_question(input, &website_index))
+        .collect();
+
+    Ok((all_retrieved, website_indexer))
+}
+
+// Lines are counted from one, like in editors
+fn check_question(line: usize, input: &QuestionAnswering) -> io::Result<()> {
+    // Questions without retrieved websites (e.g., after pruning) have a utility of zero
+    if input.correct_answers.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("correct_answers empty in line {}", line + 1)));
+    }
+    if input.retrieved_websites.len() != input.retrieved_answers.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("inconsistent number of websites and answers in line {}", line + 1)));
+    }
+    Ok(())
+}
+
+fn encode_question(input: &QuestionAnswering, website_index: &HashMap<String, usize>) -> Retrieval {
+
+    let samples: Vec<usize> = input.retrieved_websites.iter()
+        .map(|website| *website_index.get(website).unwrap())
+        .collect();
+
+    let costs: Vec<f64> = input.retrieved_answers.iter()
+        .map(|answer| {
+            if input.correct_answers.contains(answer) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let retrieval = Retrieval::new(samples, costs);
+    match input.timestamp {
+        Some(timestamp) => retrieval.at(timestamp),
+        None => retrieval,
+    }
+}
+
+/// Reads the questions of a QA retrieval JSONL file line by line whenever the retrievals are
+/// needed (e.g., once per epoch of `mle_importance_streaming`), so that only the website names are
+/// held in memory.
+pub struct QaJsonStream {
+    path: String,
+    website_index: HashMap<String, usize>,
+}
+
+impl QaJsonStream {
+    /// Indexes the websites in a first pass over the file.
+    pub fn open(path: &str) -> io::Result<(Self, StringIndexer)> {
+        let mut website_indexer = StringIndexer::new();
+
+        for (line, input) in questions_of(path)?.enumerate() {
+            let input = input?;
+            check_question(line, &input)?;
+            website_indexer.observe_all(&input.retrieved_websites);
+        }
+
+        let stream = Self { path: path.to_owned(), website_index: website_indexer.create_index() };
+        Ok((stream, website_indexer))
+    }
+}
+
+impl RetrievalStream for QaJsonStream {
+    fn for_each_chunk(
+        &self,
+        chunk_size: usize,
+        consume: &mut dyn FnMut(Vec<Retrieval>),
+    ) -> io::Result<()> {
+        let mut chunk = Vec::with_capacity(chunk_size);
+
+        for input in questions_of(&self.path)? {
+            chunk.push(encode_question(&input?, &self.website_index));
+            if chunk.len() == chunk_size {
+                consume(std::mem::replace(&mut chunk, Vec::with_capacity(chunk_size)));
+            }
+        }
+
+        if !chunk.is_empty() {
+            consume(chunk);
+        }
+        Ok(())
+    }
+}
+
+fn questions_of(path: &str) -> io::Result<impl Iterator<Item = io::Result<QuestionAnswering>>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader.lines().enumerate().map(|(line, text)| {
+        serde_json::from_str(&text?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData,
+            format!("invalid question in line {}: {}", line + 1, error)))
+    }))
+}
+
+/// Encodes the retrieved answers of every question as ids in the order of their first appearance,
+/// which is what majority voting over the answers needs.
+pub fn encode_predictions(inputs: &[QuestionAnswering]) -> Vec<Vec<usize>> {
+    inputs.iter()
+        .map(|input| {
+            let mut distinct_answers: Vec<&String> = Vec::new();
+            input.retrieved_answers.iter()
+                .map(|answer| {
+                    match distinct_answers.iter().position(|distinct| *distinct == answer) {
+                        Some(id) => id,
+                        None => {
+                            distinct_answers.push(answer);
+                            distinct_answers.len() - 1
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn read_group_json(
+    path: &str,
+    element_index: &HashMap<String, usize>
+) -> io::Result<(Grouping, StringIndexer)> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut groups: Vec<Group> = Vec::new();
+
+    for line in reader.lines() {
+        let group: Group = serde_json::from_str(&line?)?;
+        groups.push(group);
+    }
+
+    encode_groups(groups, element_index)
+}
+
+/// Assigns every element to its group, where the groups get ids in the lexicographic order of
+/// their names. Group elements that are not in `element_index` (e.g., websites that were pruned
+/// from the questions) are skipped, elements without a group are `InvalidData`.
+pub fn encode_groups(
+    groups: Vec<Group>,
+    element_index: &HashMap<String, usize>
+) -> io::Result<(Grouping, StringIndexer)> {
+
+    let mut group_name_indexer = StringIndexer::new();
+    for group in &groups {
+        group_name_indexer.observe(group.name.clone());
+    }
+
+    let group_name_index = group_name_indexer.create_index();
+    let mut group_per_samples = vec![0; element_index.len()];
+    let mut is_mapped = vec![false; element_index.len()];
+
+    for group in groups {
+        let group_id = group_name_index[&group.name];
+        for element in group.elements {
+            if let Some(element_id) = element_index.get(&element) {
+                group_per_samples[*element_id] = group_id;
+                is_mapped[*element_id] = true;
+            }
+        }
+    }
+
+    let mut unmapped: Vec<&String> = element_index.iter()
+        .filter(|(_, element_id)| !is_mapped[**element_id])
+        .map(|(element, _)| element)
+        .collect();
+    if !unmapped.is_empty() {
+        unmapped.sort();
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("Group information missing for {} elements, e.g., {}",
+                    unmapped.len(), unmapped[0])));
+    }
+
+    Ok((Grouping::new(group_name_index.len(), group_per_samples), group_name_indexer))
+}
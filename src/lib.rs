@@ -1,25 +1,33 @@
 use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
 use pyo3::types::PyList;
 use pyo3::types::PyDict;
 
 use itertools::Itertools;
 
+#[cfg(feature = "arrow")]
+pub mod arrow_io;
+pub mod io;
 pub mod mle;
+pub mod model;
+pub mod online;
+pub mod pruning;
+pub mod reranking;
+pub mod retrieval_log;
+pub mod synthetic;
 
-use mle::types::{Grouping, Retrieval};
+use mle::types::{Grouping, Regularisation, Retrieval, TimeDecay};
+use mle::Reduction;
+use mle::statistics::Bootstrap;
+use model::{Hyperparameters, Model};
+use online::{OnlineLearner, OnlineState};
+use pruning::{Aggregation, CoverageConstraints, CrossValidation, Thresholds};
+use reranking::Reranking;
 
-#[pyfunction]
-fn learn_importance(
-    py_retrievals: &PyList,
-    k: usize,
-    learning_rate: f64,
-    num_epochs: usize,
-    n_jobs: Option<isize>,
-    grouping: Option<&PyList>,
-) -> PyResult<Vec<f64>> {
+fn decode_retrievals(py_retrievals: &PyList) -> PyResult<(Vec<Retrieval>, usize)> {
 
     let mut retrievals: Vec<Retrieval> = Vec::with_capacity(py_retrievals.len());
-    let mut corpus_size: usize = 0;
+    let mut max_retrieved: usize = 0;
 
     // TODO add helpful error messages
     for py_retrieval in py_retrievals.iter() {
@@ -27,52 +35,795 @@ fn learn_importance(
             .get_item("retrieved").unwrap()
             .downcast::<PyList>()?.extract().unwrap();
 
-        if !retrieved.is_empty() {
-            if *retrieved.iter().max().unwrap() > corpus_size {
-                corpus_size = *retrieved.iter().max().unwrap();
-            }
+        if !retrieved.is_empty() && *retrieved.iter().max().unwrap() > max_retrieved {
+            max_retrieved = *retrieved.iter().max().unwrap();
         }
 
         let utility_contributions: Vec<f64> = py_retrieval.downcast::<PyDict>()?
             .get_item("utility_contributions").unwrap()
             .downcast::<PyList>()?.extract().unwrap();
 
-        retrievals.push(Retrieval::new(retrieved, utility_contributions));
+        let weight: f64 = match py_retrieval.downcast::<PyDict>()?.get_item("weight") {
+            Some(py_weight) => py_weight.extract()?,
+            None => 1.0,
+        };
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(PyValueError::new_err(format!(
+                "Weight must be non-negative and finite, got {weight}")));
+        }
+
+        let retrieval = Retrieval::with_weight(retrieved, utility_contributions, weight);
+        let retrieval = match py_retrieval.downcast::<PyDict>()?.get_item("timestamp") {
+            Some(py_timestamp) => retrieval.at(py_timestamp.extract()?),
+            None => retrieval,
+        };
+
+        retrievals.push(retrieval);
     }
 
-    let decoded_grouping = if let Some(py_grouping) = grouping {
+    check_total_weight(&retrievals)?;
+
+    Ok((retrievals, max_retrieved + 1))
+}
+
+// The gradient is normalised by the total weight, so it must be positive, which also rules out an
+// empty list of retrievals
+fn check_total_weight(retrievals: &[Retrieval]) -> PyResult<()> {
+    if retrievals.iter().map(|retrieval| retrieval.weight).sum::<f64>() <= 0.0 {
+        return Err(PyValueError::new_err("The total weight of the retrievals must be positive"));
+    }
+    Ok(())
+}
+
+// Predictions are only available if every retrieval has a "predictions" entry
+fn decode_predictions(py_retrievals: &PyList) -> PyResult<Option<Vec<Vec<usize>>>> {
+
+    let mut all_predictions: Vec<Vec<usize>> = Vec::with_capacity(py_retrievals.len());
+
+    for py_retrieval in py_retrievals.iter() {
+        match py_retrieval.downcast::<PyDict>()?.get_item("predictions") {
+            Some(py_predictions) => all_predictions.push(py_predictions.extract()?),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(all_predictions))
+}
+
+fn decode_scores(py_retrievals: &PyList) -> PyResult<Vec<Vec<f64>>> {
+    py_retrievals.iter()
+        .map(|py_retrieval| {
+            match py_retrieval.downcast::<PyDict>()?.get_item("scores") {
+                Some(py_scores) => py_scores.extract(),
+                None => Err(PyValueError::new_err("Every retrieval needs retriever scores")),
+            }
+        })
+        .collect()
+}
+
+fn decode_reranking(strategy: &str, alpha: Option<f64>, seed: Option<u64>) -> PyResult<Reranking> {
+    match strategy {
+        "product" => Ok(Reranking::Product),
+        "interpolation" => Ok(Reranking::Interpolation(alpha.unwrap_or(0.5))),
+        "sampling" => Ok(Reranking::Sampling(seed.unwrap_or(0))),
+        _ => Err(PyValueError::new_err(
+            "strategy must be one of 'product', 'interpolation' or 'sampling'")),
+    }
+}
+
+fn decode_grouping(grouping: Option<&PyList>) -> PyResult<Option<Grouping>> {
+    if let Some(py_grouping) = grouping {
         let group_assignments: Vec<usize> = py_grouping.downcast::<PyList>()?.extract().unwrap();
         let num_groups = group_assignments
             .iter()
             .unique() // TODO not sure if hashing things is the fastest option here
             .count();
 
-        Some(Grouping::new(num_groups, group_assignments))
+        Ok(Some(Grouping::new(num_groups, group_assignments)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn decode_regularisation(
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+    corpus_size: usize,
+) -> PyResult<Option<Regularisation>> {
+    if l2.is_some() || l1.is_some() || prior.is_some() {
+        let (l2, l1, prior) = (l2.unwrap_or(0.0), l1.unwrap_or(0.0), prior.unwrap_or(0.5));
+        check_regularisation(l2, l1, prior)?;
+        Ok(Some(Regularisation::uniform(l2, l1, prior, corpus_size)))
     } else {
-        None
+        Ok(None)
+    }
+}
+
+fn check_regularisation(l2: f64, l1: f64, prior: f64) -> PyResult<()> {
+    if !(l2 >= 0.0 && l2.is_finite() && l1 >= 0.0 && l1.is_finite()) {
+        return Err(PyValueError::new_err("l2 and l1 must be non-negative and finite"));
+    }
+    if !(0.0..=1.0).contains(&prior) {
+        return Err(PyValueError::new_err("The prior must be in [0, 1]"));
+    }
+    Ok(())
+}
+
+// Without `as_of`, the weights decay relative to the latest retrieval, without `half_life`, the
+// retrievals after `as_of` are only dropped
+fn decode_time_decay(
+    retrievals: &[Retrieval],
+    half_life: Option<f64>,
+    as_of: Option<i64>,
+) -> PyResult<Option<TimeDecay>> {
+    if half_life.is_none() && as_of.is_none() {
+        return Ok(None);
+    }
+    if half_life.is_some_and(|half_life| half_life.is_nan() || half_life <= 0.0) {
+        return Err(PyValueError::new_err("The half life must be positive"));
+    }
+    if retrievals.iter().any(|retrieval| retrieval.timestamp().is_none()) {
+        return Err(PyValueError::new_err("Time decay needs a timestamp for every retrieval"));
+    }
+    let half_life = half_life.unwrap_or(f64::INFINITY);
+    Ok(match as_of {
+        Some(as_of) => Some(TimeDecay::new(half_life, as_of)),
+        None => TimeDecay::as_of_latest(half_life, retrievals),
+    })
+}
+
+fn decode_bootstrap(
+    num_resamples: Option<usize>,
+    confidence_level: Option<f64>,
+    seed: Option<u64>,
+) -> PyResult<Option<Bootstrap>> {
+    let num_resamples = match num_resamples {
+        Some(num_resamples) => num_resamples,
+        None => return Ok(None),
     };
+    if num_resamples == 0 {
+        return Err(PyValueError::new_err("Need at least one bootstrap resample"));
+    }
+    let confidence_level = confidence_level.unwrap_or(0.95);
+    if !(confidence_level > 0.0 && confidence_level < 1.0) {
+        return Err(PyValueError::new_err("The confidence level must be in (0, 1)"));
+    }
+    Ok(Some(Bootstrap::new(num_resamples, confidence_level, seed.unwrap_or(0))))
+}
 
-    let grouping_reference = decoded_grouping.as_ref();
+fn decode_reduction(deterministic: Option<bool>) -> Reduction {
+    if deterministic.unwrap_or(false) { Reduction::Deterministic } else { Reduction::PerJob }
+}
 
-    let decoded_n_jobs: usize = n_jobs
+fn decode_n_jobs(n_jobs: Option<isize>) -> usize {
+    n_jobs
         .map(|n| if n < 1 { num_cpus::get() } else { n as usize })
-        .unwrap_or(1);
+        .unwrap_or(1)
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn learn_importance(
+    py_retrievals: &PyList,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: Option<isize>,
+    grouping: Option<&PyList>,
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+    tie_groups: Option<bool>,
+    half_life: Option<f64>,
+    as_of: Option<i64>,
+    deterministic: Option<bool>,
+) -> PyResult<Vec<f64>> {
+
+    let (mut retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    if let Some(time_decay) = decode_time_decay(&retrievals, half_life, as_of)? {
+        retrievals = mle::decayed(retrievals, &time_decay)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+    }
+    let decoded_grouping = decode_grouping(grouping)?;
+    if decoded_grouping.as_ref()
+        .is_some_and(|grouping| grouping.group_assignments().len() != corpus_size) {
+        return Err(PyValueError::new_err("Need a group for every retrieved source"));
+    }
+    let regularisation = decode_regularisation(l2, l1, prior, corpus_size)?;
+
+    let reduction = decode_reduction(deterministic);
 
-    let v = mle::mle_importance(
+    let v = match (decoded_grouping, tie_groups.unwrap_or(false)) {
+        (Some(grouping), true) => {
+            let (_, v) = mle::train_tied(
+                retrievals,
+                corpus_size,
+                &grouping,
+                regularisation.as_ref(),
+                k,
+                learning_rate,
+                num_epochs,
+                decode_n_jobs(n_jobs),
+                reduction
+            );
+            v
+        },
+        (decoded_grouping, _) => {
+            let learn = match reduction {
+                Reduction::PerJob => mle::mle_importance,
+                Reduction::Deterministic => mle::mle_importance_deterministic,
+            };
+            learn(
+                retrievals,
+                corpus_size,
+                decoded_grouping.as_ref(),
+                regularisation.as_ref(),
+                k,
+                learning_rate,
+                num_epochs,
+                decode_n_jobs(n_jobs)
+            )
+        }
+    };
+
+    Ok(v)
+}
+
+/// Learns one shared weight per group (like `learn_importance` with `tie_groups=True`) and returns
+/// the weights of the groups together with the corresponding weights of the sources.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn learn_group_importance(
+    py_retrievals: &PyList,
+    grouping: &PyList,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: Option<isize>,
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+    deterministic: Option<bool>,
+) -> PyResult<(Vec<f64>, Vec<f64>)> {
+
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    let decoded_grouping = decode_grouping(Some(grouping))?.unwrap();
+    if decoded_grouping.group_assignments().len() != corpus_size {
+        return Err(PyValueError::new_err("Need a group for every retrieved source"));
+    }
+    let regularisation = decode_regularisation(l2, l1, prior, corpus_size)?;
+
+    Ok(mle::train_tied(
         retrievals,
-        corpus_size + 1,
-        grouping_reference,
+        corpus_size,
+        &decoded_grouping,
+        regularisation.as_ref(),
         k,
         learning_rate,
         num_epochs,
+        decode_n_jobs(n_jobs),
+        decode_reduction(deterministic)
+    ))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn learn_importance_with_statistics(
+    py: Python,
+    py_retrievals: &PyList,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: Option<isize>,
+    grouping: Option<&PyList>,
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+    num_bootstrap_resamples: Option<usize>,
+    confidence_level: Option<f64>,
+    seed: Option<u64>,
+) -> PyResult<(Vec<f64>, Vec<PyObject>)> {
+
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    let decoded_grouping = decode_grouping(grouping)?;
+    if decoded_grouping.as_ref()
+        .is_some_and(|grouping| grouping.group_assignments().len() != corpus_size) {
+        return Err(PyValueError::new_err("Need a group for every retrieved source"));
+    }
+    let regularisation = decode_regularisation(l2, l1, prior, corpus_size)?;
+
+    let bootstrap = decode_bootstrap(num_bootstrap_resamples, confidence_level, seed)?;
+
+    let (v, statistics) = mle::statistics::mle_importance_with_statistics(
+        retrievals,
+        corpus_size,
+        decoded_grouping.as_ref(),
+        regularisation.as_ref(),
+        k,
+        learning_rate,
+        num_epochs,
+        bootstrap.as_ref(),
+        decode_n_jobs(n_jobs)
+    );
+
+    let mut py_statistics = Vec::with_capacity(statistics.len());
+    for source_statistics in statistics {
+        let py_source_statistics = PyDict::new(py);
+        py_source_statistics.set_item("num_retrievals", source_statistics.num_retrievals)?;
+        py_source_statistics.set_item(
+            "num_retrievals_in_top_k", source_statistics.num_retrievals_in_top_k)?;
+        py_source_statistics.set_item(
+            "mean_utility_contribution", source_statistics.mean_utility_contribution)?;
+        py_source_statistics.set_item(
+            "confidence_interval", source_statistics.confidence_interval)?;
+        py_statistics.push(py_source_statistics.to_object(py));
+    }
+
+    Ok((v, py_statistics))
+}
+
+#[pyfunction]
+fn tune_pruning_thresholds(
+    py_retrievals: &PyList,
+    weights: Vec<f64>,
+    thresholds: Vec<f64>,
+    k: usize,
+    normalize: Option<bool>,
+    n_jobs: Option<isize>,
+) -> PyResult<(Vec<f64>, f64, f64, usize)> {
+
+    let (retrievals, _) = decode_retrievals(py_retrievals)?;
+    let predictions = decode_predictions(py_retrievals)?;
+    if thresholds.is_empty() {
+        return Err(PyValueError::new_err("Need at least one threshold"));
+    }
+
+    let aggregation = if predictions.is_some() {
+        Aggregation::MajorityVote
+    } else {
+        Aggregation::Additive
+    };
+
+    let result = pruning::tune_pruning_threshold(
+        &retrievals,
+        predictions.as_deref(),
+        &weights,
+        k,
+        &thresholds,
+        aggregation,
+        normalize.unwrap_or(false),
+        decode_n_jobs(n_jobs)
+    );
+
+    Ok((result.achieved_utilities, result.best_utility, result.best_threshold, result.best_index))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn tune_pruning_thresholds_constrained(
+    py: Python,
+    py_retrievals: &PyList,
+    weights: Vec<f64>,
+    thresholds: Vec<f64>,
+    k: usize,
+    min_question_coverage: Option<f64>,
+    max_corpus_pruned: Option<f64>,
+    question_grouping: Option<&PyList>,
+    n_jobs: Option<isize>,
+) -> PyResult<(Vec<f64>, Vec<PyObject>, Option<usize>, Vec<usize>)> {
+
+    let (retrievals, _) = decode_retrievals(py_retrievals)?;
+    let predictions = decode_predictions(py_retrievals)?;
+    if thresholds.is_empty() {
+        return Err(PyValueError::new_err("Need at least one threshold"));
+    }
+    let decoded_question_grouping = decode_grouping(question_grouping)?;
+    if decoded_question_grouping.as_ref()
+        .is_some_and(|grouping| grouping.group_assignments().len() != retrievals.len()) {
+        return Err(PyValueError::new_err("Need a group for every question"));
+    }
+
+    let aggregation = if predictions.is_some() {
+        Aggregation::MajorityVote
+    } else {
+        Aggregation::Additive
+    };
+
+    let min_question_coverage = min_question_coverage.unwrap_or(0.0);
+    let max_corpus_pruned = max_corpus_pruned.unwrap_or(1.0);
+    if !(0.0..=1.0).contains(&min_question_coverage) || !(0.0..=1.0).contains(&max_corpus_pruned) {
+        return Err(PyValueError::new_err(
+            "min_question_coverage and max_corpus_pruned must be in [0, 1]"));
+    }
+    let constraints = CoverageConstraints::new(min_question_coverage, max_corpus_pruned);
+
+    let result = pruning::tune_pruning_threshold_constrained(
+        &retrievals,
+        predictions.as_deref(),
+        &weights,
+        k,
+        &thresholds,
+        aggregation,
+        &constraints,
+        decoded_question_grouping.as_ref(),
+        decode_n_jobs(n_jobs)
+    );
+
+    let mut py_coverages = Vec::with_capacity(result.coverages.len());
+    for (coverage, feasible) in result.coverages.iter().zip(result.feasible.iter()) {
+        let py_coverage = PyDict::new(py);
+        py_coverage.set_item("question_coverage", coverage.question_coverage)?;
+        py_coverage.set_item("min_group_coverage", coverage.min_group_coverage)?;
+        py_coverage.set_item("empty_questions", coverage.empty_questions)?;
+        py_coverage.set_item("corpus_pruned", coverage.corpus_pruned)?;
+        py_coverage.set_item("feasible", *feasible)?;
+        py_coverages.push(py_coverage.to_object(py));
+    }
+
+    Ok((result.achieved_utilities, py_coverages, result.best_index, result.pareto_frontier))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn cross_validate_pruning(
+    py_retrievals: &PyList,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    percentiles: Option<Vec<f64>>,
+    thresholds: Option<Vec<f64>>,
+    num_folds: Option<usize>,
+    seed: Option<u64>,
+    n_jobs: Option<isize>,
+    grouping: Option<&PyList>,
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+) -> PyResult<(Vec<Vec<f64>>, Vec<f64>, usize)> {
+
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    let predictions = decode_predictions(py_retrievals)?;
+    let decoded_grouping = decode_grouping(grouping)?;
+    if decoded_grouping.as_ref()
+        .is_some_and(|grouping| grouping.group_assignments().len() != corpus_size) {
+        return Err(PyValueError::new_err("Need a group for every retrieved source"));
+    }
+    let regularisation = decode_regularisation(l2, l1, prior, corpus_size)?;
+    let decoded_n_jobs = decode_n_jobs(n_jobs);
+
+    let num_folds = num_folds.unwrap_or(5);
+    if num_folds < 2 || num_folds > retrievals.len() {
+        return Err(PyValueError::new_err(
+            "num_folds must be at least 2 and at most the number of retrievals"));
+    }
+
+    let decoded_thresholds = match (percentiles, thresholds) {
+        (Some(percentiles), None) => {
+            if percentiles.iter().any(|percentile| !(0.0..=100.0).contains(percentile)) {
+                return Err(PyValueError::new_err("Percentiles must be in [0, 100]"));
+            }
+            Thresholds::Percentiles(percentiles)
+        },
+        (None, Some(thresholds)) => Thresholds::Absolute(thresholds),
+        _ => return Err(PyValueError::new_err("Specify either percentiles or thresholds")),
+    };
+    if decoded_thresholds.is_empty() {
+        return Err(PyValueError::new_err("Need at least one percentile or threshold"));
+    }
+
+    let aggregation = if predictions.is_some() {
+        Aggregation::MajorityVote
+    } else {
+        Aggregation::Additive
+    };
+
+    let result = pruning::cross_validate_pruning(
+        &retrievals,
+        predictions.as_deref(),
+        &CrossValidation::new(num_folds, seed.unwrap_or(0)),
+        |training_retrievals| {
+            mle::mle_importance(
+                training_retrievals,
+                corpus_size,
+                decoded_grouping.as_ref(),
+                regularisation.as_ref(),
+                k,
+                learning_rate,
+                num_epochs,
+                decoded_n_jobs
+            )
+        },
+        decoded_grouping.as_ref(),
+        k,
+        &decoded_thresholds,
+        aggregation,
         decoded_n_jobs
     );
 
-    Ok(v)
+    Ok((result.fold_utilities, result.mean_utilities, result.best_index))
+}
+
+#[pyfunction]
+fn expected_utility(
+    py_retrievals: &PyList,
+    weights: Vec<f64>,
+    k: usize,
+    n_jobs: Option<isize>,
+) -> PyResult<(Vec<f64>, f64)> {
+
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+
+    if weights.len() < corpus_size {
+        return Err(PyValueError::new_err("Need a weight for every retrieved source"));
+    }
+
+    let expected = mle::objective::expected_utility(
+        &retrievals, &weights, k, decode_n_jobs(n_jobs));
+
+    Ok((expected.per_question, expected.overall))
+}
+
+// Models are exchanged with Python as dicts with the same layout as the JSON format
+fn model_to_py(py: Python, model: &Model) -> PyResult<PyObject> {
+    let json = serde_json::to_string(model)
+        .map_err(|error| PyValueError::new_err(error.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.to_object(py))
+}
+
+fn model_from_py(py: Python, py_model: &PyDict) -> PyResult<Model> {
+    let json: String = py.import("json")?.call_method1("dumps", (py_model,))?.extract()?;
+    let model: Model = serde_json::from_str(&json)
+        .map_err(|error| PyValueError::new_err(format!("Invalid model: {error}")))?;
+    model.check_compatibility().map_err(decode_io_error)?;
+    Ok(model)
+}
+
+fn decode_io_error(error: std::io::Error) -> PyErr {
+    if error.kind() == std::io::ErrorKind::InvalidData {
+        PyValueError::new_err(error.to_string())
+    } else {
+        PyErr::from(error)
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn learn_model(
+    py: Python,
+    py_retrievals: &PyList,
+    source_names: Vec<String>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: Option<isize>,
+    grouping: Option<&PyList>,
+    group_names: Option<Vec<String>>,
+    l2: Option<f64>,
+    l1: Option<f64>,
+    prior: Option<f64>,
+    tie_groups: Option<bool>,
+    deterministic: Option<bool>,
+) -> PyResult<PyObject> {
+
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    if source_names.len() < corpus_size {
+        return Err(PyValueError::new_err("Need a name for every retrieved source"));
+    }
+    let decoded_grouping = decode_grouping(grouping)?;
+
+    let named_grouping = match &decoded_grouping {
+        Some(grouping) => {
+            if grouping.group_assignments().len() != source_names.len() {
+                return Err(PyValueError::new_err("Need a group for every source"));
+            }
+            let names = group_names.unwrap_or_else(|| {
+                (0..grouping.num_groups).map(|group| group.to_string()).collect()
+            });
+            if names.len() != grouping.num_groups {
+                return Err(PyValueError::new_err("Need a name for every group"));
+            }
+            Some((grouping, names))
+        },
+        None => None,
+    };
+
+    let (l2, l1, prior) = (l2.unwrap_or(0.0), l1.unwrap_or(0.0), prior.unwrap_or(0.5));
+    check_regularisation(l2, l1, prior)?;
+
+    let hyperparameters = Hyperparameters {
+        k,
+        learning_rate,
+        num_epochs,
+        l2,
+        l1,
+        prior,
+        tie_groups: tie_groups.unwrap_or(false),
+        half_life_days: None,
+        as_of: None,
+        reduction: decode_reduction(deterministic),
+    };
+
+    let model = model::learn_model(
+        retrievals,
+        source_names,
+        named_grouping,
+        hyperparameters,
+        decode_n_jobs(n_jobs)
+    );
+
+    model_to_py(py, &model)
+}
+
+/// Updates the weights of a model on new retrievals with an online learner (see `OnlineLearner`),
+/// and returns the updated model, which contains the state of the learner.
+#[pyfunction]
+fn update_model(
+    py: Python,
+    model: &PyDict,
+    py_retrievals: &PyList,
+    n_jobs: Option<isize>,
+    replay_size: Option<usize>,
+    seed: Option<u64>,
+) -> PyResult<PyObject> {
+
+    let decoded_model = model_from_py(py, model)?;
+    let (retrievals, corpus_size) = decode_retrievals(py_retrievals)?;
+    if decoded_model.source_names.len() < corpus_size {
+        return Err(PyValueError::new_err("Need a name for every retrieved source"));
+    }
+    if decoded_model.hyperparameters.tie_groups {
+        return Err(PyValueError::new_err("Online learning does not support tied groups"));
+    }
+
+    let state = OnlineState::new(replay_size.unwrap_or(0), seed.unwrap_or(0));
+    let mut learner = OnlineLearner::from_model(decoded_model, state).map_err(decode_io_error)?;
+    learner.update(retrievals, decode_n_jobs(n_jobs));
+
+    model_to_py(py, &learner.checkpoint())
+}
+
+#[pyfunction]
+fn save_model(py: Python, model: &PyDict, path: &str, binary: Option<bool>) -> PyResult<()> {
+    let decoded_model = model_from_py(py, model)?;
+    let result = if binary.unwrap_or(!path.ends_with(".json")) {
+        decoded_model.save_binary(path)
+    } else {
+        decoded_model.save_json(path)
+    };
+    result.map_err(decode_io_error)
+}
+
+#[pyfunction]
+fn load_model(py: Python, path: &str) -> PyResult<PyObject> {
+    let model = Model::load(path).map_err(decode_io_error)?;
+    model_to_py(py, &model)
+}
+
+/// Reads the retrievals of a Parquet file in the format `learn_importance` expects, together with
+/// the names of the sources.
+#[cfg(feature = "arrow")]
+#[pyfunction]
+fn read_retrievals_parquet(py: Python, path: &str) -> PyResult<(PyObject, Vec<String>)> {
+    let (retrievals, source_indexer) = arrow_io::read_retrievals_parquet(
+        path, &arrow_io::RetrievalColumns::default()).map_err(decode_io_error)?;
+
+    let py_retrievals = PyList::empty(py);
+    for retrieval in retrievals {
+        let py_retrieval = PyDict::new(py);
+        py_retrieval.set_item("retrieved", retrieval.retrieved)?;
+        py_retrieval.set_item("utility_contributions", retrieval.utility_contributions)?;
+        py_retrieval.set_item("weight", retrieval.weight)?;
+        if let Some(timestamp) = retrieval.timestamp {
+            py_retrieval.set_item("timestamp", timestamp)?;
+        }
+        py_retrievals.append(py_retrieval)?;
+    }
+
+    Ok((py_retrievals.to_object(py), source_indexer.strings()))
+}
+
+/// Reads the groups of a Parquet file as the group of every source and the names of the groups.
+#[cfg(feature = "arrow")]
+#[pyfunction]
+fn read_grouping_parquet(
+    path: &str,
+    source_names: Vec<String>,
+) -> PyResult<(Vec<usize>, Vec<String>)> {
+    let source_index = source_names.into_iter().enumerate()
+        .map(|(id, name)| (name, id))
+        .collect();
+    let (grouping, group_indexer) = arrow_io::read_groups_parquet(path, &source_index)
+        .map_err(decode_io_error)?;
+
+    Ok((grouping.group_assignments().to_vec(), group_indexer.strings()))
+}
+
+#[cfg(feature = "arrow")]
+#[pyfunction]
+fn save_weights_parquet(path: &str, names: Vec<String>, weights: Vec<f64>) -> PyResult<()> {
+    if names.len() != weights.len() {
+        return Err(PyValueError::new_err("Need a name for every weight"));
+    }
+    arrow_io::write_weights_parquet(path, &names, &weights).map_err(decode_io_error)
+}
+
+#[pyfunction]
+fn rerank(
+    retrieved: Vec<usize>,
+    scores: Vec<f64>,
+    weights: Vec<f64>,
+    strategy: &str,
+    alpha: Option<f64>,
+    seed: Option<u64>,
+) -> PyResult<Vec<usize>> {
+    if retrieved.len() != scores.len() {
+        return Err(PyValueError::new_err("Need a score for every retrieved source"));
+    }
+    let reranking = decode_reranking(strategy, alpha, seed)?;
+    let positions = reranking::rerank(&retrieved, &scores, &weights, &reranking, 0);
+    Ok(positions.into_iter().map(|position| retrieved[position]).collect())
+}
+
+#[pyfunction]
+fn evaluate_reranking(
+    py_retrievals: &PyList,
+    weights: Vec<f64>,
+    k: usize,
+    strategy: &str,
+    alpha: Option<f64>,
+    seed: Option<u64>,
+    n_jobs: Option<isize>,
+) -> PyResult<f64> {
+
+    let (retrievals, _) = decode_retrievals(py_retrievals)?;
+    let predictions = decode_predictions(py_retrievals)?;
+    let scores = decode_scores(py_retrievals)?;
+    if retrievals.iter().zip(scores.iter())
+        .any(|(retrieval, scores)| retrieval.retrieved.len() != scores.len()) {
+        return Err(PyValueError::new_err("Need a score for every retrieved source"));
+    }
+
+    let reranking = decode_reranking(strategy, alpha, seed)?;
+
+    let aggregation = if predictions.is_some() {
+        Aggregation::MajorityVote
+    } else {
+        Aggregation::Additive
+    };
+
+    Ok(reranking::evaluate_reranked(
+        &retrievals,
+        &scores,
+        predictions.as_deref(),
+        &weights,
+        k,
+        &reranking,
+        aggregation,
+        decode_n_jobs(n_jobs)
+    ))
 }
 
 #[pymodule]
 fn ragbooster(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(learn_importance, m)?)?;
+    m.add_function(wrap_pyfunction!(learn_group_importance, m)?)?;
+    m.add_function(wrap_pyfunction!(learn_importance_with_statistics, m)?)?;
+    m.add_function(wrap_pyfunction!(tune_pruning_thresholds, m)?)?;
+    m.add_function(wrap_pyfunction!(tune_pruning_thresholds_constrained, m)?)?;
+    m.add_function(wrap_pyfunction!(cross_validate_pruning, m)?)?;
+    m.add_function(wrap_pyfunction!(expected_utility, m)?)?;
+    m.add_function(wrap_pyfunction!(learn_model, m)?)?;
+    m.add_function(wrap_pyfunction!(save_model, m)?)?;
+    m.add_function(wrap_pyfunction!(load_model, m)?)?;
+    m.add_function(wrap_pyfunction!(update_model, m)?)?;
+    m.add_function(wrap_pyfunction!(rerank, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_reranking, m)?)?;
+    #[cfg(feature = "arrow")]
+    {
+        m.add_function(wrap_pyfunction!(read_retrievals_parquet, m)?)?;
+        m.add_function(wrap_pyfunction!(read_grouping_parquet, m)?)?;
+        m.add_function(wrap_pyfunction!(save_weights_parquet, m)?)?;
+    }
     Ok(())
 }
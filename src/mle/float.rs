@@ -0,0 +1,39 @@
+use crate::mle::prob::{BoundarySetProb, TransposedProb};
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::AddAssign;
+
+/// Floating point type of the probability tensors and gradient computations. Training with `f32`
+/// doubles the SIMD width at the cost of accuracy, the weights themselves are always `f64`.
+pub trait Float:
+    num_traits::Float + AddAssign + Sum + Debug + Send + Sync + BoundarySetProb + TransposedProb
+    + 'static
+{
+    fn of_f64(value: f64) -> Self;
+
+    fn as_f64(self) -> f64;
+}
+
+impl Float for f64 {
+    #[inline(always)]
+    fn of_f64(value: f64) -> Self {
+        value
+    }
+
+    #[inline(always)]
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Float for f32 {
+    #[inline(always)]
+    fn of_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    #[inline(always)]
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
@@ -1,16 +1,144 @@
+use crate::mle::float::Float;
 use crate::mle::prob;
 use crate::mle::tensors::{DenseMatrix, DenseTensor};
 use crate::mle::types::Retrieval;
 use rayon::prelude::*;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Computes the gradient contributions of the retrieved sources of a single retrieval, e.g., from
+/// probabilities of a float type or from log-probabilities, with buffers which are reused across
+/// retrievals.
+pub(crate) trait Backend {
+    type Buffers;
+
+    #[allow(non_snake_case)]
+    fn allocate(K: usize, M_max: usize, E_max: usize) -> Self::Buffers;
+
+    #[allow(non_snake_case)]
+    fn gradient(
+        utility_contributions: &[f64],
+        p: &[f64],
+        K: usize,
+        N: f64,
+        buffers: &mut Self::Buffers,
+    ) -> Vec<f64>;
+}
+
+/// Computes the probabilities with floats of type T, the gradients are accumulated in f64.
+pub(crate) struct Linear<T>(PhantomData<T>);
+
+impl<T: Float> Backend for Linear<T> {
+    type Buffers = (DenseMatrix<T>, DenseMatrix<T>, DenseTensor<T>);
+
+    #[allow(non_snake_case)]
+    fn allocate(K: usize, M_max: usize, E_max: usize) -> Self::Buffers {
+        (
+            DenseMatrix::new(M_max + 2, K + 1),
+            DenseMatrix::new(M_max + 2, K + 1),
+            DenseTensor::new(K + 1, M_max + 2, E_max),
+        )
+    }
+
+    #[allow(non_snake_case)]
+    fn gradient(
+        utility_contributions: &[f64],
+        p: &[f64],
+        K: usize,
+        N: f64,
+        buffers: &mut Self::Buffers,
+    ) -> Vec<f64> {
+        let (IP, RP, B) = buffers;
+        let s = additive_any_loss_mle_gradient(
+            &convert::<T>(utility_contributions),
+            &convert::<T>(p),
+            K,
+            T::of_f64(N),
+            IP,
+            RP,
+            B
+        );
+        s.into_iter().map(|contribution| contribution.as_f64()).collect()
+    }
+}
+
+fn convert<T: Float>(values: &[f64]) -> Vec<T> {
+    values.iter().map(|value| T::of_f64(*value)).collect()
+}
+
+/// Computes the probabilities with floats of type T like `Linear`, but replaces the boundary-set
+/// tensor by its expected utility, which removes the E dimension from memory and runtime.
+pub(crate) struct Compact<T>(PhantomData<T>);
+
+impl<T: Float> Backend for Compact<T> {
+    type Buffers = [DenseMatrix<T>; 4];
+
+    #[allow(non_snake_case)]
+    fn allocate(K: usize, M_max: usize, _E_max: usize) -> Self::Buffers {
+        std::array::from_fn(|_| DenseMatrix::new(K + 1, M_max + 2))
+    }
+
+    #[allow(non_snake_case)]
+    fn gradient(
+        utility_contributions: &[f64],
+        p: &[f64],
+        K: usize,
+        N: f64,
+        buffers: &mut Self::Buffers,
+    ) -> Vec<f64> {
+        let [IP, RP, S, U] = buffers;
+        let s = additive_mle_gradient_compact(
+            &convert::<T>(utility_contributions),
+            &convert::<T>(p),
+            K,
+            T::of_f64(N),
+            IP,
+            RP,
+            S,
+            U
+        );
+        s.into_iter().map(|contribution| contribution.as_f64()).collect()
+    }
+}
+
+/// Computes log-probabilities, which stay accurate for long retrieval lists and extreme
+/// probabilities, at the cost of a slower, scalar computation.
+pub(crate) struct LogSpace;
+
+impl Backend for LogSpace {
+    type Buffers = (DenseMatrix, DenseMatrix, DenseTensor);
+
+    #[allow(non_snake_case)]
+    fn allocate(K: usize, M_max: usize, E_max: usize) -> Self::Buffers {
+        (
+            DenseMatrix::new(K + 1, M_max + 2),
+            DenseMatrix::new(K + 1, M_max + 2),
+            DenseTensor::new(K + 1, M_max + 2, E_max),
+        )
+    }
+
+    #[allow(non_snake_case)]
+    fn gradient(
+        utility_contributions: &[f64],
+        p: &[f64],
+        K: usize,
+        N: f64,
+        buffers: &mut Self::Buffers,
+    ) -> Vec<f64> {
+        let (log_IP, log_RP, log_B) = buffers;
+        additive_any_loss_mle_gradient_log_space(
+            utility_contributions, p, K, N, log_IP, log_RP, log_B)
+    }
+}
 
 #[allow(non_snake_case)]
-pub(crate) fn mle_importance_gradient_parallel(
-    D_val: &Vec<Retrieval>, // validation set with labels and ranked retrieved samples
-    v: &Vec<f64>, // existence variables
+pub(crate) fn mle_importance_gradient_parallel<Bk: Backend>(
+    D_val: &[Retrieval], // validation set with labels and ranked retrieved samples
+    v: &[f64], // existence variables
     K: usize, // k of knn-classifier,
     max_distinct_retrieved: usize,
     max_distinct_utility_contributions: usize,
-    N: usize,
+    N: f64, // total weight of the validation set
     n_jobs: usize,
 ) -> Vec<f64> {
 
@@ -26,21 +154,17 @@ pub(crate) fn mle_importance_gradient_parallel(
         // TODO we allocate per thread/gradient step at the moment,
         // TODO we could also only allocate once per thread
         // TODO we could also compute max_retrieved_samples and max_distinct_labels from the chunk
-        let mut IP = DenseMatrix::new(K + 1, M_max + 2);
-        let mut RP = DenseMatrix::new(K + 1, M_max + 2);
-        let mut B = DenseTensor::new(K + 1, M_max + 2, E_max);
+        let mut buffers = Bk::allocate(K, M_max, E_max);
 
         for retrieval in retrieval_chunk {
             // TODO maybe reuse a buffer here
             let p = retrieval.existence_probabilities(v);
-            let s = additive_any_loss_mle_gradient(
+            let s = Bk::gradient(
                 &retrieval.utility_contributions,
                 &p,
                 K,
-                N,
-                &mut IP,
-                &mut RP,
-                &mut B
+                N / retrieval.weight,
+                &mut buffers
             );
 
             for (retrieved_id, contribution) in retrieval.retrieved.iter().zip(s.iter()) {
@@ -61,13 +185,13 @@ pub(crate) fn mle_importance_gradient_parallel(
 }
 
 #[allow(non_snake_case)]
-pub(crate) fn mle_importance_gradient(
-    D_val: &Vec<Retrieval>, // validation set with ranked retrieved samples
-    v: &Vec<f64>, // existence variables
+pub(crate) fn mle_importance_gradient<Bk: Backend>(
+    D_val: &[Retrieval], // validation set with ranked retrieved samples
+    v: &[f64], // existence variables
     K: usize, // k of knn-classifier,
     max_distinct_retrieved: usize,
     max_distinct_utility_contributions: usize,
-    N: usize,
+    N: f64, // total weight of the validation set
 ) -> Vec<f64> {
 
     let M_max = max_distinct_retrieved;
@@ -75,21 +199,17 @@ pub(crate) fn mle_importance_gradient(
 
     let mut g = vec![0.0_f64; v.len()];
 
-    let mut IP = DenseMatrix::new(K + 1, M_max + 2);
-    let mut RP = DenseMatrix::new(K + 1, M_max + 2);
-    let mut B =  DenseTensor::new(K + 1, M_max + 2, E_max);
+    let mut buffers = Bk::allocate(K, M_max, E_max);
 
     for retrieval in D_val {
         // TODO maybe reuse a buffer here
         let p = retrieval.existence_probabilities(v);
-        let s = additive_any_loss_mle_gradient(
+        let s = Bk::gradient(
             &retrieval.utility_contributions,
             &p,
             K,
-            N,
-            &mut IP,
-            &mut RP,
-            &mut B
+            N / retrieval.weight,
+            &mut buffers
         );
 
         for (retrieved, contribution) in retrieval.retrieved.iter().zip(s.iter()) {
@@ -100,6 +220,135 @@ pub(crate) fn mle_importance_gradient(
     g
 }
 
+/// Number of chunks of the validation set for `mle_importance_gradient_deterministic`, which
+/// bounds the number of jobs that can work in parallel.
+pub const NUM_DETERMINISTIC_CHUNKS: usize = 64;
+
+/// Same as `mle_importance_gradient_parallel`, but splits the validation set into chunks which do
+/// not depend on the number of jobs, and sums up their gradients pairwise in a fixed order, so that
+/// the gradient is bit-identical for any number of threads of the current rayon pool. Within a
+/// chunk, the gradients of the retrievals are summed up with compensated summation, so that the
+/// rounding error does not grow with the size of the chunks.
+#[allow(non_snake_case)]
+pub(crate) fn mle_importance_gradient_deterministic<Bk: Backend>(
+    D_val: &[Retrieval], // validation set with labels and ranked retrieved samples
+    v: &[f64], // existence variables
+    K: usize, // k of knn-classifier,
+    max_distinct_retrieved: usize,
+    max_distinct_utility_contributions: usize,
+    N: f64, // total weight of the validation set
+) -> Vec<f64> {
+
+    let chunk_size = D_val.len().div_ceil(NUM_DETERMINISTIC_CHUNKS).max(1);
+    let chunks: Vec<&[Retrieval]> = D_val.chunks(chunk_size).collect();
+
+    fn pairwise_sum(
+        chunks: &[&[Retrieval]],
+        gradient_of: &(impl Fn(&[Retrieval]) -> Vec<f64> + Sync),
+    ) -> Vec<f64> {
+        if chunks.len() == 1 {
+            return gradient_of(chunks[0]);
+        }
+        let (left, right) = chunks.split_at(chunks.len() / 2);
+        let (mut sum, right_sum) = rayon::join(
+            || pairwise_sum(left, gradient_of),
+            || pairwise_sum(right, gradient_of)
+        );
+        sum.iter_mut().zip(right_sum.iter()).for_each(|(s, g)| *s += g);
+        sum
+    }
+
+    let gradient_of = |chunk: &[Retrieval]| {
+        let mut g = vec![0.0_f64; v.len()];
+        let mut compensation = vec![0.0_f64; v.len()];
+
+        let mut buffers =
+            Bk::allocate(K, max_distinct_retrieved, max_distinct_utility_contributions);
+
+        for retrieval in chunk {
+            let p = retrieval.existence_probabilities(v);
+            let s = Bk::gradient(
+                &retrieval.utility_contributions,
+                &p,
+                K,
+                N / retrieval.weight,
+                &mut buffers
+            );
+
+            for (retrieved, contribution) in retrieval.retrieved.iter().zip(s.iter()) {
+                compensated_add(&mut g[*retrieved], &mut compensation[*retrieved], *contribution);
+            }
+        }
+
+        g.iter_mut().zip(compensation.iter()).for_each(|(sum, c)| *sum += c);
+        g
+    };
+
+    pairwise_sum(&chunks, &gradient_of)
+}
+
+// Adds `value` to `sum` with Neumaier's variant of Kahan summation, which accumulates the lost
+// low-order bits in `compensation` (to be added to the sum at the end)
+fn compensated_add(sum: &mut f64, compensation: &mut f64, value: f64) {
+    let total = *sum + value;
+    if sum.abs() >= value.abs() {
+        *compensation += (*sum - total) + value;
+    } else {
+        *compensation += (value - total) + *sum;
+    }
+    *sum = total;
+}
+
+/// Same as `mle_importance_gradient_parallel`, but every job accumulates the gradient of its chunk
+/// in a sparse map, so that its memory depends on the sources retrieved in the chunk instead of
+/// the corpus size. The gradients are added to the dense gradient `g` of all chunks.
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub(crate) fn add_sparse_gradient<Bk: Backend>(
+    D_val: &[Retrieval], // chunk of the validation set with ranked retrieved samples
+    v: &[f64], // existence variables
+    K: usize, // k of knn-classifier,
+    max_distinct_retrieved: usize,
+    max_distinct_utility_contributions: usize,
+    N: f64, // total weight of the validation set
+    n_jobs: usize,
+    g: &mut [f64],
+) {
+
+    let M_max = max_distinct_retrieved;
+    let E_max = max_distinct_utility_contributions;
+
+    let chunk_size = (D_val.len() / n_jobs) + 1;
+
+    let chunk_gradients: Vec<_> = D_val.par_chunks(chunk_size).map(|retrieval_chunk| {
+
+        let mut sparse_g: HashMap<usize, f64> = HashMap::new();
+        let mut buffers = Bk::allocate(K, M_max, E_max);
+
+        for retrieval in retrieval_chunk {
+            let p = retrieval.existence_probabilities(v);
+            let s = Bk::gradient(
+                &retrieval.utility_contributions,
+                &p,
+                K,
+                N / retrieval.weight,
+                &mut buffers
+            );
+
+            for (retrieved_id, contribution) in retrieval.retrieved.iter().zip(s.iter()) {
+                *sparse_g.entry(*retrieved_id).or_insert(0.0) += contribution;
+            }
+        }
+        sparse_g
+    })
+    .collect();
+
+    for sparse_g in chunk_gradients {
+        for (retrieved_id, contribution) in sparse_g {
+            g[retrieved_id] += contribution;
+        }
+    }
+}
+
 /*
 def Additive_anyloss_MLE_Gradient_new(v_train, f_train, p, K, M):
 
@@ -132,27 +381,28 @@ def Additive_anyloss_MLE_Gradient_new(v_train, f_train, p, K, M):
     return s
 */
 #[allow(non_snake_case)]
-pub fn additive_any_loss_mle_gradient(
-    utility_contributions: &[f64],
-    p: &[f64],
+pub fn additive_any_loss_mle_gradient<T: Float>(
+    utility_contributions: &[T],
+    p: &[T],
     K: usize,
-    N: usize,
-    IP: &mut DenseMatrix,
-    RP: &mut DenseMatrix,
-    B: &mut DenseTensor
-) -> Vec<f64> {
+    N: T,
+    IP: &mut DenseMatrix<T>,
+    RP: &mut DenseMatrix<T>,
+    B: &mut DenseTensor<T>
+) -> Vec<T> {
 
     let num_retrieved = p.len();
     assert_eq!(num_retrieved, utility_contributions.len());
 
     // TODO we could also reuse a buffer here
-    let mut s = vec![0_f64; num_retrieved];
+    let mut s = vec![T::zero(); num_retrieved];
 
-    IP.reuse_as(K + 1, num_retrieved + 2);
-    RP.reuse_as(K + 1, num_retrieved + 2);
-    prob::compute_prob_from_tensors(p, K, num_retrieved, IP, RP);
+    // Transposed, i.e., IP[[j,k]] instead of IP[k][j] in the Python code
+    IP.reuse_as(num_retrieved + 2, K + 1);
+    RP.reuse_as(num_retrieved + 2, K + 1);
+    prob::compute_transposed_prob_from_tensors(p, K, num_retrieved, IP, RP);
 
-    let mut distinct_utility_contributions: Vec<f64> = Vec::new();
+    let mut distinct_utility_contributions: Vec<T> = Vec::new();
 
     // TODO can this be faster?
     for utility_contribution in utility_contributions {
@@ -171,15 +421,144 @@ pub fn additive_any_loss_mle_gradient(
         B
     );
 
+    let k_as_float = T::of_f64(K as f64);
+
+    for i in 1..num_retrieved +1 {
+        let c = utility_contributions[i-1];
+
+        // G_1
+        if c != T::zero() {
+            let mu_1 = (c / k_as_float) / N;
+            for k in 0..K {
+                for j in 0..k + 1 {
+                    s[i - 1] += mu_1 * IP[[i - 1, j]] * RP[[i + 1, k - j]];
+                }
+            }
+        }
+
+        // G_2
+        for e in 0..distinct_utility_contributions.len() {
+            let difference = c - distinct_utility_contributions[e];
+
+            if difference != T::zero() {
+                let mu_2 = (difference / k_as_float)  / N;
+                for j in 0..K {
+                    s[i - 1] += mu_2 * IP[[i - 1, j]] * B[[K - j, i + 1, e]];
+                }
+            }
+        }
+    }
+
+    s
+}
+
+/// Same as `additive_any_loss_mle_gradient`, but computes G_2 from the probability S and expected
+/// utility U of the boundary sources (see `prob::compute_boundary_utility_from_tensors`), as
+///
+///   sum_e (c - u_e) * B[K-j][i+1][e] = c * S[K-j][i+1] - U[K-j][i+1]
+///
+/// which takes O(M * K) instead of O(M * K * E) time per retrieval.
+#[allow(non_snake_case, clippy::too_many_arguments)]
+pub fn additive_mle_gradient_compact<T: Float>(
+    utility_contributions: &[T],
+    p: &[T],
+    K: usize,
+    N: T,
+    IP: &mut DenseMatrix<T>,
+    RP: &mut DenseMatrix<T>,
+    S: &mut DenseMatrix<T>,
+    U: &mut DenseMatrix<T>,
+) -> Vec<T> {
+
+    let num_retrieved = p.len();
+    assert_eq!(num_retrieved, utility_contributions.len());
+
+    let mut s = vec![T::zero(); num_retrieved];
+
+    // Transposed, i.e., IP[[j,k]] instead of IP[k][j] in the Python code
+    IP.reuse_as(num_retrieved + 2, K + 1);
+    RP.reuse_as(num_retrieved + 2, K + 1);
+    prob::compute_transposed_prob_from_tensors(p, K, num_retrieved, IP, RP);
+
+    S.reuse_as(K + 1, num_retrieved + 2);
+    U.reuse_as(K + 1, num_retrieved + 2);
+    prob::compute_boundary_utility_from_tensors(utility_contributions, p, K, num_retrieved, S, U);
+
+    let k_as_float = T::of_f64(K as f64);
+
+    for i in 1..num_retrieved +1 {
+        let c = utility_contributions[i-1];
+
+        // G_1
+        if c != T::zero() {
+            let mu_1 = (c / k_as_float) / N;
+            for k in 0..K {
+                for j in 0..k + 1 {
+                    s[i - 1] += mu_1 * IP[[i - 1, j]] * RP[[i + 1, k - j]];
+                }
+            }
+        }
+
+        // G_2
+        let mu_2 = T::one() / k_as_float / N;
+        for j in 0..K {
+            let boundary_difference = c * S[[K - j, i + 1]] - U[[K - j, i + 1]];
+            s[i - 1] += mu_2 * IP[[i - 1, j]] * boundary_difference;
+        }
+    }
+
+    s
+}
+
+/// Same as `additive_any_loss_mle_gradient`, but computes the products of the probabilities from
+/// log-probabilities, so that they are only rounded once instead of underflowing step by step.
+#[allow(non_snake_case)]
+pub fn additive_any_loss_mle_gradient_log_space(
+    utility_contributions: &[f64],
+    p: &[f64],
+    K: usize,
+    N: f64,
+    log_IP: &mut DenseMatrix,
+    log_RP: &mut DenseMatrix,
+    log_B: &mut DenseTensor
+) -> Vec<f64> {
+
+    let num_retrieved = p.len();
+    assert_eq!(num_retrieved, utility_contributions.len());
+
+    let mut s = vec![0_f64; num_retrieved];
+
+    log_IP.reuse_as(K + 1, num_retrieved + 2);
+    log_RP.reuse_as(K + 1, num_retrieved + 2);
+    prob::compute_log_prob_from_tensors(p, K, num_retrieved, log_IP, log_RP);
+
+    let mut distinct_utility_contributions: Vec<f64> = Vec::new();
+
+    for utility_contribution in utility_contributions {
+        if !distinct_utility_contributions.contains(utility_contribution) {
+            distinct_utility_contributions.push(*utility_contribution);
+        }
+    }
+
+    log_B.reuse_as(K + 1, num_retrieved + 2, distinct_utility_contributions.len());
+    prob::compute_log_boundary_set_prob_any_loss(
+        utility_contributions,
+        &distinct_utility_contributions,
+        p,
+        K,
+        num_retrieved,
+        log_B
+    );
+
     for i in 1..num_retrieved +1 {
         let c = utility_contributions[i-1];
 
         // G_1
         if c != 0.0 {
-            let mu_1 = (c as f64 / K as f64) / N as f64;
+            let mu_1 = (c / K as f64) / N;
             for k in 0..K {
                 for j in 0..k + 1 {
-                    s[i - 1] += mu_1 * IP[[j, i - 1]] * RP[[k - j, i + 1]];
+                    s[i - 1] += mu_1 * (log_IP[[j, i - 1]] + log_RP[[k - j, i + 1]]).exp();
                 }
             }
         }
@@ -189,9 +568,9 @@ pub fn additive_any_loss_mle_gradient(
             let difference = c - distinct_utility_contributions[e];
 
             if difference != 0.0 {
-                let mu_2 = (difference as f64 / K as f64)  / N as f64;
+                let mu_2 = (difference / K as f64)  / N;
                 for j in 0..K {
-                    s[i - 1] += mu_2 * IP[[j, i - 1]] * B[[K - j, i + 1, e]];
+                    s[i - 1] += mu_2 * (log_IP[[j, i - 1]] + log_B[[K - j, i + 1, e]]).exp();
                 }
             }
         }
@@ -199,3 +578,80 @@ pub fn additive_any_loss_mle_gradient(
 
     s
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_space_gradient_matches_linear_gradient() {
+        let utility_contributions = vec![1.0, 0.0, 0.5, 1.0, 0.0, 1.0, 0.5, 0.0, 0.25, 1.0];
+        let p = vec![0.9, 0.1, 0.5, 0.7, 0.3, 0.99, 0.01, 0.6, 0.4, 0.5];
+
+        for k in [1, 3, 5] {
+            let mut buffers = Linear::<f64>::allocate(k, p.len(), 4);
+            let expected =
+                Linear::<f64>::gradient(&utility_contributions, &p, k, 2.0, &mut buffers);
+
+            let mut log_buffers = LogSpace::allocate(k, p.len(), 4);
+            let s = LogSpace::gradient(&utility_contributions, &p, k, 2.0, &mut log_buffers);
+
+            for (actual, expected) in s.iter().zip(expected.iter()) {
+                assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn compact_gradient_matches_boundary_set_gradient() {
+        // Many distinct utility contributions, including repeated and negative ones
+        let utility_contributions = vec![1.0, 0.0, 0.5, 1.0, 0.25, -0.5, 0.75, 0.0, 0.25, 0.1, 0.9];
+        let p = vec![0.9, 0.1, 0.5, 0.7, 0.3, 0.99, 0.01, 0.6, 0.4, 0.5, 0.0];
+
+        for k in [1, 3, 5, 11] {
+            let mut buffers = Linear::<f64>::allocate(k, p.len(), 9);
+            let expected =
+                Linear::<f64>::gradient(&utility_contributions, &p, k, 3.0, &mut buffers);
+
+            let mut compact_buffers = Compact::<f64>::allocate(k, p.len(), 9);
+            let s =
+                Compact::<f64>::gradient(&utility_contributions, &p, k, 3.0, &mut compact_buffers);
+
+            for (actual, expected) in s.iter().zip(expected.iter()) {
+                assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn compensated_summation_keeps_low_order_bits() {
+        let (mut sum, mut compensation) = (0.0, 0.0);
+        for value in [1.0, 1e-16, 1e-16, 1e-16, 1e-16, -1.0] {
+            compensated_add(&mut sum, &mut compensation, value);
+        }
+        assert!((sum + compensation - 4e-16).abs() < 1e-30);
+    }
+
+    #[test]
+    fn log_space_gradient_for_long_retrievals() {
+        // With thousands of retrieved sources, the probabilities for the later sources underflow,
+        // the log-space gradient must agree wherever the linear gradient is still a normal float
+        let num_retrieved = 3000;
+        let utility_contributions: Vec<f64> = (0..num_retrieved)
+            .map(|i| if i % 3 == 0 { 1.0 } else { 0.0 })
+            .collect();
+        let p = vec![0.5; num_retrieved];
+
+        let mut log_buffers = LogSpace::allocate(10, num_retrieved, 2);
+        let s = LogSpace::gradient(&utility_contributions, &p, 10, 1.0, &mut log_buffers);
+
+        let mut buffers = Linear::<f64>::allocate(10, num_retrieved, 2);
+        let expected = Linear::<f64>::gradient(&utility_contributions, &p, 10, 1.0, &mut buffers);
+
+        assert!(s.iter().all(|s_i| s_i.is_finite()));
+        for (actual, expected) in s.iter().zip(expected.iter()).filter(|(_, e)| e.is_normal()) {
+            assert!(((actual - expected) / expected).abs() < 1e-9, "{actual} != {expected}");
+        }
+    }
+}
@@ -1,83 +1,518 @@
 // TODO See if we can adjust the visibilities without affecting the integration tests
+pub mod float;
 pub mod prob;
+pub mod simd;
 pub mod tensors;
 pub mod types;
 pub mod gradient;
+pub mod objective;
+pub mod statistics;
+pub mod streaming;
 
-use crate::mle::types::{Grouping, Retrieval};
+use crate::mle::float::Float;
+use crate::mle::gradient::{Backend, Compact, Linear, LogSpace};
+use crate::mle::types::{Grouping, Hierarchy, Regularisation, Retrieval, TimeDecay};
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
+use std::io;
+
+/// Utility contributions are rounded to this number of decimals before training, which bounds the
+/// number of distinct utility contributions per retrieval.
+pub const UTILITY_DECIMALS: i32 = 2;
+
+/// How the gradients of the retrievals are summed up over parallel jobs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reduction {
+    /// One chunk of retrievals per job, whose boundaries (and hence the rounding of the sums)
+    /// depend on `n_jobs`
+    #[default]
+    PerJob,
+    /// A fixed number of chunks whose gradients are summed up pairwise in a fixed order, so that
+    /// the weights are bit-identical for any `n_jobs` (e.g., to audit pruning decisions)
+    Deterministic,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn mle_importance(
-    mut retrievals: Vec<Retrieval>,
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> Vec<f64> {
+    mle_importance_with_precision::<f64>(
+        retrievals,
+        corpus_size,
+        optional_grouping,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs
+    )
+}
+
+/// Same as `mle_importance`, but learns the weights as of `time_decay.as_of()`: retrievals asked
+/// later are ignored, and the contribution of every other retrieval to the gradient decays with
+/// its age (see `TimeDecay`). Backtesting a pruning decision, for example, learns the weights as of
+/// some date and evaluates them on the retrievals asked afterwards. Fails if nothing is left to
+/// learn from (see `decayed`).
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_as_of(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+    time_decay: &TimeDecay,
+) -> io::Result<Vec<f64>> {
+    Ok(mle_importance(
+        decayed(retrievals, time_decay)?,
+        corpus_size,
+        optional_grouping,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs
+    ))
+}
+
+/// Decays the weights of the retrievals with `TimeDecay::apply`, and fails with `InvalidInput` if
+/// a retrieval has no timestamp, if no retrieval was asked until `as_of` or if the weights of all
+/// of them decayed to zero (e.g., for a very short half life), as the weights cannot be learned
+/// from them.
+pub fn decayed(retrievals: Vec<Retrieval>, time_decay: &TimeDecay) -> io::Result<Vec<Retrieval>> {
+    if retrievals.iter().any(|retrieval| retrieval.timestamp().is_none()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "time decay needs a timestamp for every retrieval"));
+    }
+    let retrievals = time_decay.apply(retrievals);
+    if retrievals.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "no retrievals until the given time"));
+    }
+    if total_weight(&retrievals) <= 0.0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "the weights of all retrievals decayed to zero, the half life is too short"));
+    }
+    Ok(retrievals)
+}
+
+/// Same as `mle_importance`, but with the `Deterministic` reduction of the gradients, i.e., the
+/// weights are bit-identical for any `n_jobs`.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_deterministic(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> Vec<f64> {
+    train::<Linear<f64>>(
+        retrievals,
+        corpus_size,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::Deterministic,
+        |v| {
+            if let Some(grouping) = optional_grouping {
+                adjust_for_groups(v, grouping);
+            }
+        }
+    )
+}
+
+/// Same as `mle_importance`, but computes the gradients with floats of type `T`, e.g., `f32` for
+/// twice the SIMD width at a lower accuracy (see the README for a comparison).
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_with_precision<T: Float>(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> Vec<f64> {
+    train::<Linear<T>>(
+        retrievals,
+        corpus_size,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::PerJob,
+        |v| {
+            if let Some(grouping) = optional_grouping {
+                adjust_for_groups(v, grouping);
+            }
+        }
+    )
+}
+
+/// Same as `mle_importance`, but tracks the expected utility of the boundary sources instead of the
+/// probabilities per distinct utility contribution, which is faster and needs less memory for
+/// retrievals with many distinct utility contributions.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_compact(
+    retrievals: Vec<Retrieval>,
     corpus_size: usize,
     optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
     k: usize,
     learning_rate: f64,
     num_epochs: usize,
     n_jobs: usize,
 ) -> Vec<f64> {
+    train::<Compact<f64>>(
+        retrievals,
+        corpus_size,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::PerJob,
+        |v| {
+            if let Some(grouping) = optional_grouping {
+                adjust_for_groups(v, grouping);
+            }
+        }
+    )
+}
 
-    let mut v = vec![0.5_f64; corpus_size];
+/// Same as `mle_importance`, but computes the probabilities in log-space, which is slower but stays
+/// accurate for long retrieval lists (e.g., thousands of retrieved sources) and extreme weights,
+/// where the probabilities of `mle_importance` underflow.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_log_space(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> Vec<f64> {
+    train::<LogSpace>(
+        retrievals,
+        corpus_size,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::PerJob,
+        |v| {
+            if let Some(grouping) = optional_grouping {
+                adjust_for_groups(v, grouping);
+            }
+        }
+    )
+}
 
+/// Learns importance weights for sources organised in a hierarchy (e.g., URL -> host -> domain),
+/// where the weight of every element is shrunk towards the weight of its group after each step.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_hierarchical(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    hierarchy: &Hierarchy,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> Vec<f64> {
+    assert_eq!(hierarchy.levels()[0].group_assignments().len(), corpus_size,
+        "the first level of the hierarchy must assign every source to a group");
+
+    train::<Linear<f64>>(
+        retrievals,
+        corpus_size,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::PerJob,
+        |v| shrink_towards_hierarchy(v, hierarchy)
+    )
+}
+
+// Runs gradient ascent and calls `adjust` on the clipped weights after every step
+#[allow(clippy::too_many_arguments)]
+fn train<Bk: Backend>(
+    mut retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+    reduction: Reduction,
+    adjust: impl Fn(&mut [f64]),
+) -> Vec<f64> {
+
+    let mut v = match optional_regularisation {
+        Some(regularisation) => {
+            assert_eq!(regularisation.priors().len(), corpus_size, "need one prior per source");
+            regularisation.priors().to_vec()
+        },
+        None => vec![0.5_f64; corpus_size],
+    };
+
+    discretise(&mut retrievals);
+    gradient_ascent::<Bk>(
+        &retrievals,
+        &mut v,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        reduction,
+        |_| {},
+        adjust
+    );
+
+    v
+}
+
+// Performs `num_steps` steps of gradient ascent from the current weights `v` on discretised
+// retrievals. Every step calls `project` on the (regularised) gradient before it is applied, and
+// `adjust` on the clipped weights afterwards
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn gradient_ascent<Bk: Backend>(
+    retrievals: &[Retrieval],
+    v: &mut [f64],
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_steps: usize,
+    n_jobs: usize,
+    reduction: Reduction,
+    project: impl Fn(&mut [f64]),
+    adjust: impl Fn(&mut [f64]),
+) {
+
+    let max_distinct_retrieved = max_distinct_retrieved(retrievals);
+    let max_distinct_utility_contributions = max_distinct_utility_contributions(retrievals);
+
+    // Also for a single job, so that the sums are rounded the same way for any number of jobs. The
+    // pool is built once, as only the fixed chunks determine the rounding.
+    let deterministic_pool = (reduction == Reduction::Deterministic).then(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n_jobs)
+            .build()
+            .unwrap()
+    });
+
+    for _ in 0..num_steps {
+        let mut g = compute_gradient::<Bk>(
+            retrievals,
+            v,
+            k,
+            max_distinct_retrieved,
+            max_distinct_utility_contributions,
+            n_jobs,
+            deterministic_pool.as_ref()
+        );
+
+        if let Some(regularisation) = optional_regularisation {
+            regularisation.add_to_gradient(v, &mut g);
+        }
+
+        project(&mut g);
+
+        for i in 0..v.len() {
+            // Clipping
+            v[i] = (v[i] + learning_rate * g[i]).clamp(0.0, 1.0);
+        }
+
+        adjust(v);
+    }
+}
+
+/// Learns one importance weight per group, i.e., all members of a group share (are tied to) the
+/// same existence variable, whose gradient is the sum of the gradients of its members. Returns the
+/// group-level weights and the corresponding member-level weights.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_tied(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    grouping: &Grouping,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+) -> (Vec<f64>, Vec<f64>) {
+    train_tied(
+        retrievals,
+        corpus_size,
+        grouping,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        Reduction::PerJob
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn train_tied(
+    mut retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    grouping: &Grouping,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+    reduction: Reduction,
+) -> (Vec<f64>, Vec<f64>) {
+
+    let assignments = grouping.group_assignments();
+    assert_eq!(assignments.len(), corpus_size, "every source must be assigned to a group");
+
+    let mut v_groups = match optional_regularisation {
+        Some(regularisation) => {
+            assert_eq!(regularisation.priors().len(), corpus_size, "need one prior per source");
+            v_grouped(regularisation.priors(), grouping)
+        },
+        None => vec![0.5_f64; grouping.num_groups],
+    };
+
+    discretise(&mut retrievals);
+
+    let mut v: Vec<f64> = assignments.iter().map(|group| v_groups[*group]).collect();
+
+    // Every member gets the gradient of its group, so that the members stay tied
+    gradient_ascent::<Linear<f64>>(
+        &retrievals,
+        &mut v,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs,
+        reduction,
+        |g| {
+            let mut g_groups = vec![0.0_f64; grouping.num_groups];
+            for (group, g_i) in assignments.iter().zip(g.iter()) {
+                g_groups[*group] += g_i;
+            }
+            for (g_i, group) in g.iter_mut().zip(assignments.iter()) {
+                *g_i = g_groups[*group];
+            }
+        },
+        |_| {}
+    );
+
+    // Groups without members keep their initial weight
+    for (v_i, group) in v.iter().zip(assignments.iter()) {
+        v_groups[*group] = *v_i;
+    }
+
+    (v_groups, v)
+}
+
+// Discretises the utility contributions in place and returns the maximum number of retrieved
+// sources and distinct utility contributions, which determine the sizes of the buffers.
+pub(crate) fn discretise(retrievals: &mut [Retrieval]) -> (usize, usize) {
     retrievals
         .iter_mut()
         .for_each(|retrieval| {
             retrieval.utility_contributions.iter_mut()
                 // TODO Make discretization configurable here
-                .for_each(|u| *u = (*u * 100.0).round() / 100.0)
+                .for_each(|u| *u = discretised(*u))
         });
 
-    let max_distinct_retrieved = max_distinct_retrieved(&retrievals);
-    let max_distinct_utility_contributions = max_distinct_utility_contributions(&retrievals);
+    (max_distinct_retrieved(retrievals), max_distinct_utility_contributions(retrievals))
+}
 
-    #[allow(non_snake_case)]
-    let N = retrievals.len();
+// A utility contribution rounded to UTILITY_DECIMALS decimals, as seen by the training
+pub(crate) fn discretised(utility_contribution: f64) -> f64 {
+    let scale = 10_f64.powi(UTILITY_DECIMALS);
+    (utility_contribution * scale).round() / scale
+}
 
-    for _ in 0..num_epochs {
-        let g = if n_jobs > 1 {
+// The gradients are reduced deterministically (see `Reduction::Deterministic`) on the given pool
+fn compute_gradient<Bk: Backend>(
+    retrievals: &[Retrieval],
+    v: &[f64],
+    k: usize,
+    max_distinct_retrieved: usize,
+    max_distinct_utility_contributions: usize,
+    n_jobs: usize,
+    deterministic_pool: Option<&rayon::ThreadPool>,
+) -> Vec<f64> {
 
-            rayon::ThreadPoolBuilder::new()
-                .num_threads(n_jobs)
-                .build()
-                .unwrap();
+    #[allow(non_snake_case)]
+    let N = total_weight(retrievals);
+    assert!(N > 0.0, "the total weight of the retrievals must be positive");
 
-            gradient::mle_importance_gradient_parallel(
-                &retrievals,
-                &v,
-                k,
-                max_distinct_retrieved,
-                max_distinct_utility_contributions,
-                N,
-                n_jobs
-            )
-        } else {
-            gradient::mle_importance_gradient(
-                &retrievals,
-                &v,
+    if let Some(pool) = deterministic_pool {
+
+        pool.install(|| {
+            gradient::mle_importance_gradient_deterministic::<Bk>(
+                retrievals,
+                v,
                 k,
                 max_distinct_retrieved,
                 max_distinct_utility_contributions,
-                N,
+                N
             )
-        };
-
-        for i in 0..v.len() {
-            v[i] += learning_rate * g[i];
+        })
+    } else if n_jobs > 1 {
 
-            // Clipping
-            if v[i] > 1.0 {
-                v[i] = 1.0;
-            } else if v[i] < 0.0 {
-                v[i] = 0.0;
-            }
-        }
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n_jobs)
+            .build()
+            .unwrap();
 
-        if let Some(grouping) = optional_grouping {
-            adjust_for_groups(&mut v, grouping);
-        }
+        gradient::mle_importance_gradient_parallel::<Bk>(
+            retrievals,
+            v,
+            k,
+            max_distinct_retrieved,
+            max_distinct_utility_contributions,
+            N,
+            n_jobs
+        )
+    } else {
+        gradient::mle_importance_gradient::<Bk>(
+            retrievals,
+            v,
+            k,
+            max_distinct_retrieved,
+            max_distinct_utility_contributions,
+            N,
+        )
     }
+}
 
-    v
+fn total_weight(retrievals: &[Retrieval]) -> f64 {
+    retrievals.iter().map(|retrieval| retrieval.weight).sum()
 }
 
 fn max_distinct_retrieved(retrievals: &[Retrieval]) -> usize {
@@ -93,7 +528,8 @@ fn max_distinct_utility_contributions(retrievals: &[Retrieval]) -> usize {
         .iter()
         .map(|retrieval| {
             retrieval.utility_contributions.iter()
-                .map(|x| *x as i64)
+                // The utility contributions are discretised, so comparing their bits is exact
+                .map(|x| x.to_bits())
                 .unique() // TODO not sure if hashing things is the fastest option here
                 .count()
         })
@@ -117,7 +553,7 @@ pub fn v_grouped(v: &[f64], grouping: &Grouping) -> Vec<f64> {
         .collect()
 }
 
-fn adjust_for_groups(v: &mut Vec<f64>, grouping: &Grouping) {
+pub(crate) fn adjust_for_groups(v: &mut [f64], grouping: &Grouping) {
 
     let v_grouped = v_grouped(v, grouping);
 
@@ -126,3 +562,61 @@ fn adjust_for_groups(v: &mut Vec<f64>, grouping: &Grouping) {
         v[retrieved_index] = v_grouped[assignments[retrieved_index]];
     }
 }
+
+/// Reports the weights at every level of the hierarchy, starting with the first level of groups.
+/// The weight of a group is the mean weight of its members on the level below.
+pub fn v_hierarchical(v: &[f64], hierarchy: &Hierarchy) -> Vec<Vec<f64>> {
+
+    let mut v_per_level: Vec<Vec<f64>> = Vec::with_capacity(hierarchy.levels().len());
+
+    for grouping in hierarchy.levels() {
+        let v_below = v_per_level.last().map(|v_level| v_level.as_slice()).unwrap_or(v);
+        let v_level = v_grouped(v_below, grouping);
+        v_per_level.push(v_level);
+    }
+
+    v_per_level
+}
+
+// The top-most groups keep their mean weight, every other element is shrunk towards its (already
+// shrunk) group from the top down, i.e., v_i = (1 - strength) * v_i + strength * v_group(i)
+fn shrink_towards_hierarchy(v: &mut [f64], hierarchy: &Hierarchy) {
+
+    let mut v_per_level = v_hierarchical(v, hierarchy);
+
+    let levels = hierarchy.levels();
+    let strengths = hierarchy.strengths();
+
+    for level in (1..levels.len()).rev() {
+        let (lower_levels, upper_levels) = v_per_level.split_at_mut(level);
+        shrink(&mut lower_levels[level - 1], &upper_levels[0], &levels[level], strengths[level]);
+    }
+
+    shrink(v, &v_per_level[0], &levels[0], strengths[0]);
+}
+
+fn shrink(v_members: &mut [f64], v_groups: &[f64], grouping: &Grouping, strength: f64) {
+    let assignments = grouping.group_assignments();
+    for (member, value) in v_members.iter_mut().enumerate() {
+        *value = (1.0 - strength) * *value + strength * v_groups[assignments[member]];
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_utility_contributions_are_distinct() {
+        let mut retrievals = vec![
+            Retrieval::new(vec![0, 1, 2, 3], vec![0.25, 0.5, 0.75, 0.5]),
+            Retrieval::new(vec![1, 2], vec![-0.5, -0.25]),
+        ];
+        assert_eq!(discretise(&mut retrievals), (4, 3));
+
+        // Undercounting them made the boundary-set tensor too small
+        let v = mle_importance(retrievals, 4, None, None, 2, 0.1, 5, 1);
+        assert!(v.iter().all(|v_i| (0.0..=1.0).contains(v_i)));
+    }
+}
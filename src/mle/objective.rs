@@ -0,0 +1,238 @@
+use crate::mle::{self, prob};
+use crate::mle::tensors::DenseMatrix;
+use crate::mle::types::{Regularisation, Retrieval};
+use rayon::prelude::*;
+
+/*
+The expected additive top-K utility of a single retrieval, whose derivative is computed by
+additive_any_loss_mle_gradient:
+
+    E[U] = 1/K * sum_i c_i * p_i * P(less than K of the first i-1 retrieved exist)
+         = 1/K * sum_i c_i * p_i * sum_{j<K} IP[j][i-1]
+*/
+#[allow(non_snake_case)]
+pub(crate) fn expected_additive_utility(
+    utility_contributions: &[f64],
+    p: &[f64],
+    K: usize,
+    IP: &mut DenseMatrix,
+    RP: &mut DenseMatrix,
+) -> f64 {
+
+    let num_retrieved = p.len();
+    assert_eq!(num_retrieved, utility_contributions.len());
+
+    // Transposed, i.e., IP[[j,k]] instead of IP[k][j] in the Python code
+    IP.reuse_as(num_retrieved + 2, K + 1);
+    RP.reuse_as(num_retrieved + 2, K + 1);
+    prob::compute_transposed_prob_from_tensors(p, K, num_retrieved, IP, RP);
+
+    let mut expected_utility = 0.0;
+
+    for i in 1..num_retrieved + 1 {
+        let c = utility_contributions[i - 1];
+        if c != 0.0 {
+            let prob_of_fewer_than_k_before: f64 = (0..K).map(|j| IP[[i - 1, j]]).sum();
+            expected_utility += c * p[i - 1] * prob_of_fewer_than_k_before;
+        }
+    }
+
+    expected_utility / K as f64
+}
+
+/// Expected top-K utility if every source is independently kept with its learned probability.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedUtility {
+    /// Expected utility of every question (unweighted)
+    pub per_question: Vec<f64>,
+    /// Mean expected utility over all questions, weighted by their weights (0.0 if there are no
+    /// questions or all of them are weightless)
+    pub overall: f64,
+}
+
+/// Computes the expected additive top-K utility under the existence probabilities `v`, e.g., to
+/// monitor training or to compare different weight vectors on the same questions. The utility
+/// contributions are rounded to `UTILITY_DECIMALS` decimals like during training.
+#[allow(non_snake_case)]
+pub fn expected_utility(
+    retrievals: &[Retrieval],
+    v: &[f64],
+    K: usize,
+    n_jobs: usize,
+) -> ExpectedUtility {
+
+    let M_max = retrievals.iter().map(|retrieval| retrieval.retrieved.len()).max().unwrap_or(0);
+    let new_buffers = || (DenseMatrix::new(M_max + 2, K + 1), DenseMatrix::new(M_max + 2, K + 1));
+
+    let utility_of = |(IP, RP): &mut (DenseMatrix, DenseMatrix), retrieval: &Retrieval| {
+        let utility_contributions: Vec<f64> = retrieval.utility_contributions.iter()
+            .map(|utility_contribution| mle::discretised(*utility_contribution))
+            .collect();
+        let p = retrieval.existence_probabilities(v);
+        expected_additive_utility(&utility_contributions, &p, K, IP, RP)
+    };
+
+    // No thread pool for a single job, as the objective is evaluated after every training run
+    let per_question: Vec<f64> = if n_jobs == 1 {
+        let mut buffers = new_buffers();
+        retrievals.iter().map(|retrieval| utility_of(&mut buffers, retrieval)).collect()
+    } else {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n_jobs)
+            .build()
+            .unwrap();
+
+        pool.install(|| {
+            retrievals
+                .par_iter()
+                .map_init(new_buffers, utility_of)
+                .collect()
+        })
+    };
+
+    let total_utility: f64 = retrievals.iter().zip(per_question.iter())
+        .map(|(retrieval, utility)| retrieval.weight * utility)
+        .sum();
+    let total_weight: f64 = retrievals.iter().map(|retrieval| retrieval.weight).sum();
+
+    // The weights are non-negative, so the mean is only undefined for a total weight of zero
+    let overall = if total_weight > 0.0 { total_utility / total_weight } else { 0.0 };
+
+    ExpectedUtility { overall, per_question }
+}
+
+/// The objective maximised by `mle_importance`: the expected top-K utility averaged over all
+/// retrievals (weighted by their weights), minus the regularisation penalty (if any). The
+/// expected utility is computed with `n_jobs` threads.
+#[allow(non_snake_case)]
+pub fn objective(
+    retrievals: &[Retrieval],
+    v: &[f64],
+    K: usize,
+    optional_regularisation: Option<&Regularisation>,
+    n_jobs: usize,
+) -> f64 {
+
+    let mean_utility = expected_utility(retrievals, v, K, n_jobs).overall;
+
+    match optional_regularisation {
+        Some(regularisation) => mean_utility - regularisation.penalty(v),
+        None => mean_utility,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mle::gradient;
+    use approx::assert_abs_diff_eq;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn expected_utility_matches_brute_force() {
+        let K = 2;
+        let utility_contributions = vec![1.0, 0.0, 1.0, 0.5];
+        let p = vec![0.3, 0.6, 0.8, 0.5];
+        let M = p.len();
+
+        let mut IP = DenseMatrix::new(M + 2, K + 1);
+        let mut RP = DenseMatrix::new(M + 2, K + 1);
+
+        let expected_utility =
+            expected_additive_utility(&utility_contributions, &p, K, &mut IP, &mut RP);
+
+        // Enumerate all possible worlds
+        let mut brute_force = 0.0;
+        for world in 0..(1 << M) {
+            let mut prob_of_world = 1.0;
+            let mut top_k = Vec::new();
+            for i in 0..M {
+                if world & (1 << i) != 0 {
+                    prob_of_world *= p[i];
+                    if top_k.len() < K {
+                        top_k.push(utility_contributions[i]);
+                    }
+                } else {
+                    prob_of_world *= 1.0 - p[i];
+                }
+            }
+            brute_force += prob_of_world * top_k.iter().sum::<f64>() / K as f64;
+        }
+
+        assert_abs_diff_eq!(expected_utility, brute_force, epsilon=0.00000001);
+    }
+
+    #[test]
+    fn expected_utility_per_question() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![1.0, 1.0]),
+            Retrieval::with_weight(vec![1, 2], vec![0.0, 1.0], 3.0),
+        ];
+        let v = vec![0.5, 1.0, 0.2];
+
+        let expected = expected_utility(&retrievals, &v, 1, 2);
+
+        assert_abs_diff_eq!(expected.per_question[0], 1.0, epsilon=0.00000001);
+        assert_abs_diff_eq!(expected.per_question[1], 0.0, epsilon=0.00000001);
+        assert_abs_diff_eq!(expected.overall, 0.25, epsilon=0.00000001);
+
+        // With K = 2, source 2 contributes whenever it exists
+        let expected = expected_utility(&retrievals, &v, 2, 1);
+        assert_abs_diff_eq!(expected.per_question[0], 0.75, epsilon=0.00000001);
+        assert_abs_diff_eq!(expected.per_question[1], 0.1, epsilon=0.00000001);
+    }
+
+    #[test]
+    fn expected_utility_discretises_like_training() {
+        let retrievals = vec![Retrieval::new(vec![0, 1], vec![0.123, 0.456])];
+        let rounded = vec![Retrieval::new(vec![0, 1], vec![0.12, 0.46])];
+        let v = vec![0.5, 0.8];
+
+        let expected = expected_utility(&retrievals, &v, 2, 1);
+        assert_eq!(expected, expected_utility(&rounded, &v, 2, 1));
+        assert_eq!(expected, expected_utility(&retrievals, &v, 2, 3));
+    }
+
+    #[test]
+    fn expected_utility_without_weight() {
+        let v = vec![0.5, 0.8];
+
+        let expected = expected_utility(&[], &v, 2, 1);
+        assert_eq!(expected, ExpectedUtility { per_question: vec![], overall: 0.0 });
+
+        let weightless = vec![Retrieval::with_weight(vec![0, 1], vec![1.0, 0.5], 0.0)];
+        let expected = expected_utility(&weightless, &v, 2, 1);
+        assert_eq!(expected.per_question.len(), 1);
+        assert_eq!(expected.overall, 0.0);
+    }
+
+    #[test]
+    fn regularised_gradient_matches_finite_differences() {
+        let k = 2;
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1, 2, 3], vec![1.0, 0.0, 1.0, 0.0]),
+            Retrieval::new(vec![3, 1, 0], vec![0.0, 1.0, 1.0]),
+            Retrieval::with_weight(vec![2, 4, 1, 0], vec![1.0, 1.0, 0.0, 0.0], 2.5),
+        ];
+        let v = vec![0.3, 0.6, 0.8, 0.5, 0.4];
+        let regularisation = Regularisation::new(
+            0.5, 0.1, vec![0.5, 0.5, 0.2, 0.9, 0.5], vec![1.0, 2.0, 0.5, 1.0, 1.0]);
+
+        let mut g = gradient::mle_importance_gradient::<gradient::Linear<f64>>(
+            &retrievals, &v, k, 4, 2, 4.5);
+        regularisation.add_to_gradient(&v, &mut g);
+
+        let h = 0.000001;
+        for i in 0..v.len() {
+            let mut v_plus = v.clone();
+            v_plus[i] += h;
+            let mut v_minus = v.clone();
+            v_minus[i] -= h;
+
+            let finite_difference = (objective(&retrievals, &v_plus, k, Some(&regularisation), 1)
+                - objective(&retrievals, &v_minus, k, Some(&regularisation), 1)) / (2.0 * h);
+
+            assert_abs_diff_eq!(g[i], finite_difference, epsilon=0.000001);
+        }
+    }
+}
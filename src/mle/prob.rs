@@ -1,3 +1,7 @@
+#[cfg(target_arch = "x86_64")]
+use crate::mle::simd::{Avx2, Avx512};
+use crate::mle::float::Float;
+use crate::mle::simd::{InstructionSet, Kernels, Portable};
 use crate::mle::tensors::{DenseMatrix, DenseTensor};
 
 /*
@@ -20,41 +24,112 @@ def ComputeProb(p, K, M, IP, RP):
     return IP, RP
 */
 #[allow(non_snake_case,unused)]
-pub fn compute_prob_from_tensors(
-    p: &[f64],
+pub fn compute_prob_from_tensors<T: Float>(
+    p: &[T],
     K: usize,
     M: usize,
-    IP: &mut DenseMatrix,
-    RP: &mut DenseMatrix,
+    IP: &mut DenseMatrix<T>,
+    RP: &mut DenseMatrix<T>,
 ) {
-    IP[[0,0]] = 1.0;
-    RP[[0,M+1]] = 1.0;
+    let one = T::one();
+
+    IP[[0,0]] = one;
+    RP[[0,M+1]] = one;
 
     // Required because we reuse un-zeroed memory
     for k in 1..K+1 {
-        IP[[k,0]] = 0.0;
-        RP[[k,M+1]] = 0.0;
+        IP[[k,0]] = T::zero();
+        RP[[k,M+1]] = T::zero();
     }
     // Required because we reuse un-zeroed memory
     for j in 1..M {
-        IP[[0,j]] = 0.0;
+        IP[[0,j]] = T::zero();
     }
 
+    // Scalar reference in the layout of the Python code, the inner loops run along the strided
+    // columns of IP and RP (see `compute_transposed_prob_from_tensors` for the vectorised version)
     for j in 1..M+1 {
-        IP[[0,j]] = IP[[0,j-1]] * (1.0 - p[j-1]);
+        IP[[0,j]] = IP[[0,j-1]] * (one - p[j-1]);
         for k in 1..K+1 {
-            IP[[k,j]] = IP[[k,j-1]] * (1.0 - p[j-1]) + IP[[k-1, j-1]] * p[j-1];
+            IP[[k,j]] = IP[[k,j-1]] * (one - p[j-1]) + IP[[k-1, j-1]] * p[j-1];
         }
     }
 
     for j in (1..M+1).rev() {
-        RP[[0,j]] = RP[[0,j+1]] * (1.0 - p[j-1]);
+        RP[[0,j]] = RP[[0,j+1]] * (one - p[j-1]);
         for k in 1..K+1 {
-            RP[[k,j]] = RP[[k,j+1]] * (1.0 - p[j-1]) + RP[[k-1,j+1]] * p[j-1];
+            RP[[k,j]] = RP[[k,j+1]] * (one - p[j-1]) + RP[[k-1,j+1]] * p[j-1];
         }
     }
 }
 
+/// Computes IP and RP like `compute_prob_from_tensors`, but transposed, i.e., as matrices of shape
+/// `[M+2, K+1]` with `IP[[j,k]]` instead of `IP[[k,j]]`. The recurrences then run along contiguous
+/// rows on the SIMD kernels of the CPU, with bit-identical results.
+#[allow(non_snake_case)]
+pub fn compute_transposed_prob_from_tensors<T: Float>(
+    p: &[T],
+    K: usize,
+    M: usize,
+    IP: &mut DenseMatrix<T>,
+    RP: &mut DenseMatrix<T>,
+) {
+    compute_transposed_prob_with_instruction_set(InstructionSet::detect(), p, K, M, IP, RP);
+}
+
+/// Computes the transposed IP and RP with the kernels of the given instruction set, which must be
+/// supported by the CPU. All instruction sets produce bit-identical results.
+#[allow(non_snake_case)]
+pub fn compute_transposed_prob_with_instruction_set<T: Float>(
+    instruction_set: InstructionSet,
+    p: &[T],
+    K: usize,
+    M: usize,
+    IP: &mut DenseMatrix<T>,
+    RP: &mut DenseMatrix<T>,
+) {
+    assert!(instruction_set <= InstructionSet::detect(), "instruction set is not supported");
+
+    T::transposed_prob(instruction_set, (p, K, M, IP, RP));
+}
+
+pub type TransposedProbArgs<'a, T> =
+    (&'a [T], usize, usize, &'a mut DenseMatrix<T>, &'a mut DenseMatrix<T>);
+
+/// Selects the SIMD kernels for the computation of the transposed IP and RP, which are specific to
+/// the float type.
+pub trait TransposedProb: Sized {
+    fn transposed_prob(instruction_set: InstructionSet, args: TransposedProbArgs<Self>);
+}
+
+#[allow(non_snake_case)]
+#[inline(always)]
+unsafe fn transposed_prob<S: Kernels<T>, T: Float>(args: TransposedProbArgs<T>) {
+    let (p, K, M, IP, RP) = args;
+
+    // Required because we reuse un-zeroed memory
+    S::fill_zero(&mut IP.row_mut(0)[1..]);
+    S::fill_zero(&mut RP.row_mut(M + 1)[1..]);
+    IP[[0,0]] = T::one();
+    RP[[M+1,0]] = T::one();
+
+    for j in 1..M+1 {
+        // IP[[j,k]] = IP[[j-1,k]] * (1.0 - p[j-1]) + IP[[j-1,k-1]] * p[j-1]
+        let not_p = T::one() - p[j - 1];
+        let (y, x) = IP.rows(j, j - 1);
+        y[0] = x[0] * not_p;
+        S::linear_combination(&mut y[1..], &x[1..], not_p, &x[..K], p[j - 1]);
+    }
+
+    for j in (1..M+1).rev() {
+        // RP[[j,k]] = RP[[j+1,k]] * (1.0 - p[j-1]) + RP[[j+1,k-1]] * p[j-1]
+        let not_p = T::one() - p[j - 1];
+        let (y, x) = RP.rows(j, j + 1);
+        y[0] = x[0] * not_p;
+        S::linear_combination(&mut y[1..], &x[1..], not_p, &x[..K], p[j - 1]);
+    }
+}
+
 /*
 value_dict = []
 f_dict = []
@@ -88,45 +163,261 @@ def ComputeBoundarySetProb_anyloss_new(f_train, value_dict, p, K, M, B):
 
 */
 #[allow(non_snake_case)]
-pub fn compute_boundary_set_prob_any_loss_from_tensor_predicated_simd(
+pub fn compute_boundary_set_prob_any_loss_from_tensor_predicated_simd<T: Float>(
+    retrieved_utility_contributions: &[T],
+    distinct_utility_contributions: &[T],
+    p: &[T],
+    K: usize,
+    M: usize,
+    B: &mut DenseTensor<T>,
+) {
+    compute_boundary_set_prob_any_loss_with_instruction_set(
+        InstructionSet::detect(),
+        retrieved_utility_contributions,
+        distinct_utility_contributions,
+        p,
+        K,
+        M,
+        B
+    );
+}
+
+/// Computes B with the kernels of the given instruction set, which must be supported by the CPU.
+/// All instruction sets produce bit-identical results.
+#[allow(non_snake_case)]
+pub fn compute_boundary_set_prob_any_loss_with_instruction_set<T: Float>(
+    instruction_set: InstructionSet,
+    retrieved_utility_contributions: &[T],
+    distinct_utility_contributions: &[T],
+    p: &[T],
+    K: usize,
+    M: usize,
+    B: &mut DenseTensor<T>,
+) {
+    assert!(instruction_set <= InstructionSet::detect(), "instruction set is not supported");
+
+    let args = (retrieved_utility_contributions, distinct_utility_contributions, p, K, M, B);
+    T::boundary_set_prob(instruction_set, args);
+}
+
+pub type BoundarySetArgs<'a, T> = (&'a [T], &'a [T], &'a [T], usize, usize, &'a mut DenseTensor<T>);
+
+/// Selects the SIMD kernels for the computation of B, which are specific to the float type.
+pub trait BoundarySetProb: Sized {
+    fn boundary_set_prob(instruction_set: InstructionSet, args: BoundarySetArgs<Self>);
+}
+
+// Implements a trait which selects the SIMD kernels of a recurrence for a float type, where the
+// target features of the wrappers allow the compiler to inline the kernels into the recurrence
+macro_rules! simd_recurrence {
+    ($trait:ident, $method:ident, $args:ident, $recurrence:ident, $float:ty) => {
+        impl $trait for $float {
+            fn $method(instruction_set: InstructionSet, args: $args<Self>) {
+
+                #[cfg(target_arch = "x86_64")]
+                #[target_feature(enable = "avx2")]
+                unsafe fn avx2(args: $args<$float>) {
+                    $recurrence::<Avx2, $float>(args)
+                }
+
+                #[cfg(target_arch = "x86_64")]
+                #[target_feature(enable = "avx512f,avx2")]
+                unsafe fn avx512(args: $args<$float>) {
+                    $recurrence::<Avx512, $float>(args)
+                }
+
+                unsafe {
+                    match instruction_set {
+                        InstructionSet::Portable => $recurrence::<Portable, $float>(args),
+                        #[cfg(target_arch = "x86_64")]
+                        InstructionSet::Avx2 => avx2(args),
+                        #[cfg(target_arch = "x86_64")]
+                        InstructionSet::Avx512 => avx512(args),
+                        #[cfg(not(target_arch = "x86_64"))]
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    };
+}
+
+simd_recurrence!(BoundarySetProb, boundary_set_prob, BoundarySetArgs, boundary_set_prob, f64);
+simd_recurrence!(BoundarySetProb, boundary_set_prob, BoundarySetArgs, boundary_set_prob, f32);
+simd_recurrence!(TransposedProb, transposed_prob, TransposedProbArgs, transposed_prob, f64);
+simd_recurrence!(TransposedProb, transposed_prob, TransposedProbArgs, transposed_prob, f32);
+
+#[allow(non_snake_case)]
+#[inline(always)]
+unsafe fn boundary_set_prob<S: Kernels<T>, T: Float>(args: BoundarySetArgs<T>) {
+    let (retrieved_utility_contributions, distinct_utility_contributions, p, K, M, B) = args;
+    let size_of_e = distinct_utility_contributions.len();
+
+    // Required because we reuse un-zeroed memory
+    for i in 1..M+2 {
+        S::fill_zero(&mut B.row_mut([0, i])[..size_of_e]);
+    }
+    // Required because we reuse un-zeroed memory
+    for k in 1..K+1 {
+        S::fill_zero(&mut B.row_mut([k, M + 1])[..size_of_e]);
+    }
+
+    for i in (1..M+1).rev() {
+        // B[[1,i,e]] = B[[1,i+1,e]] * (1.0 - p[i-1]) + B[[0,i+1,e]] * p[i-1] + matches * p[i-1]
+        let (y, x1, x2) = B.rows([1, i], [1, i + 1], [0, i + 1]);
+        S::linear_combination_plus_matches(
+            &mut y[..size_of_e],
+            x1,
+            T::one() - p[i - 1],
+            x2,
+            p[i - 1],
+            distinct_utility_contributions,
+            retrieved_utility_contributions[i - 1]
+        );
+
+        for k in 2..K+1 {
+            // B[[k,i,e]] = B[[k,i+1,e]] * (1.0 - p[i-1]) + B[[k-1,i+1,e]] * p[i-1]
+            let (y, x1, x2) = B.rows([k, i], [k, i + 1], [k - 1, i + 1]);
+            S::linear_combination(&mut y[..size_of_e], x1, T::one() - p[i - 1], x2, p[i - 1]);
+        }
+    }
+}
+
+
+
+/*
+Summing B over the distinct utility contributions e eliminates the E dimension for the additive
+utility, as the gradient only needs
+
+    S[k][i] = sum_e B[k][i][e]        (the k-th existing source from position i onwards exists)
+    U[k][i] = sum_e u_e * B[k][i][e]  (its expected utility contribution)
+
+and exactly one e matches the utility contribution u_i of position i, so that
+
+    S[k][i] = S[k][i+1] * (1 - p[i-1]) + S[k-1][i+1] * p[i-1] + [k == 1] * p[i-1]
+    U[k][i] = U[k][i+1] * (1 - p[i-1]) + U[k-1][i+1] * p[i-1] + [k == 1] * p[i-1] * u_i
+*/
+#[allow(non_snake_case)]
+pub fn compute_boundary_utility_from_tensors<T: Float>(
+    retrieved_utility_contributions: &[T],
+    p: &[T],
+    K: usize,
+    M: usize,
+    S: &mut DenseMatrix<T>,
+    U: &mut DenseMatrix<T>,
+) {
+    // Required because we reuse un-zeroed memory
+    for i in 1..M+2 {
+        S[[0,i]] = T::zero();
+        U[[0,i]] = T::zero();
+    }
+    // Required because we reuse un-zeroed memory
+    for k in 1..K+1 {
+        S[[k,M+1]] = T::zero();
+        U[[k,M+1]] = T::zero();
+    }
+
+    for i in (1..M+1).rev() {
+        let not_p = T::one() - p[i-1];
+
+        S[[1,i]] = S[[1,i+1]] * not_p + S[[0,i+1]] * p[i-1] + p[i-1];
+        U[[1,i]] = U[[1,i+1]] * not_p + U[[0,i+1]] * p[i-1]
+            + p[i-1] * retrieved_utility_contributions[i-1];
+
+        for k in 2..K+1 {
+            S[[k,i]] = S[[k,i+1]] * not_p + S[[k-1,i+1]] * p[i-1];
+            U[[k,i]] = U[[k,i+1]] * not_p + U[[k-1,i+1]] * p[i-1];
+        }
+    }
+}
+
+/// log(exp(a) + exp(b)) without overflow or underflow, where log(0) is negative infinity.
+#[inline(always)]
+pub fn log_sum_exp(a: f64, b: f64) -> f64 {
+    let max = a.max(b);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + (-(a - b).abs()).exp().ln_1p()
+}
+
+// log(p) and log(1 - p), the latter is accurate for small p
+#[inline(always)]
+fn log_probabilities(p: f64) -> (f64, f64) {
+    (p.ln(), (-p).ln_1p())
+}
+
+/// Computes the natural logarithms of IP and RP, which stay accurate for long retrieval lists and
+/// extreme probabilities, where the products in `compute_prob_from_tensors` underflow.
+#[allow(non_snake_case)]
+pub fn compute_log_prob_from_tensors(
+    p: &[f64],
+    K: usize,
+    M: usize,
+    log_IP: &mut DenseMatrix,
+    log_RP: &mut DenseMatrix,
+) {
+    log_IP[[0,0]] = 0.0;
+    log_RP[[0,M+1]] = 0.0;
+
+    // Required because we reuse un-zeroed memory
+    for k in 1..K+1 {
+        log_IP[[k,0]] = f64::NEG_INFINITY;
+        log_RP[[k,M+1]] = f64::NEG_INFINITY;
+    }
+
+    for j in 1..M+1 {
+        let (log_p, log_not_p) = log_probabilities(p[j-1]);
+        log_IP[[0,j]] = log_IP[[0,j-1]] + log_not_p;
+        for k in 1..K+1 {
+            log_IP[[k,j]] = log_sum_exp(log_IP[[k,j-1]] + log_not_p, log_IP[[k-1,j-1]] + log_p);
+        }
+    }
+
+    for j in (1..M+1).rev() {
+        let (log_p, log_not_p) = log_probabilities(p[j-1]);
+        log_RP[[0,j]] = log_RP[[0,j+1]] + log_not_p;
+        for k in 1..K+1 {
+            log_RP[[k,j]] = log_sum_exp(log_RP[[k,j+1]] + log_not_p, log_RP[[k-1,j+1]] + log_p);
+        }
+    }
+}
+
+/// Computes the natural logarithm of B, see `compute_log_prob_from_tensors`.
+#[allow(non_snake_case)]
+pub fn compute_log_boundary_set_prob_any_loss(
     retrieved_utility_contributions: &[f64],
     distinct_utility_contributions: &[f64],
     p: &[f64],
     K: usize,
     M: usize,
-    B: &mut DenseTensor,
+    log_B: &mut DenseTensor,
 ) {
     let size_of_e = distinct_utility_contributions.len();
 
-    // TODO maybe these should be run via SIMD as well?
     // Required because we reuse un-zeroed memory
     for i in 1..M+2 {
-        for e in 0..size_of_e {
-            B[[0, i, e]] = 0.0;
-        }
+        log_B.row_mut([0, i])[..size_of_e].fill(f64::NEG_INFINITY);
     }
     // Required because we reuse un-zeroed memory
     for k in 1..K+1 {
-        for e in 0..size_of_e {
-            B[[k, M + 1, e]] = 0.0;
-        }
+        log_B.row_mut([k, M + 1])[..size_of_e].fill(f64::NEG_INFINITY);
     }
 
     for i in (1..M+1).rev() {
+        let (log_p, log_not_p) = log_probabilities(p[i-1]);
+
         for e in 0..size_of_e {
-            let matches = (distinct_utility_contributions[e] ==
-                retrieved_utility_contributions[i-1]) as i64 as f64;
-            B[[1,i,e]] = B[[1,i+1,e]] * (1.0 - p[i-1]) + B[[0,i+1,e]] * p[i-1] + matches * p[i-1];
+            let log_matches = if distinct_utility_contributions[e] ==
+                retrieved_utility_contributions[i-1] { log_p } else { f64::NEG_INFINITY };
+            log_B[[1,i,e]] = log_sum_exp(log_B[[1,i+1,e]] + log_not_p, log_matches);
         }
 
         for k in 2..K+1 {
-            // Vectorizable version of the following code
-            // for e in 0..num_labels {
-            //     B[[k,i,e]] = B[[k,i+1,e]] * (1.0 - p[i-1]) + B[[k-1,i+1,e]] * p[i-1];
-            // }
-            B.set_y_to_x1_a1_plus_x2_a2(
-                [k, i], [k, i + 1], 1.0 - p[i-1], [k - 1, i + 1], p[i - 1]
-            );
+            for e in 0..size_of_e {
+                log_B[[k,i,e]] =
+                    log_sum_exp(log_B[[k,i+1,e]] + log_not_p, log_B[[k-1,i+1,e]] + log_p);
+            }
         }
     }
 }
@@ -144,8 +435,8 @@ mod tests {
         let K = 3;
         let p = vec![0.5; M];
 
-        let mut IP = DenseMatrix::new(K + 1, M + 2);
-        let mut RP = DenseMatrix::new(K + 1, M + 2);
+        let mut IP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
+        let mut RP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
 
         compute_prob_from_tensors(&p, K, M, &mut IP, &mut RP);
 
@@ -169,5 +460,205 @@ mod tests {
         assert!(norm_of_RP_difference < 0.0000001);
     }
 
+    #[allow(non_snake_case)]
+    fn check_identical_transposed_IP_RP<T: Float>() {
+        let M = 23;
+        let p: Vec<T> = (0..M)
+            .map(|i| T::of_f64(0.05 + (i as f64 * 0.61).sin().abs() * 0.9))
+            .collect();
+
+        // Values of K around the register widths
+        for K in 0..35 {
+            let mut expected_IP = DenseMatrix::<T>::new(K + 1, M + 2);
+            let mut expected_RP = DenseMatrix::<T>::new(K + 1, M + 2);
+            compute_prob_from_tensors(&p, K, M, &mut expected_IP, &mut expected_RP);
+
+            for instruction_set in InstructionSet::available() {
+                // Garbage in the buffers must be overwritten
+                let mut IP = DenseMatrix::<T>::new(M + 2, K + 1);
+                let mut RP = DenseMatrix::<T>::new(M + 2, K + 1);
+                IP.row_mut(0).fill(T::nan());
+                RP.row_mut(M + 1).fill(T::nan());
+
+                compute_transposed_prob_with_instruction_set(
+                    instruction_set, &p, K, M, &mut IP, &mut RP);
+
+                for k in 0..K+1 {
+                    for j in 0..M+1 {
+                        assert_eq!(IP[[j,k]], expected_IP[[k,j]], "{instruction_set:?}, K {K}");
+                        assert_eq!(RP[[j+1,k]], expected_RP[[k,j+1]], "{instruction_set:?}, K {K}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn instruction_sets_compute_identical_transposed_IP_RP() {
+        check_identical_transposed_IP_RP::<f64>();
+        check_identical_transposed_IP_RP::<f32>();
+    }
+
+    #[allow(non_snake_case)]
+    fn check_identical_B<T: Float>() {
+        let M = 37;
+        let K = 6;
+        let p: Vec<T> = (0..M)
+            .map(|i| T::of_f64(0.05 + (i as f64 * 0.61).sin().abs() * 0.9))
+            .collect();
+
+        // Numbers of distinct utility contributions around the register widths
+        for E in 1..35 {
+            let retrieved_utility_contributions: Vec<T> = (0..M)
+                .map(|i| T::of_f64(((i * 7) % E) as f64 / 100.0))
+                .collect();
+            let distinct_utility_contributions: Vec<T> = (0..E)
+                .map(|e| T::of_f64(e as f64 / 100.0))
+                .collect();
+
+            // Scalar reference implementation of the recurrence
+            let mut expected = DenseTensor::<T>::new(K + 1, M + 2, E);
+            for i in (1..M+1).rev() {
+                for k in 1..K+1 {
+                    for e in 0..E {
+                        let matches = k == 1 && distinct_utility_contributions[e] ==
+                            retrieved_utility_contributions[i-1];
+                        let matches = if matches { T::one() } else { T::zero() };
+                        expected[[k,i,e]] = expected[[k,i+1,e]] * (T::one() - p[i-1])
+                            + expected[[k-1,i+1,e]] * p[i-1] + matches * p[i-1];
+                    }
+                }
+            }
+
+            for instruction_set in InstructionSet::available() {
+                // Garbage in the buffer must be overwritten
+                let mut B = DenseTensor::<T>::new(K + 1, M + 2, E);
+                B.row_mut([0, 3]).fill(T::nan());
+                B.row_mut([K, M + 1]).fill(T::nan());
+
+                compute_boundary_set_prob_any_loss_with_instruction_set(
+                    instruction_set,
+                    &retrieved_utility_contributions,
+                    &distinct_utility_contributions,
+                    &p,
+                    K,
+                    M,
+                    &mut B
+                );
+
+                assert_eq!(B.view_buffer(), expected.view_buffer(), "{instruction_set:?}, E {E}");
+            }
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn instruction_sets_compute_identical_B() {
+        check_identical_B::<f64>();
+        check_identical_B::<f32>();
+    }
+
+    // Log-probabilities of IP, RP and B by enumerating all subsets of existing sources
+    #[allow(non_snake_case, clippy::type_complexity, clippy::needless_range_loop)]
+    fn brute_force_log_probabilities(
+        retrieved_utility_contributions: &[f64],
+        distinct_utility_contributions: &[f64],
+        p: &[f64],
+        K: usize,
+    ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>, Vec<Vec<Vec<f64>>>) {
+        let M = p.len();
+        let E = distinct_utility_contributions.len();
+        let mut log_IP = vec![vec![f64::NEG_INFINITY; M + 2]; K + 1];
+        let mut log_RP = vec![vec![f64::NEG_INFINITY; M + 2]; K + 1];
+        let mut log_B = vec![vec![vec![f64::NEG_INFINITY; E]; M + 2]; K + 1];
+
+        for subset in 0..(1_usize << M) {
+            let exists = |i: usize| subset & (1 << (i - 1)) != 0;
+            let log_prob: f64 = (1..M+1)
+                .map(|i| if exists(i) { p[i-1].ln() } else { (-p[i-1]).ln_1p() })
+                .sum();
+
+            for j in 0..M+1 {
+                let k = (1..j+1).filter(|i| exists(*i)).count();
+                if k <= K {
+                    log_IP[k][j] = log_sum_exp(log_IP[k][j], log_prob);
+                }
+            }
+            for j in 1..M+2 {
+                let k = (j..M+1).filter(|i| exists(*i)).count();
+                if k <= K {
+                    log_RP[k][j] = log_sum_exp(log_RP[k][j], log_prob);
+                }
+                // The k-th existing source from position j onwards has utility value e
+                for (k, i) in (j..M+1).filter(|i| exists(*i)).enumerate().take(K) {
+                    let e = distinct_utility_contributions.iter()
+                        .position(|u| *u == retrieved_utility_contributions[i-1])
+                        .unwrap();
+                    log_B[k+1][j][e] = log_sum_exp(log_B[k+1][j][e], log_prob);
+                }
+            }
+        }
+
+        (log_IP, log_RP, log_B)
+    }
+
+    fn assert_log_close(actual: f64, expected: f64) {
+        if expected == f64::NEG_INFINITY {
+            assert_eq!(actual, expected);
+        } else {
+            assert!((actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+                "{actual} != {expected}");
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn log_space_matches_brute_force_at_extreme_probabilities() {
+        let K = 3;
+        let retrieved_utility_contributions = vec![1.0, 0.0, 0.5, 1.0, 0.0, 1.0, 0.5, 0.0];
+        let distinct_utility_contributions = vec![1.0, 0.0, 0.5];
+        let M = retrieved_utility_contributions.len();
+
+        for p in [
+            vec![1e-150; M],
+            vec![1.0 - 1e-12; M],
+            vec![1e-300, 0.5, 1.0 - 1e-15, 1e-100, 0.0, 1.0, 1e-200, 0.3],
+        ] {
+            let mut log_IP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
+            let mut log_RP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
+            let mut log_B: DenseTensor = DenseTensor::new(K + 1, M + 2, 3);
+            compute_log_prob_from_tensors(&p, K, M, &mut log_IP, &mut log_RP);
+            compute_log_boundary_set_prob_any_loss(&retrieved_utility_contributions,
+                &distinct_utility_contributions, &p, K, M, &mut log_B);
+
+            let (expected_IP, expected_RP, expected_B) = brute_force_log_probabilities(
+                &retrieved_utility_contributions, &distinct_utility_contributions, &p, K);
+
+            for k in 0..K+1 {
+                for j in 0..M+1 {
+                    assert_log_close(log_IP[[k,j]], expected_IP[k][j]);
+                }
+                for j in 1..M+2 {
+                    assert_log_close(log_RP[[k,j]], expected_RP[k][j]);
+                }
+            }
+            for k in 1..K+1 {
+                for i in 1..M+2 {
+                    for e in 0..3 {
+                        assert_log_close(log_B[[k,i,e]], expected_B[k][i][e]);
+                    }
+                }
+            }
+        }
+
+        // In contrast, the probabilities underflow in linear space
+        let p = vec![1e-150; M];
+        let mut IP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
+        let mut RP: DenseMatrix = DenseMatrix::new(K + 1, M + 2);
+        compute_prob_from_tensors(&p, K, M, &mut IP, &mut RP);
+        assert_eq!(IP[[3, M]], 0.0);
+    }
+
     // TODO add test where the shape of the reused matrix is changed
 }
\ No newline at end of file
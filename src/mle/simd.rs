@@ -0,0 +1,379 @@
+//! Explicitly vectorised kernels for the contiguous inner loops of the recurrences in `prob`, i.e.,
+//! of the boundary-set recurrence and of the transposed recurrences for `IP` and `RP`.
+//!
+//! The kernels only use separate multiplications and additions (no fused multiply-adds) in the
+//! same order as the scalar code, so that all instruction sets produce bit-identical results.
+use crate::mle::float::Float;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Instruction set used for the kernels, detected at runtime by `InstructionSet::detect`. Every
+/// instruction set implies the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    Portable,
+    Avx2,
+    Avx512,
+}
+
+impl InstructionSet {
+
+    /// The widest instruction set supported by the current CPU.
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                if is_x86_feature_detected!("avx512f") {
+                    return InstructionSet::Avx512;
+                }
+                return InstructionSet::Avx2;
+            }
+        }
+        InstructionSet::Portable
+    }
+
+    /// All instruction sets supported by the current CPU, e.g., to compare them in tests.
+    pub fn available() -> Vec<Self> {
+        [InstructionSet::Portable, InstructionSet::Avx2, InstructionSet::Avx512]
+            .into_iter()
+            .filter(|instruction_set| *instruction_set <= Self::detect())
+            .collect()
+    }
+}
+
+/// Kernels over slices of the same length. The methods are unsafe, as implementations may
+/// require CPU features which the caller has to check (e.g., via `InstructionSet::detect`).
+pub(crate) trait Kernels<T> {
+    /// y = 0
+    unsafe fn fill_zero(y: &mut [T]);
+
+    /// y = x1 * a1 + x2 * a2
+    unsafe fn linear_combination(y: &mut [T], x1: &[T], a1: T, x2: &[T], a2: T);
+
+    /// y = x1 * a1 + x2 * a2 + [values == value] * a2
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn linear_combination_plus_matches(
+        y: &mut [T],
+        x1: &[T],
+        a1: T,
+        x2: &[T],
+        a2: T,
+        values: &[T],
+        value: T,
+    );
+}
+
+const CHUNK_SIZE: usize = 4;
+
+/// Scalar fallback, which processes chunks of four elements to help auto-vectorisation.
+pub(crate) struct Portable;
+
+impl<T: Float> Kernels<T> for Portable {
+
+    #[inline(always)]
+    unsafe fn fill_zero(y: &mut [T]) {
+        y.fill(T::zero());
+    }
+
+    #[inline(always)]
+    unsafe fn linear_combination(y: &mut [T], x1: &[T], a1: T, x2: &[T], a2: T) {
+        let x1 = &x1[..y.len()];
+        let x2 = &x2[..y.len()];
+
+        let y_chunked = y.chunks_exact_mut(CHUNK_SIZE);
+        let x1_chunked = x1.chunks_exact(CHUNK_SIZE);
+        let x2_chunked = x2.chunks_exact(CHUNK_SIZE);
+
+        for ((y_chunk, x1_chunk), x2_chunk) in y_chunked.zip(x1_chunked).zip(x2_chunked) {
+            let inner_iter = y_chunk.iter_mut().zip(x1_chunk.iter()).zip(x2_chunk.iter());
+            for ((y, x1), x2) in inner_iter {
+                *y = *x1 * a1 + *x2 * a2;
+            }
+        }
+
+        let y_remainder = y.chunks_exact_mut(CHUNK_SIZE).into_remainder().iter_mut();
+        let x1_remainder = x1.chunks_exact(CHUNK_SIZE).remainder().iter();
+        let x2_remainder = x2.chunks_exact(CHUNK_SIZE).remainder().iter();
+
+        for ((y, x1), x2) in y_remainder.zip(x1_remainder).zip(x2_remainder) {
+            *y = *x1 * a1 + *x2 * a2;
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn linear_combination_plus_matches(
+        y: &mut [T],
+        x1: &[T],
+        a1: T,
+        x2: &[T],
+        a2: T,
+        values: &[T],
+        value: T,
+    ) {
+        let inner_iter = y.iter_mut().zip(x1.iter()).zip(x2.iter()).zip(values.iter());
+        for (((y, x1), x2), v) in inner_iter {
+            let matches = if *v == value { T::one() } else { T::zero() };
+            *y = *x1 * a1 + *x2 * a2 + matches * a2;
+        }
+    }
+}
+
+/// Kernels with 256 bit registers, the tail is handled by the portable kernels.
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Avx2;
+
+// Implements the AVX2 kernels for a float type with the corresponding intrinsics
+#[cfg(target_arch = "x86_64")]
+macro_rules! avx2_kernels {
+    ($float:ty, $lanes:expr, $setzero:ident, $set1:ident, $loadu:ident, $storeu:ident,
+     $add:ident, $mul:ident, $and:ident, $cmp:ident) => {
+
+        impl Kernels<$float> for Avx2 {
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn fill_zero(y: &mut [$float]) {
+                let num_vectorised = y.len() - y.len() % $lanes;
+                let zero = $setzero();
+                for offset in (0..num_vectorised).step_by($lanes) {
+                    $storeu(y.as_mut_ptr().add(offset), zero);
+                }
+                Portable::fill_zero(&mut y[num_vectorised..]);
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn linear_combination(
+                y: &mut [$float],
+                x1: &[$float],
+                a1: $float,
+                x2: &[$float],
+                a2: $float,
+            ) {
+                assert!(x1.len() >= y.len() && x2.len() >= y.len());
+                let num_vectorised = y.len() - y.len() % $lanes;
+                let a1_vec = $set1(a1);
+                let a2_vec = $set1(a2);
+                for offset in (0..num_vectorised).step_by($lanes) {
+                    let x1_vec = $loadu(x1.as_ptr().add(offset));
+                    let x2_vec = $loadu(x2.as_ptr().add(offset));
+                    let y_vec = $add($mul(x1_vec, a1_vec), $mul(x2_vec, a2_vec));
+                    $storeu(y.as_mut_ptr().add(offset), y_vec);
+                }
+                Portable::linear_combination(
+                    &mut y[num_vectorised..], &x1[num_vectorised..], a1, &x2[num_vectorised..], a2);
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx2")]
+            unsafe fn linear_combination_plus_matches(
+                y: &mut [$float],
+                x1: &[$float],
+                a1: $float,
+                x2: &[$float],
+                a2: $float,
+                values: &[$float],
+                value: $float,
+            ) {
+                assert!(x1.len() >= y.len() && x2.len() >= y.len() && values.len() >= y.len());
+                let num_vectorised = y.len() - y.len() % $lanes;
+                let a1_vec = $set1(a1);
+                let a2_vec = $set1(a2);
+                let value_vec = $set1(value);
+                for offset in (0..num_vectorised).step_by($lanes) {
+                    let x1_vec = $loadu(x1.as_ptr().add(offset));
+                    let x2_vec = $loadu(x2.as_ptr().add(offset));
+                    let values_vec = $loadu(values.as_ptr().add(offset));
+                    // All bits are set for matching lanes, so the mask selects a2 or zero
+                    let matches = $and($cmp::<_CMP_EQ_OQ>(values_vec, value_vec), a2_vec);
+                    let y_vec = $add($add($mul(x1_vec, a1_vec), $mul(x2_vec, a2_vec)), matches);
+                    $storeu(y.as_mut_ptr().add(offset), y_vec);
+                }
+                Portable::linear_combination_plus_matches(
+                    &mut y[num_vectorised..], &x1[num_vectorised..], a1, &x2[num_vectorised..], a2,
+                    &values[num_vectorised..], value);
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+avx2_kernels!(f64, 4, _mm256_setzero_pd, _mm256_set1_pd, _mm256_loadu_pd, _mm256_storeu_pd,
+              _mm256_add_pd, _mm256_mul_pd, _mm256_and_pd, _mm256_cmp_pd);
+
+#[cfg(target_arch = "x86_64")]
+avx2_kernels!(f32, 8, _mm256_setzero_ps, _mm256_set1_ps, _mm256_loadu_ps, _mm256_storeu_ps,
+              _mm256_add_ps, _mm256_mul_ps, _mm256_and_ps, _mm256_cmp_ps);
+
+/// Kernels with 512 bit registers, which handle the tail with masked loads and stores, so that
+/// short rows (e.g., five distinct utility contributions) need a single iteration.
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Avx512;
+
+// Bit mask of the lanes which are within a slice of the given length, starting at offset
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn lane_mask(offset: usize, len: usize, lanes: usize) -> u32 {
+    let remaining = len - offset;
+    if remaining >= lanes { (1_u32 << lanes) - 1 } else { (1_u32 << remaining) - 1 }
+}
+
+// Implements the AVX-512 kernels for a float type with the corresponding intrinsics
+#[cfg(target_arch = "x86_64")]
+macro_rules! avx512_kernels {
+    ($float:ty, $lanes:expr, $mask:ty, $setzero:ident, $set1:ident, $maskz_loadu:ident,
+     $mask_storeu:ident, $add:ident, $mul:ident, $cmp_mask:ident, $maskz_mov:ident) => {
+
+        impl Kernels<$float> for Avx512 {
+
+            #[inline]
+            #[target_feature(enable = "avx512f,avx2")]
+            unsafe fn fill_zero(y: &mut [$float]) {
+                let zero = $setzero();
+                for offset in (0..y.len()).step_by($lanes) {
+                    let mask = lane_mask(offset, y.len(), $lanes) as $mask;
+                    $mask_storeu(y.as_mut_ptr().add(offset), mask, zero);
+                }
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx512f,avx2")]
+            unsafe fn linear_combination(
+                y: &mut [$float],
+                x1: &[$float],
+                a1: $float,
+                x2: &[$float],
+                a2: $float,
+            ) {
+                assert!(x1.len() >= y.len() && x2.len() >= y.len());
+                let a1_vec = $set1(a1);
+                let a2_vec = $set1(a2);
+                for offset in (0..y.len()).step_by($lanes) {
+                    let mask = lane_mask(offset, y.len(), $lanes) as $mask;
+                    let x1_vec = $maskz_loadu(mask, x1.as_ptr().add(offset));
+                    let x2_vec = $maskz_loadu(mask, x2.as_ptr().add(offset));
+                    let y_vec = $add($mul(x1_vec, a1_vec), $mul(x2_vec, a2_vec));
+                    $mask_storeu(y.as_mut_ptr().add(offset), mask, y_vec);
+                }
+            }
+
+            #[inline]
+            #[target_feature(enable = "avx512f,avx2")]
+            unsafe fn linear_combination_plus_matches(
+                y: &mut [$float],
+                x1: &[$float],
+                a1: $float,
+                x2: &[$float],
+                a2: $float,
+                values: &[$float],
+                value: $float,
+            ) {
+                assert!(x1.len() >= y.len() && x2.len() >= y.len() && values.len() >= y.len());
+                let a1_vec = $set1(a1);
+                let a2_vec = $set1(a2);
+                let value_vec = $set1(value);
+                for offset in (0..y.len()).step_by($lanes) {
+                    let mask = lane_mask(offset, y.len(), $lanes) as $mask;
+                    let x1_vec = $maskz_loadu(mask, x1.as_ptr().add(offset));
+                    let x2_vec = $maskz_loadu(mask, x2.as_ptr().add(offset));
+                    let values_vec = $maskz_loadu(mask, values.as_ptr().add(offset));
+                    let matches_mask = $cmp_mask::<_CMP_EQ_OQ>(values_vec, value_vec);
+                    let matches = $maskz_mov(matches_mask, a2_vec);
+                    let y_vec = $add($add($mul(x1_vec, a1_vec), $mul(x2_vec, a2_vec)), matches);
+                    $mask_storeu(y.as_mut_ptr().add(offset), mask, y_vec);
+                }
+            }
+        }
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+avx512_kernels!(f64, 8, __mmask8, _mm512_setzero_pd, _mm512_set1_pd, _mm512_maskz_loadu_pd,
+                _mm512_mask_storeu_pd, _mm512_add_pd, _mm512_mul_pd, _mm512_cmp_pd_mask,
+                _mm512_maskz_mov_pd);
+
+#[cfg(target_arch = "x86_64")]
+avx512_kernels!(f32, 16, __mmask16, _mm512_setzero_ps, _mm512_set1_ps, _mm512_maskz_loadu_ps,
+                _mm512_mask_storeu_ps, _mm512_add_ps, _mm512_mul_ps, _mm512_cmp_ps_mask,
+                _mm512_maskz_mov_ps);
+
+// Calls a kernel of the given instruction set, after checking that the CPU supports it
+macro_rules! dispatch {
+    ($instruction_set:expr, $kernel:ident($($argument:expr),*)) => {{
+        let instruction_set = $instruction_set;
+        assert!(instruction_set <= InstructionSet::detect(), "instruction set is not supported");
+        unsafe {
+            match instruction_set {
+                InstructionSet::Portable => Portable::$kernel($($argument),*),
+                #[cfg(target_arch = "x86_64")]
+                InstructionSet::Avx2 => Avx2::$kernel($($argument),*),
+                #[cfg(target_arch = "x86_64")]
+                InstructionSet::Avx512 => Avx512::$kernel($($argument),*),
+                #[cfg(not(target_arch = "x86_64"))]
+                _ => unreachable!(),
+            }
+        }
+    }};
+}
+
+pub(crate) fn linear_combination(
+    instruction_set: InstructionSet,
+    y: &mut [f64],
+    x1: &[f64],
+    a1: f64,
+    x2: &[f64],
+    a2: f64,
+) {
+    dispatch!(instruction_set, linear_combination(y, x1, a1, x2, a2))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compares the kernels of all available instruction sets to scalar code for a float type
+    macro_rules! check_kernels {
+        ($float:ty) => {
+            // Lengths around the register widths, to cover the vectorised loops and the tails
+            for len in 0..35 {
+                let x1: Vec<$float> = (0..len).map(|i| 1.0 / (i as $float + 3.0)).collect();
+                let x2: Vec<$float> = (0..len).map(|i| (i as $float * 0.37).sin().abs()).collect();
+                let values: Vec<$float> = (0..len).map(|i| (i % 3) as $float * 0.25).collect();
+
+                let expected: Vec<$float> = x1.iter().zip(x2.iter())
+                    .map(|(x1, x2)| x1 * 0.3 + x2 * 0.7)
+                    .collect();
+                let expected_plus_matches: Vec<$float> = expected.iter().zip(values.iter())
+                    .map(|(y, v)| if *v == 0.5 { y + 0.7 } else { *y })
+                    .collect();
+
+                for instruction_set in InstructionSet::available() {
+                    let mut y = vec![<$float>::NAN; len];
+                    dispatch!(instruction_set, linear_combination(&mut y, &x1, 0.3, &x2, 0.7));
+                    assert_eq!(y, expected, "{instruction_set:?}, length {len}");
+
+                    dispatch!(instruction_set,
+                        linear_combination_plus_matches(&mut y, &x1, 0.3, &x2, 0.7, &values, 0.5));
+                    assert_eq!(y, expected_plus_matches, "{instruction_set:?}, length {len}");
+
+                    dispatch!(instruction_set, fill_zero(&mut y));
+                    assert!(y.iter().all(|y| *y == 0.0), "{instruction_set:?}, length {len}");
+                }
+            }
+        };
+    }
+
+    #[test]
+    fn kernels_are_bit_identical_to_scalar_code() {
+        check_kernels!(f64);
+        check_kernels!(f32);
+    }
+
+    #[test]
+    fn detects_an_available_instruction_set() {
+        let available = InstructionSet::available();
+        assert_eq!(available[0], InstructionSet::Portable);
+        assert_eq!(*available.last().unwrap(), InstructionSet::detect());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::mle::mle_importance;
+use crate::mle::types::{Grouping, Regularisation, Retrieval};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// Evidence about a single source in the validation set, which helps to tell apart a weight
+/// learned from a single question from a weight learned from hundreds of questions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStatistics {
+    /// Number of retrievals in which the source appeared at all
+    pub num_retrievals: usize,
+    /// Number of retrievals in which the source appeared within the top-k
+    pub num_retrievals_in_top_k: usize,
+    /// Mean utility contribution of the source over all its appearances (0.0 if never retrieved)
+    pub mean_utility_contribution: f64,
+    /// Bootstrap confidence interval for the learned weight, if requested
+    pub confidence_interval: Option<(f64, f64)>,
+}
+
+/// Configuration for bootstrapping confidence intervals over the validation questions.
+#[derive(Debug, Clone)]
+pub struct Bootstrap {
+    pub(crate) num_resamples: usize,
+    pub(crate) confidence_level: f64,
+    pub(crate) seed: u64,
+}
+
+impl Bootstrap {
+    pub fn new(num_resamples: usize, confidence_level: f64, seed: u64) -> Self {
+        assert!(num_resamples > 0, "need at least one bootstrap resample");
+        assert!(confidence_level > 0.0 && confidence_level < 1.0,
+                "confidence level must be in (0, 1)");
+        Self { num_resamples, confidence_level, seed }
+    }
+}
+
+pub fn source_statistics(
+    retrievals: &[Retrieval],
+    corpus_size: usize,
+    k: usize,
+) -> Vec<SourceStatistics> {
+
+    let mut num_retrievals = vec![0_usize; corpus_size];
+    let mut num_retrievals_in_top_k = vec![0_usize; corpus_size];
+    let mut utility_sums = vec![0.0_f64; corpus_size];
+    let mut num_appearances = vec![0_usize; corpus_size];
+
+    // A source can appear several times in the same retrieval, so we remember the last
+    // retrieval in which we counted it.
+    let mut last_seen_in = vec![usize::MAX; corpus_size];
+    let mut last_seen_in_top_k = vec![usize::MAX; corpus_size];
+
+    for (retrieval_index, retrieval) in retrievals.iter().enumerate() {
+        let positions = retrieval.retrieved.iter().zip(retrieval.utility_contributions.iter());
+        for (position, (retrieved, utility_contribution)) in positions.enumerate() {
+            if last_seen_in[*retrieved] != retrieval_index {
+                last_seen_in[*retrieved] = retrieval_index;
+                num_retrievals[*retrieved] += 1;
+            }
+            if position < k && last_seen_in_top_k[*retrieved] != retrieval_index {
+                last_seen_in_top_k[*retrieved] = retrieval_index;
+                num_retrievals_in_top_k[*retrieved] += 1;
+            }
+            utility_sums[*retrieved] += utility_contribution;
+            num_appearances[*retrieved] += 1;
+        }
+    }
+
+    (0..corpus_size)
+        .map(|source| {
+            let mean_utility_contribution = if num_appearances[source] > 0 {
+                utility_sums[source] / num_appearances[source] as f64
+            } else {
+                0.0
+            };
+
+            SourceStatistics {
+                num_retrievals: num_retrievals[source],
+                num_retrievals_in_top_k: num_retrievals_in_top_k[source],
+                mean_utility_contribution,
+                confidence_interval: None,
+            }
+        })
+        .collect()
+}
+
+/// Evidence about every group of sources, where a group counts as retrieved (in the top-k)
+/// whenever one of its members is.
+pub fn group_statistics(
+    retrievals: &[Retrieval],
+    grouping: &Grouping,
+    k: usize,
+) -> Vec<SourceStatistics> {
+
+    let assignments = grouping.group_assignments();
+
+    let group_retrievals: Vec<Retrieval> = retrievals
+        .iter()
+        .map(|retrieval| {
+            Retrieval::with_weight(
+                retrieval.retrieved.iter().map(|source| assignments[*source]).collect(),
+                retrieval.utility_contributions.clone(),
+                retrieval.weight
+            )
+        })
+        .collect();
+
+    source_statistics(&group_retrievals, grouping.num_groups, k)
+}
+
+/// Percentile bootstrap: re-learns the weights on resamples (with replacement) of the
+/// validation questions and reports the central `confidence_level` interval per source. The
+/// resamples are processed in parallel, each with a single-threaded `mle_importance`. Resamples
+/// which only contain weightless questions are drawn again, so at least one question needs a
+/// positive weight.
+#[allow(clippy::too_many_arguments)]
+pub fn bootstrap_confidence_intervals(
+    retrievals: &[Retrieval],
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    bootstrap: &Bootstrap,
+    n_jobs: usize,
+) -> Vec<(f64, f64)> {
+
+    assert!(retrievals.iter().any(|retrieval| retrieval.weight > 0.0),
+            "need a retrieval with a positive weight");
+
+    // The seeds of the resamples are drawn from a single generator, so that the resamples of
+    // different seeds do not overlap and do not depend on the number of jobs
+    let mut seed_rng = StdRng::seed_from_u64(bootstrap.seed);
+    let resample_seeds: Vec<u64> = (0..bootstrap.num_resamples).map(|_| seed_rng.gen()).collect();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_jobs)
+        .build()
+        .unwrap();
+
+    let resampled_weights: Vec<Vec<f64>> = pool.install(|| {
+        resample_seeds
+            .into_par_iter()
+            .map(|resample_seed| {
+                let mut rng = StdRng::seed_from_u64(resample_seed);
+                let resampled_retrievals: Vec<Retrieval> = loop {
+                    let resample: Vec<Retrieval> = (0..retrievals.len())
+                        .map(|_| retrievals[rng.gen_range(0..retrievals.len())].clone())
+                        .collect();
+                    // The gradient is normalised by the total weight of the resample
+                    if resample.iter().any(|retrieval| retrieval.weight > 0.0) {
+                        break resample;
+                    }
+                };
+
+                mle_importance(
+                    resampled_retrievals,
+                    corpus_size,
+                    optional_grouping,
+                    optional_regularisation,
+                    k,
+                    learning_rate,
+                    num_epochs,
+                    1
+                )
+            })
+            .collect()
+    });
+
+    let alpha = (1.0 - bootstrap.confidence_level) / 2.0;
+
+    (0..corpus_size)
+        .map(|source| {
+            let mut samples: Vec<f64> = resampled_weights.iter().map(|v| v[source]).collect();
+            samples.sort_by(|a, b| a.total_cmp(b));
+            (percentile(&samples, alpha), percentile(&samples, 1.0 - alpha))
+        })
+        .collect()
+}
+
+// Linear interpolation between the closest ranks, as numpy.percentile does by default
+pub(crate) fn percentile(sorted_samples: &[f64], q: f64) -> f64 {
+    let position = q * (sorted_samples.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    sorted_samples[lower] + (sorted_samples[upper] - sorted_samples[lower]) * fraction
+}
+
+/// Learns the importance weights and reports per-source evidence alongside them. Confidence
+/// intervals are only computed if a bootstrap configuration is given.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_with_statistics(
+    retrievals: Vec<Retrieval>,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    optional_bootstrap: Option<&Bootstrap>,
+    n_jobs: usize,
+) -> (Vec<f64>, Vec<SourceStatistics>) {
+
+    let mut statistics = source_statistics(&retrievals, corpus_size, k);
+
+    if let Some(bootstrap) = optional_bootstrap {
+        let confidence_intervals = bootstrap_confidence_intervals(
+            &retrievals,
+            corpus_size,
+            optional_grouping,
+            optional_regularisation,
+            k,
+            learning_rate,
+            num_epochs,
+            bootstrap,
+            n_jobs
+        );
+
+        for (source_statistics, interval) in statistics.iter_mut().zip(confidence_intervals) {
+            source_statistics.confidence_interval = Some(interval);
+        }
+    }
+
+    let v = mle_importance(
+        retrievals,
+        corpus_size,
+        optional_grouping,
+        optional_regularisation,
+        k,
+        learning_rate,
+        num_epochs,
+        n_jobs
+    );
+
+    (v, statistics)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_appearances() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1, 2, 0], vec![1.0, 0.0, 1.0, 0.5]),
+            Retrieval::new(vec![2, 1], vec![0.0, 1.0]),
+        ];
+
+        let statistics = source_statistics(&retrievals, 4, 2);
+
+        assert_eq!(statistics[0].num_retrievals, 1);
+        assert_eq!(statistics[0].num_retrievals_in_top_k, 1);
+        assert_eq!(statistics[0].mean_utility_contribution, 0.75);
+
+        assert_eq!(statistics[1].num_retrievals, 2);
+        assert_eq!(statistics[1].num_retrievals_in_top_k, 2);
+        assert_eq!(statistics[1].mean_utility_contribution, 0.5);
+
+        assert_eq!(statistics[2].num_retrievals, 2);
+        assert_eq!(statistics[2].num_retrievals_in_top_k, 1);
+
+        assert_eq!(statistics[3].num_retrievals, 0);
+        assert_eq!(statistics[3].mean_utility_contribution, 0.0);
+    }
+
+    #[test]
+    fn counts_group_appearances() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 1.0]),
+            Retrieval::new(vec![2, 1], vec![0.0, 1.0]),
+        ];
+        let grouping = Grouping::new(2, vec![0, 0, 1]);
+
+        let statistics = group_statistics(&retrievals, &grouping, 1);
+
+        assert_eq!(statistics[0].num_retrievals, 2);
+        assert_eq!(statistics[0].num_retrievals_in_top_k, 1);
+        assert_eq!(statistics[0].mean_utility_contribution, 2.0 / 3.0);
+
+        assert_eq!(statistics[1].num_retrievals, 2);
+        assert_eq!(statistics[1].num_retrievals_in_top_k, 1);
+    }
+
+    #[test]
+    fn percentile_interpolates() {
+        let samples = vec![0.0, 1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&samples, 0.0), 0.0);
+        assert_eq!(percentile(&samples, 0.5), 2.0);
+        assert_eq!(percentile(&samples, 0.125), 0.5);
+        assert_eq!(percentile(&samples, 1.0), 4.0);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::mle::gradient::{self, Linear};
+use crate::mle::types::{Grouping, Regularisation, Retrieval};
+use crate::mle::{adjust_for_groups, discretise};
+
+use std::io;
+
+/// Retrievals which are read in chunks, once per epoch, e.g., from a file which does not fit into
+/// memory.
+pub trait RetrievalStream {
+    /// Passes all retrievals to `consume`, in chunks of at most `chunk_size` retrievals.
+    fn for_each_chunk(
+        &self,
+        chunk_size: usize,
+        consume: &mut dyn FnMut(Vec<Retrieval>),
+    ) -> io::Result<()>;
+}
+
+impl RetrievalStream for [Retrieval] {
+    fn for_each_chunk(
+        &self,
+        chunk_size: usize,
+        consume: &mut dyn FnMut(Vec<Retrieval>),
+    ) -> io::Result<()> {
+        for chunk in self.chunks(chunk_size) {
+            consume(chunk.to_vec());
+        }
+        Ok(())
+    }
+}
+
+/// Same as `mle_importance`, but reads the retrievals chunk by chunk from `retrievals` in every
+/// epoch and accumulates the gradients of the chunks in sparse maps. Only a single chunk is held in
+/// memory, so that we can train on validation logs which do not fit into memory. The memory still
+/// grows with the corpus size, as the weights and the gradient of the epoch (which is only applied
+/// after all chunks, like in `mle_importance`) are dense vectors of `corpus_size` floats, in
+/// addition to one sparse map per job with the sources retrieved in its part of the chunk. Fails
+/// with `InvalidInput` if `chunk_size` is zero.
+#[allow(clippy::too_many_arguments)]
+pub fn mle_importance_streaming<S: RetrievalStream + ?Sized>(
+    retrievals: &S,
+    corpus_size: usize,
+    optional_grouping: Option<&Grouping>,
+    optional_regularisation: Option<&Regularisation>,
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    n_jobs: usize,
+    chunk_size: usize,
+) -> io::Result<Vec<f64>> {
+
+    if chunk_size == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "chunks must contain at least one retrieval"));
+    }
+
+    let mut v = match optional_regularisation {
+        Some(regularisation) => {
+            assert_eq!(regularisation.priors().len(), corpus_size, "need one prior per source");
+            regularisation.priors().to_vec()
+        },
+        None => vec![0.5_f64; corpus_size],
+    };
+
+    #[allow(non_snake_case)]
+    let N = total_weight(retrievals, corpus_size, chunk_size)?;
+    // E.g., an empty file
+    if N <= 0.0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "the total weight of the retrievals must be positive"));
+    }
+
+    for _ in 0..num_epochs {
+        let mut g = vec![0.0_f64; corpus_size];
+
+        retrievals.for_each_chunk(chunk_size, &mut |mut chunk| {
+            if chunk.is_empty() {
+                return;
+            }
+
+            let (max_distinct_retrieved, max_distinct_utility_contributions) =
+                discretise(&mut chunk);
+
+            gradient::add_sparse_gradient::<Linear<f64>>(
+                &chunk,
+                &v,
+                k,
+                max_distinct_retrieved,
+                max_distinct_utility_contributions,
+                N,
+                n_jobs,
+                &mut g
+            );
+        })?;
+
+        if let Some(regularisation) = optional_regularisation {
+            regularisation.add_to_gradient(&v, &mut g);
+        }
+
+        for i in 0..v.len() {
+            // Clipping
+            v[i] = (v[i] + learning_rate * g[i]).clamp(0.0, 1.0);
+        }
+
+        if let Some(grouping) = optional_grouping {
+            adjust_for_groups(&mut v, grouping);
+        }
+    }
+
+    Ok(v)
+}
+
+// Reads the retrievals once to compute their total weight, and checks that they only retrieve
+// sources of the corpus, as we cannot validate them up front like for retrievals in memory
+fn total_weight<S: RetrievalStream + ?Sized>(
+    retrievals: &S,
+    corpus_size: usize,
+    chunk_size: usize,
+) -> io::Result<f64> {
+
+    let mut total_weight = 0.0;
+    let mut num_invalid = 0;
+
+    retrievals.for_each_chunk(chunk_size, &mut |chunk| {
+        for retrieval in chunk {
+            total_weight += retrieval.weight;
+            if retrieval.retrieved.iter().any(|source| *source >= corpus_size) {
+                num_invalid += 1;
+            }
+        }
+    })?;
+
+    if num_invalid > 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("{num_invalid} retrievals contain sources outside of the corpus")));
+    }
+
+    Ok(total_weight)
+}
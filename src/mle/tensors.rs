@@ -1,21 +1,23 @@
+use crate::mle::float::Float;
+use crate::mle::simd::{self, InstructionSet};
 use std::ops::{Index, IndexMut};
 use std::slice;
 
 #[derive(Clone)]
-pub struct DenseMatrix {
+pub struct DenseMatrix<T = f64> {
     num_rows: usize,
     num_columns: usize,
-    buffer: Vec<f64>,
+    buffer: Vec<T>,
 }
 
-impl DenseMatrix {
+impl<T: Float> DenseMatrix<T> {
     pub fn new(num_rows: usize, num_columns: usize) -> Self {
         Self::allocate_with_capacity(num_rows, num_columns, num_rows * num_columns)
     }
 
     pub fn allocate_with_capacity(num_rows: usize, num_columns: usize, capacity: usize) -> Self {
         assert!(capacity >= num_rows * num_columns);
-        let buffer = vec![0_f64; capacity];
+        let buffer = vec![T::zero(); capacity];
         Self { num_rows, num_columns, buffer }
     }
 
@@ -26,14 +28,34 @@ impl DenseMatrix {
         self.num_columns = num_columns;
     }
 
+    pub(crate) fn row_mut(&mut self, row: usize) -> &mut [T] {
+        let offset = row * self.num_columns;
+        &mut self.buffer[offset..offset + self.num_columns]
+    }
+
+    // The row y to write and the row x to read in a recurrence over the rows
+    pub(crate) fn rows(&mut self, y_row: usize, x_row: usize) -> (&mut [T], &[T]) {
+        assert!(y_row != x_row, "y must not alias x");
+        assert!((y_row.max(x_row) + 1) * self.num_columns <= self.buffer.len());
+
+        unsafe {
+            let y_offset = y_row * self.num_columns;
+            let x_offset = x_row * self.num_columns;
+            let y_ptr = self.buffer.as_mut_ptr().add(y_offset);
+            let y = slice::from_raw_parts_mut(y_ptr, self.num_columns);
+            let x = slice::from_raw_parts(self.buffer.as_ptr().add(x_offset), self.num_columns);
+            (y, x)
+        }
+    }
+
     #[allow(unused)] // used for testing only
-    pub fn view_buffer(&self) -> &[f64] {
+    pub fn view_buffer(&self) -> &[T] {
         &self.buffer
     }
 }
 
-impl Index<[usize; 2]> for DenseMatrix {
-    type Output = f64;
+impl<T> Index<[usize; 2]> for DenseMatrix<T> {
+    type Output = T;
 
     #[inline(always)]
     fn index(&self, index: [usize; 2]) -> &Self::Output {
@@ -46,7 +68,7 @@ impl Index<[usize; 2]> for DenseMatrix {
     }
 }
 
-impl IndexMut<[usize; 2]> for DenseMatrix {
+impl<T> IndexMut<[usize; 2]> for DenseMatrix<T> {
     #[inline(always)]
     fn index_mut(&mut self, index: [usize; 2]) -> &mut Self::Output {
         unsafe {
@@ -58,21 +80,19 @@ impl IndexMut<[usize; 2]> for DenseMatrix {
     }
 }
 
-pub struct DenseTensor {
+pub struct DenseTensor<T = f64> {
     #[allow(unused)]
     dim_1: usize,
     dim_2: usize,
     dim_3: usize,
-    buffer: Vec<f64>,
+    buffer: Vec<T>,
 }
 
-const CHUNK_SIZE: usize = 4;
-
-impl DenseTensor {
+impl<T: Float> DenseTensor<T> {
 
     #[allow(non_snake_case)]
     pub fn new(dim_1: usize, dim_2: usize, dim_3: usize) -> Self {
-        let buffer = vec![0_f64; dim_1 * dim_2 * dim_3];
+        let buffer = vec![T::zero(); dim_1 * dim_2 * dim_3];
         Self { dim_1, dim_2, dim_3, buffer }
     }
 
@@ -85,64 +105,63 @@ impl DenseTensor {
     }
 
     #[inline(always)]
-    pub(crate) fn set_y_to_x1_a1_plus_x2_a2(
+    fn offset_of(&self, indices: [usize; 2]) -> usize {
+        indices[0] * self.dim_2 * self.dim_3 + indices[1] * self.dim_3
+    }
+
+    #[inline(always)]
+    pub(crate) fn row_mut(&mut self, indices: [usize; 2]) -> &mut [T] {
+        let offset = self.offset_of(indices);
+        &mut self.buffer[offset..offset + self.dim_3]
+    }
+
+    // Mutable row y and immutable rows x1 and x2 (along the third dimension) at the same time
+    #[inline(always)]
+    pub(crate) fn rows(
         &mut self,
         y_indices: [usize; 2],
         x1_indices: [usize; 2],
-        a1: f64,
         x2_indices: [usize; 2],
-        a2: f64,
-    ) {
+    ) -> (&mut [T], &[T], &[T]) {
+        assert!(y_indices != x1_indices && y_indices != x2_indices, "y must not alias x1 or x2");
 
-        let y_offset =
-            (y_indices[0] * self.dim_2 * self.dim_3 + y_indices[1] * self.dim_3) as isize;
-        let x1_offset =
-            (x1_indices[0] * self.dim_2 * self.dim_3 + x1_indices[1] * self.dim_3) as isize;
-        let x2_offset =
-            (x2_indices[0] * self.dim_2 * self.dim_3 + x2_indices[1] * self.dim_3) as isize;
-
-        let y_vec = unsafe {
-            slice::from_raw_parts_mut(self.buffer.as_mut_ptr().offset(y_offset), self.dim_3)
-        };
-        let x1_vec = unsafe {
-            slice::from_raw_parts(self.buffer.as_ptr().offset(x1_offset), self.dim_3)
-        };
-        let x2_vec = unsafe {
-            slice::from_raw_parts(self.buffer.as_ptr().offset(x2_offset), self.dim_3)
-        };
-
-        let y_chunked = y_vec.chunks_exact_mut(CHUNK_SIZE);
-        let x1_chunked = x1_vec.chunks_exact(CHUNK_SIZE);
-        let x2_chunked = x2_vec.chunks_exact(CHUNK_SIZE);
-
-        let outer_iter = y_chunked.zip(x1_chunked).zip(x2_chunked);
-
-        for ((y_chunk, x1_chunk), x2_chunk) in outer_iter {
-            let inner_iter = y_chunk.iter_mut().zip(x1_chunk.iter()).zip(x2_chunk.iter());
-            for ((y, x1), x2) in inner_iter {
-                *y = *x1 * a1 + *x2 * a2;
-            }
-        }
-
-        let y_remainder = y_vec.chunks_exact_mut(CHUNK_SIZE).into_remainder().iter_mut();
-        let x1_remainder = x1_vec.chunks_exact(CHUNK_SIZE).remainder().iter();
-        let x2_remainder = x2_vec.chunks_exact(CHUNK_SIZE).remainder().iter();
-
-        let remainders = y_remainder.zip(x1_remainder).zip(x2_remainder);
+        let y_offset = self.offset_of(y_indices);
+        let x1_offset = self.offset_of(x1_indices);
+        let x2_offset = self.offset_of(x2_indices);
+        assert!(y_offset.max(x1_offset).max(x2_offset) + self.dim_3 <= self.buffer.len());
 
-        for ((y, x1), x2) in remainders {
-            *y = *x1 * a1 + *x2 * a2;
+        unsafe {
+            let y = slice::from_raw_parts_mut(self.buffer.as_mut_ptr().add(y_offset), self.dim_3);
+            let x1 = slice::from_raw_parts(self.buffer.as_ptr().add(x1_offset), self.dim_3);
+            let x2 = slice::from_raw_parts(self.buffer.as_ptr().add(x2_offset), self.dim_3);
+            (y, x1, x2)
         }
     }
 
     #[allow(unused)] // used for testing only
-    pub fn view_buffer(&self) -> &[f64] {
+    pub fn view_buffer(&self) -> &[T] {
         &self.buffer
     }
 }
 
-impl Index<[usize; 3]> for DenseTensor {
-    type Output = f64;
+impl DenseTensor<f64> {
+
+    #[allow(unused)] // used for testing only
+    pub(crate) fn set_y_to_x1_a1_plus_x2_a2(
+        &mut self,
+        y_indices: [usize; 2],
+        x1_indices: [usize; 2],
+        a1: f64,
+        x2_indices: [usize; 2],
+        a2: f64,
+    ) {
+        let (y, x1, x2) = self.rows(y_indices, x1_indices, x2_indices);
+        simd::linear_combination(InstructionSet::detect(), y, x1, a1, x2, a2);
+    }
+}
+
+impl<T> Index<[usize; 3]> for DenseTensor<T> {
+    type Output = T;
 
     #[inline(always)]
     fn index(&self, index: [usize; 3]) -> &Self::Output {
@@ -156,7 +175,7 @@ impl Index<[usize; 3]> for DenseTensor {
     }
 }
 
-impl IndexMut<[usize; 3]> for DenseTensor {
+impl<T> IndexMut<[usize; 3]> for DenseTensor<T> {
     #[inline(always)]
     fn index_mut(&mut self, index: [usize; 3]) -> &mut Self::Output {
         unsafe {
@@ -1,5 +1,7 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-
+#[derive(Debug, Clone)]
 pub struct Grouping {
     pub(crate) num_groups: usize,
     group_per_retrieved: Vec<usize>,
@@ -10,21 +12,86 @@ impl Grouping {
         Self { num_groups, group_per_retrieved }
     }
 
+    pub fn num_groups(&self) -> usize {
+        self.num_groups
+    }
+
     pub fn group_assignments(&self) -> &[usize] {
         &self.group_per_retrieved
     }
 }
 
-#[derive(Debug, Clone)]
+/// A multi-level grouping such as URL -> host -> domain -> organisation. The first level groups
+/// the sources, every further level groups the groups of the level below. During training, the
+/// weight of each element is shrunk towards the weight of its group with the strength of the
+/// level, e.g., a page towards its host, the host towards its domain, and so on.
+pub struct Hierarchy {
+    levels: Vec<Grouping>,
+    strengths: Vec<f64>,
+}
+
+impl Hierarchy {
+    pub fn new(levels: Vec<Grouping>, strengths: Vec<f64>) -> Self {
+        assert!(!levels.is_empty(), "a hierarchy needs at least one level");
+        assert_eq!(levels.len(), strengths.len(), "need one strength per level");
+        for strength in &strengths {
+            assert!((0.0..=1.0).contains(strength), "strengths must be in [0, 1]");
+        }
+        for (lower, upper) in levels.iter().tuple_windows() {
+            assert_eq!(lower.num_groups, upper.group_assignments().len(),
+                "every group of a level must be assigned to a group of the next level");
+        }
+        Self { levels, strengths }
+    }
+
+    pub fn levels(&self) -> &[Grouping] {
+        &self.levels
+    }
+
+    pub fn strengths(&self) -> &[f64] {
+        &self.strengths
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Retrieval {
     pub(crate) retrieved: Vec<usize>,
     pub(crate) utility_contributions: Vec<f64>,
+    pub(crate) weight: f64,
+    /// When the question was asked, in seconds since the Unix epoch
+    #[serde(default)]
+    pub(crate) timestamp: Option<i64>,
 }
 
 impl Retrieval {
 
     pub fn new(retrieved: Vec<usize>, utility_contributions: Vec<f64>) -> Self {
-        Self { retrieved, utility_contributions }
+        Self::with_weight(retrieved, utility_contributions, 1.0)
+    }
+
+    /// A retrieval whose contribution to the objective and gradient is scaled by `weight`,
+    /// relative to the total weight of all retrievals (e.g., to emphasise high-traffic queries).
+    pub fn with_weight(
+        retrieved: Vec<usize>,
+        utility_contributions: Vec<f64>,
+        weight: f64,
+    ) -> Self {
+        assert!(weight >= 0.0 && weight.is_finite(), "weight must be non-negative and finite");
+        Self { retrieved, utility_contributions, weight, timestamp: None }
+    }
+
+    /// The same retrieval, asked at `timestamp` (in seconds since the Unix epoch), e.g., to decay
+    /// its weight with `TimeDecay`.
+    pub fn at(self, timestamp: i64) -> Self {
+        Self { timestamp: Some(timestamp), ..self }
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
     }
 
     pub(crate) fn existence_probabilities(&self, v: &[f64]) -> Vec<f64> {
@@ -34,3 +101,125 @@ impl Retrieval {
             .collect()
     }
 }
+
+/// Exponential decay of the weights of retrievals with their age, so that recent evidence about
+/// the quality of a source dominates older evidence. The weight of a retrieval asked at time `t`
+/// is multiplied by `0.5^((as_of - t) / half_life)`, retrievals asked after `as_of` are dropped,
+/// which allows to re-learn the weights as they would have been learned at that time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeDecay {
+    half_life: f64,
+    as_of: i64,
+}
+
+impl TimeDecay {
+
+    /// The half life is given in seconds, an infinite half life only drops the retrievals asked
+    /// after `as_of`.
+    pub fn new(half_life: f64, as_of: i64) -> Self {
+        assert!(half_life > 0.0, "half life must be positive");
+        Self { half_life, as_of }
+    }
+
+    /// Decays the weights relative to the latest timestamp of the retrievals, i.e., uses all of
+    /// them. None if no retrieval has a timestamp.
+    pub fn as_of_latest(half_life: f64, retrievals: &[Retrieval]) -> Option<Self> {
+        retrievals.iter()
+            .filter_map(|retrieval| retrieval.timestamp)
+            .max()
+            .map(|as_of| Self::new(half_life, as_of))
+    }
+
+    pub fn half_life(&self) -> f64 {
+        self.half_life
+    }
+
+    pub fn as_of(&self) -> i64 {
+        self.as_of
+    }
+
+    /// Factor for the weight of a retrieval asked at `timestamp`, zero after `as_of`.
+    pub fn factor(&self, timestamp: i64) -> f64 {
+        if timestamp > self.as_of {
+            0.0
+        } else {
+            // The age can exceed the range of i64 for timestamps far apart
+            let age = (self.as_of as i128 - timestamp as i128) as f64;
+            0.5_f64.powf(age / self.half_life)
+        }
+    }
+
+    /// Decays the weights of the retrievals asked until `as_of` and drops all later ones. Every
+    /// retrieval needs a timestamp.
+    pub fn apply(&self, retrievals: Vec<Retrieval>) -> Vec<Retrieval> {
+        retrievals.into_iter()
+            .map(|retrieval| {
+                let timestamp = retrieval.timestamp.expect("time decay needs timestamps");
+                (retrieval, timestamp)
+            })
+            .filter(|(_, timestamp)| *timestamp <= self.as_of)
+            .map(|(retrieval, timestamp)| {
+                let weight = retrieval.weight * self.factor(timestamp);
+                Retrieval { weight, ..retrieval }
+            })
+            .collect()
+    }
+}
+
+/// Regularisation of the existence variables. The L2 term pulls each weight towards its prior
+/// value, the L1 term pushes weights towards zero (i.e., towards pruning the source). Both terms
+/// are scaled by a per-source strength, so that sources with little evidence stay near the prior.
+#[derive(Debug, Clone)]
+pub struct Regularisation {
+    pub(crate) l2: f64,
+    pub(crate) l1: f64,
+    priors: Vec<f64>,
+    strengths: Vec<f64>,
+}
+
+impl Regularisation {
+
+    pub fn new(l2: f64, l1: f64, priors: Vec<f64>, strengths: Vec<f64>) -> Self {
+        assert_eq!(priors.len(), strengths.len(), "need one prior strength per prior");
+        assert!(l2 >= 0.0 && l2.is_finite(), "l2 must be non-negative and finite");
+        assert!(l1 >= 0.0 && l1.is_finite(), "l1 must be non-negative and finite");
+        for prior in &priors {
+            assert!((0.0..=1.0).contains(prior), "priors must be in [0, 1]");
+        }
+        for strength in &strengths {
+            assert!(*strength >= 0.0 && strength.is_finite(),
+                    "prior strengths must be non-negative and finite");
+        }
+        Self { l2, l1, priors, strengths }
+    }
+
+    pub fn uniform(l2: f64, l1: f64, prior: f64, corpus_size: usize) -> Self {
+        Self::new(l2, l1, vec![prior; corpus_size], vec![1.0; corpus_size])
+    }
+
+    pub fn priors(&self) -> &[f64] {
+        &self.priors
+    }
+
+    pub fn strengths(&self) -> &[f64] {
+        &self.strengths
+    }
+
+    pub fn penalty(&self, v: &[f64]) -> f64 {
+        v.iter()
+            .zip(self.priors.iter().zip(self.strengths.iter()))
+            .map(|(v_i, (prior, strength))| {
+                let difference = v_i - prior;
+                strength * (0.5 * self.l2 * difference * difference + self.l1 * v_i.abs())
+            })
+            .sum()
+    }
+
+    // Adds the gradient of the negative penalty, as we maximise the objective
+    pub(crate) fn add_to_gradient(&self, v: &[f64], g: &mut [f64]) {
+        for i in 0..g.len() {
+            let difference = v[i] - self.priors[i];
+            g[i] -= self.strengths[i] * (self.l2 * difference + self.l1 * v[i].signum());
+        }
+    }
+}
@@ -0,0 +1,672 @@
+use crate::mle;
+use crate::mle::types::{Grouping, Regularisation, Retrieval};
+use crate::mle::{Reduction, UTILITY_DECIMALS};
+use crate::online::OnlineState;
+
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the on-disk format, which is increased whenever the layout of `Model` changes.
+pub const FORMAT_VERSION: u32 = 3;
+
+// Prefix of the binary variant, followed by the format version (little endian u32)
+const MAGIC: &[u8; 4] = b"RAGB";
+
+/// The hyperparameters the weights were learned with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hyperparameters {
+    pub k: usize,
+    pub learning_rate: f64,
+    pub num_epochs: usize,
+    pub l2: f64,
+    pub l1: f64,
+    pub prior: f64,
+    pub tie_groups: bool,
+    /// Half life of the time decay the retrievals were decayed with before learning (see
+    /// `mle::decayed`), None for no decay or an infinite half life
+    #[serde(default)]
+    pub half_life_days: Option<f64>,
+    /// Time (in seconds since the epoch) the retrievals were decayed as of
+    #[serde(default)]
+    pub as_of: Option<i64>,
+    /// How the gradients were summed up over parallel jobs
+    #[serde(default)]
+    pub reduction: Reduction,
+}
+
+impl Hyperparameters {
+    /// The uniform regularisation of the hyperparameters, None if they do not regularise (and
+    /// start from weights of 0.5).
+    pub fn regularisation(&self, corpus_size: usize) -> Option<Regularisation> {
+        if self.l2 != 0.0 || self.l1 != 0.0 || self.prior != 0.5 {
+            Some(Regularisation::uniform(self.l2, self.l1, self.prior, corpus_size))
+        } else {
+            None
+        }
+    }
+}
+
+// Layout of the hyperparameters up to version 2, which did not record the time decay and the
+// reduction
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct HyperparametersV2 {
+    k: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    l2: f64,
+    l1: f64,
+    prior: f64,
+    tie_groups: bool,
+}
+
+impl From<HyperparametersV2> for Hyperparameters {
+    fn from(hyperparameters: HyperparametersV2) -> Self {
+        Self {
+            k: hyperparameters.k,
+            learning_rate: hyperparameters.learning_rate,
+            num_epochs: hyperparameters.num_epochs,
+            l2: hyperparameters.l2,
+            l1: hyperparameters.l1,
+            prior: hyperparameters.prior,
+            tie_groups: hyperparameters.tie_groups,
+            half_life_days: None,
+            as_of: None,
+            reduction: Reduction::PerJob,
+        }
+    }
+}
+
+/// How the utility contributions were discretised during training.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Discretisation {
+    /// Rounded to the given number of decimals
+    Round { decimals: i32 },
+}
+
+impl Default for Discretisation {
+    fn default() -> Self {
+        Discretisation::Round { decimals: UTILITY_DECIMALS }
+    }
+}
+
+/// Identifies the validation set the weights were learned on, to detect when a model is used with
+/// (or updated on) different data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatasetFingerprint {
+    pub num_questions: usize,
+    pub num_sources: usize,
+    /// FNV-1a hash of the retrieved sources, utility contributions, weights and timestamps, as hex
+    /// string
+    pub hash: String,
+}
+
+impl DatasetFingerprint {
+    pub fn of(retrievals: &[Retrieval], corpus_size: usize) -> Self {
+        let mut fingerprint = Self::empty(corpus_size);
+        fingerprint.extend(retrievals);
+        fingerprint
+    }
+
+    /// The fingerprint of a validation set without questions.
+    pub fn empty(corpus_size: usize) -> Self {
+        Self {
+            num_questions: 0,
+            num_sources: corpus_size,
+            // FNV-1a offset basis
+            hash: format!("{:016x}", 0xcbf29ce484222325_u64),
+        }
+    }
+
+    /// Adds questions to the fingerprint, such that extending the fingerprint of some questions by
+    /// further questions gives the fingerprint of all of them.
+    pub fn extend(&mut self, retrievals: &[Retrieval]) {
+        // FNV-1a, as its result does not depend on the platform or the Rust version
+        let mut hash = u64::from_str_radix(&self.hash, 16).expect("invalid fingerprint hash");
+        let mut update = |bytes: [u8; 8]| {
+            for byte in bytes {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        for retrieval in retrievals {
+            update((retrieval.retrieved.len() as u64).to_le_bytes());
+            for (source, utility_contribution) in
+                retrieval.retrieved.iter().zip(retrieval.utility_contributions.iter()) {
+                update((*source as u64).to_le_bytes());
+                update(utility_contribution.to_bits().to_le_bytes());
+            }
+            update(retrieval.weight.to_bits().to_le_bytes());
+            // Only hashed if present, so that the fingerprints of untimed data stay the same
+            if let Some(timestamp) = retrieval.timestamp {
+                update(timestamp.to_le_bytes());
+            }
+        }
+
+        self.num_questions += retrievals.len();
+        self.hash = format!("{hash:016x}");
+    }
+}
+
+/// Grouping of the sources, with the weights of the groups.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelGrouping {
+    pub group_names: Vec<String>,
+    pub group_per_source: Vec<usize>,
+    pub group_weights: Vec<f64>,
+}
+
+/// A single (re-)training of the weights.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub num_epochs: usize,
+    pub fingerprint: DatasetFingerprint,
+    /// Value of the objective on the training data after training
+    pub objective: f64,
+}
+
+/// Learned importance weights together with everything needed to interpret, reproduce and
+/// continue training them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Model {
+    pub format_version: u32,
+    pub source_names: Vec<String>,
+    pub weights: Vec<f64>,
+    pub grouping: Option<ModelGrouping>,
+    pub hyperparameters: Hyperparameters,
+    pub discretisation: Discretisation,
+    pub fingerprint: DatasetFingerprint,
+    pub history: Vec<HistoryEntry>,
+    /// State of the online learner the weights were learned with, to continue online training
+    #[serde(default)]
+    pub online: Option<OnlineState>,
+}
+
+// Layout of version 1, which had no online state
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct ModelV1 {
+    format_version: u32,
+    source_names: Vec<String>,
+    weights: Vec<f64>,
+    grouping: Option<ModelGrouping>,
+    hyperparameters: HyperparametersV2,
+    discretisation: Discretisation,
+    fingerprint: DatasetFingerprint,
+    history: Vec<HistoryEntry>,
+}
+
+impl From<ModelV1> for Model {
+    fn from(model: ModelV1) -> Self {
+        Self {
+            format_version: model.format_version,
+            source_names: model.source_names,
+            weights: model.weights,
+            grouping: model.grouping,
+            hyperparameters: model.hyperparameters.into(),
+            discretisation: model.discretisation,
+            fingerprint: model.fingerprint,
+            history: model.history,
+            online: None,
+        }
+    }
+}
+
+// Layout of version 2, whose hyperparameters did not record the time decay and the reduction
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct ModelV2 {
+    format_version: u32,
+    source_names: Vec<String>,
+    weights: Vec<f64>,
+    grouping: Option<ModelGrouping>,
+    hyperparameters: HyperparametersV2,
+    discretisation: Discretisation,
+    fingerprint: DatasetFingerprint,
+    history: Vec<HistoryEntry>,
+    online: Option<OnlineState>,
+}
+
+impl From<ModelV2> for Model {
+    fn from(model: ModelV2) -> Self {
+        Self {
+            format_version: model.format_version,
+            source_names: model.source_names,
+            weights: model.weights,
+            grouping: model.grouping,
+            hyperparameters: model.hyperparameters.into(),
+            discretisation: model.discretisation,
+            fingerprint: model.fingerprint,
+            history: model.history,
+            online: model.online,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Model {
+    pub fn new(
+        source_names: Vec<String>,
+        weights: Vec<f64>,
+        hyperparameters: Hyperparameters,
+        fingerprint: DatasetFingerprint,
+    ) -> Self {
+        assert_eq!(source_names.len(), weights.len(), "need one name per weight");
+        Self {
+            format_version: FORMAT_VERSION,
+            source_names,
+            weights,
+            grouping: None,
+            hyperparameters,
+            discretisation: Discretisation::default(),
+            fingerprint,
+            history: Vec::new(),
+            online: None,
+        }
+    }
+
+    pub fn with_grouping(
+        mut self,
+        group_names: Vec<String>,
+        grouping: &Grouping,
+        group_weights: Vec<f64>,
+    ) -> Self {
+        assert_eq!(grouping.group_assignments().len(), self.source_names.len(),
+                   "need one group per source");
+        assert_eq!(group_names.len(), grouping.num_groups, "need one name per group");
+        assert_eq!(group_weights.len(), grouping.num_groups, "need one weight per group");
+        self.grouping = Some(ModelGrouping {
+            group_names,
+            group_per_source: grouping.group_assignments().to_vec(),
+            group_weights,
+        });
+        self
+    }
+
+    pub fn record_training(&mut self, num_epochs: usize, objective: f64) {
+        self.history.push(HistoryEntry {
+            num_epochs,
+            fingerprint: self.fingerprint.clone(),
+            objective,
+        });
+    }
+
+    /// Checks that the model was written by a compatible version, is consistent and was trained
+    /// with the discretisation this version of the library uses.
+    pub fn check_compatibility(&self) -> io::Result<()> {
+        if self.format_version == 0 || self.format_version > FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported format version {}, this version supports up to {}",
+                self.format_version, FORMAT_VERSION)));
+        }
+        if self.weights.len() != self.source_names.len() {
+            return Err(invalid_data(format!("{} weights for {} sources",
+                self.weights.len(), self.source_names.len())));
+        }
+        if self.weights.iter().any(|weight| !(0.0..=1.0).contains(weight)) {
+            return Err(invalid_data("weights must be in [0, 1]".to_owned()));
+        }
+        let hyperparameters = &self.hyperparameters;
+        if !(hyperparameters.l2 >= 0.0 && hyperparameters.l2.is_finite()
+            && hyperparameters.l1 >= 0.0 && hyperparameters.l1.is_finite()
+            && (0.0..=1.0).contains(&hyperparameters.prior)) {
+            return Err(invalid_data("invalid regularisation hyperparameters".to_owned()));
+        }
+        if hyperparameters.half_life_days.is_some_and(|days| !(days > 0.0 && days.is_finite())) {
+            return Err(invalid_data("invalid half life".to_owned()));
+        }
+        if let Some(grouping) = &self.grouping {
+            let num_groups = grouping.group_names.len();
+            if grouping.group_per_source.len() != self.source_names.len()
+                || grouping.group_weights.len() != num_groups
+                || grouping.group_per_source.iter().any(|group| *group >= num_groups) {
+                return Err(invalid_data("inconsistent grouping".to_owned()));
+            }
+        }
+        if u64::from_str_radix(&self.fingerprint.hash, 16).is_err() {
+            return Err(invalid_data("invalid fingerprint hash".to_owned()));
+        }
+        if let Some(online) = &self.online {
+            online.check_compatibility(self.source_names.len())?;
+        }
+        if self.discretisation != Discretisation::default() {
+            return Err(invalid_data(format!(
+                "trained with discretisation {:?}, but this version uses {:?}",
+                self.discretisation, Discretisation::default())));
+        }
+        Ok(())
+    }
+
+    pub fn save_json(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    pub fn save_binary(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, self)
+            .map_err(|error| invalid_data(error.to_string()))?;
+        writer.flush()
+    }
+
+    /// Loads a model in either format and checks its compatibility.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let is_binary = reader.fill_buf()?.starts_with(MAGIC);
+
+        let model: Model = if is_binary {
+            let mut header = [0_u8; 8];
+            reader.read_exact(&mut header)?;
+            let format_version = u32::from_le_bytes(header[4..].try_into().unwrap());
+            // The layout of the remaining bytes depends on the version, so check it first
+            if format_version == 0 || format_version > FORMAT_VERSION {
+                return Err(invalid_data(format!(
+                    "unsupported format version {}, this version supports up to {}",
+                    format_version, FORMAT_VERSION)));
+            }
+            let decode_error = |error: bincode::Error| invalid_data(error.to_string());
+            match format_version {
+                1 => bincode::deserialize_from::<_, ModelV1>(reader).map_err(decode_error)?.into(),
+                2 => bincode::deserialize_from::<_, ModelV2>(reader).map_err(decode_error)?.into(),
+                _ => bincode::deserialize_from(reader).map_err(decode_error)?,
+            }
+        } else {
+            serde_json::from_reader(reader)?
+        };
+
+        model.check_compatibility()?;
+        Ok(model)
+    }
+}
+
+/// Learns the weights for the given hyperparameters (with a shared weight per group if
+/// `tie_groups` is set) and wraps them into a model, whose history records this training. With
+/// the `Deterministic` reduction of the hyperparameters, the weights do not depend on `n_jobs`.
+pub fn learn_model(
+    retrievals: Vec<Retrieval>,
+    source_names: Vec<String>,
+    optional_grouping: Option<(&Grouping, Vec<String>)>,
+    hyperparameters: Hyperparameters,
+    n_jobs: usize,
+) -> Model {
+
+    let corpus_size = source_names.len();
+    if let Some((grouping, _)) = &optional_grouping {
+        assert_eq!(grouping.group_assignments().len(), corpus_size, "need one group per source");
+    }
+    let fingerprint = DatasetFingerprint::of(&retrievals, corpus_size);
+
+    let regularisation = hyperparameters.regularisation(corpus_size);
+
+    let (v, optional_v_groups) = match &optional_grouping {
+        Some((grouping, _)) if hyperparameters.tie_groups => {
+            let (v_groups, v) = mle::train_tied(
+                retrievals.clone(),
+                corpus_size,
+                grouping,
+                regularisation.as_ref(),
+                hyperparameters.k,
+                hyperparameters.learning_rate,
+                hyperparameters.num_epochs,
+                n_jobs,
+                hyperparameters.reduction
+            );
+            (v, Some(v_groups))
+        },
+        _ => {
+            let learn = match hyperparameters.reduction {
+                Reduction::PerJob => mle::mle_importance,
+                Reduction::Deterministic => mle::mle_importance_deterministic,
+            };
+            let v = learn(
+                retrievals.clone(),
+                corpus_size,
+                optional_grouping.as_ref().map(|(grouping, _)| *grouping),
+                regularisation.as_ref(),
+                hyperparameters.k,
+                hyperparameters.learning_rate,
+                hyperparameters.num_epochs,
+                n_jobs
+            );
+            let optional_v_groups = optional_grouping.as_ref()
+                .map(|(grouping, _)| mle::v_grouped(&v, grouping));
+            (v, optional_v_groups)
+        },
+    };
+
+    let objective = mle::objective::objective(
+        &retrievals, &v, hyperparameters.k, regularisation.as_ref(), n_jobs);
+    let num_epochs = hyperparameters.num_epochs;
+
+    let mut model = Model::new(source_names, v, hyperparameters, fingerprint);
+    if let (Some((grouping, group_names)), Some(v_groups)) =
+        (optional_grouping, optional_v_groups) {
+        model = model.with_grouping(group_names, grouping, v_groups);
+    }
+    model.record_training(num_epochs, objective);
+
+    model
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toy_model() -> Model {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![1.0, 0.0]),
+            Retrieval::new(vec![2, 1], vec![0.5, 1.0]),
+        ];
+        let hyperparameters = Hyperparameters {
+            k: 1, learning_rate: 0.1, num_epochs: 5, l2: 0.0, l1: 0.0, prior: 0.5,
+            tie_groups: false, half_life_days: Some(30.0), as_of: Some(1_700_000_000),
+            reduction: Reduction::Deterministic,
+        };
+        let mut model = Model::new(
+            vec!["a.com".to_owned(), "b.com".to_owned(), "c.com".to_owned()],
+            // Weights whose shortest decimal representations need all 17 digits
+            vec![0.7, 0.1 + 0.2, 0.9999999999999999],
+            hyperparameters,
+            DatasetFingerprint::of(&retrievals, 3)
+        ).with_grouping(
+            vec!["a".to_owned(), "bc".to_owned()],
+            &Grouping::new(2, vec![0, 1, 1]),
+            vec![0.7, 0.375]
+        );
+        model.record_training(5, 0.42);
+        model
+    }
+
+    #[test]
+    fn round_trips_in_both_formats() {
+        let model = toy_model();
+        let directory = std::env::temp_dir();
+
+        let json_path = directory.join(format!("ragbooster_model_{}.json", std::process::id()));
+        model.save_json(json_path.to_str().unwrap()).unwrap();
+        assert_eq!(Model::load(json_path.to_str().unwrap()).unwrap(), model);
+
+        let binary_path = directory.join(format!("ragbooster_model_{}.bin", std::process::id()));
+        model.save_binary(binary_path.to_str().unwrap()).unwrap();
+        assert_eq!(Model::load(binary_path.to_str().unwrap()).unwrap(), model);
+
+        assert!(std::fs::metadata(&binary_path).unwrap().len()
+            < std::fs::metadata(&json_path).unwrap().len());
+
+        std::fs::remove_file(json_path).unwrap();
+        std::fs::remove_file(binary_path).unwrap();
+    }
+
+    #[test]
+    fn rejects_incompatible_models() {
+        let mut model = toy_model();
+        model.format_version = FORMAT_VERSION + 1;
+        assert!(model.check_compatibility().is_err());
+
+        let mut model = toy_model();
+        model.discretisation = Discretisation::Round { decimals: 3 };
+        assert!(model.check_compatibility().is_err());
+
+        let mut model = toy_model();
+        model.weights.pop();
+        assert!(model.check_compatibility().is_err());
+
+        let mut model = toy_model();
+        model.hyperparameters.half_life_days = Some(0.0);
+        assert!(model.check_compatibility().is_err());
+    }
+
+    #[test]
+    fn learned_model_records_training() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![1.0, 0.0]),
+            Retrieval::new(vec![2, 1], vec![1.0, 0.0]),
+        ];
+        let hyperparameters = Hyperparameters {
+            k: 1, learning_rate: 0.1, num_epochs: 5, l2: 0.0, l1: 0.0, prior: 0.5, tie_groups: true,
+            half_life_days: None, as_of: None, reduction: Reduction::PerJob,
+        };
+        let names = vec!["a.com".to_owned(), "b.com".to_owned(), "c.com".to_owned()];
+        let grouping = Grouping::new(2, vec![0, 1, 0]);
+
+        let model = learn_model(retrievals.clone(), names, Some((&grouping, vec!["ac".to_owned(),
+            "b".to_owned()])), hyperparameters, 1);
+
+        assert_eq!(model.fingerprint, DatasetFingerprint::of(&retrievals, 3));
+        assert_eq!(model.history.len(), 1);
+        assert_eq!(model.weights[0], model.weights[2]);
+        assert_eq!(model.grouping.as_ref().unwrap().group_weights[0], model.weights[0]);
+        assert!(model.weights[0] > model.weights[1]);
+        model.check_compatibility().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "need one group per source")]
+    fn grouping_needs_a_group_per_source() {
+        let grouping = Grouping::new(1, vec![0, 0]);
+        toy_model().with_grouping(vec!["all".to_owned()], &grouping, vec![0.5]);
+    }
+
+    // The hyperparameters of the toy model in the layout of versions 1 and 2
+    fn hyperparameters_v2(model: &Model) -> HyperparametersV2 {
+        let hyperparameters = &model.hyperparameters;
+        HyperparametersV2 {
+            k: hyperparameters.k,
+            learning_rate: hyperparameters.learning_rate,
+            num_epochs: hyperparameters.num_epochs,
+            l2: hyperparameters.l2,
+            l1: hyperparameters.l1,
+            prior: hyperparameters.prior,
+            tie_groups: hyperparameters.tie_groups,
+        }
+    }
+
+    fn load_binary(format_version: u32, encoded: &[u8]) -> Model {
+        let path = std::env::temp_dir()
+            .join(format!("ragbooster_model_v{}_{}.bin", format_version, std::process::id()));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&format_version.to_le_bytes());
+        bytes.extend_from_slice(encoded);
+        std::fs::write(&path, bytes).unwrap();
+
+        let model = Model::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        model
+    }
+
+    // The toy model without the hyperparameters which versions 1 and 2 did not record
+    fn with_version_2_defaults(mut model: Model) -> Model {
+        model.hyperparameters.half_life_days = None;
+        model.hyperparameters.as_of = None;
+        model.hyperparameters.reduction = Reduction::PerJob;
+        model
+    }
+
+    #[test]
+    fn loads_version_1_models() {
+        let model = toy_model();
+        let model_v1 = ModelV1 {
+            format_version: 1,
+            source_names: model.source_names.clone(),
+            weights: model.weights.clone(),
+            grouping: model.grouping.clone(),
+            hyperparameters: hyperparameters_v2(&model),
+            discretisation: model.discretisation.clone(),
+            fingerprint: model.fingerprint.clone(),
+            history: model.history.clone(),
+        };
+
+        let loaded = load_binary(1, &bincode::serialize(&model_v1).unwrap());
+        assert_eq!(loaded, Model { format_version: 1, ..with_version_2_defaults(model) });
+    }
+
+    #[test]
+    fn loads_version_2_models() {
+        let model = toy_model();
+        let model_v2 = ModelV2 {
+            format_version: 2,
+            source_names: model.source_names.clone(),
+            weights: model.weights.clone(),
+            grouping: model.grouping.clone(),
+            hyperparameters: hyperparameters_v2(&model),
+            discretisation: model.discretisation.clone(),
+            fingerprint: model.fingerprint.clone(),
+            history: model.history.clone(),
+            online: None,
+        };
+
+        let loaded = load_binary(2, &bincode::serialize(&model_v2).unwrap());
+        assert_eq!(loaded, Model { format_version: 2, ..with_version_2_defaults(model.clone()) });
+
+        // JSON models of version 2 lack the time decay and the reduction as well
+        let mut json = serde_json::to_value(&model).unwrap();
+        json["format_version"] = 2.into();
+        let hyperparameters = json["hyperparameters"].as_object_mut().unwrap();
+        hyperparameters.remove("half_life_days");
+        hyperparameters.remove("as_of");
+        hyperparameters.remove("reduction");
+        let loaded: Model = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded, Model { format_version: 2, ..with_version_2_defaults(model) });
+    }
+
+    #[test]
+    fn fingerprint_can_be_extended() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![1.0, 0.0]),
+            Retrieval::with_weight(vec![2], vec![0.5], 2.0),
+            Retrieval::new(vec![], vec![]),
+        ];
+
+        let mut fingerprint = DatasetFingerprint::empty(3);
+        fingerprint.extend(&retrievals[..1]);
+        fingerprint.extend(&retrievals[1..]);
+
+        assert_eq!(fingerprint, DatasetFingerprint::of(&retrievals, 3));
+    }
+
+    #[test]
+    fn fingerprint_depends_on_data() {
+        let retrievals = vec![Retrieval::new(vec![0, 1], vec![1.0, 0.0])];
+        let reweighted = vec![Retrieval::with_weight(vec![0, 1], vec![1.0, 0.0], 2.0)];
+
+        assert_eq!(DatasetFingerprint::of(&retrievals, 2), DatasetFingerprint::of(&retrievals, 2));
+        assert_ne!(DatasetFingerprint::of(&retrievals, 2).hash,
+                   DatasetFingerprint::of(&reweighted, 2).hash);
+
+        let timed = vec![Retrieval::new(vec![0, 1], vec![1.0, 0.0]).at(1_700_000_000)];
+        assert_ne!(DatasetFingerprint::of(&retrievals, 2).hash,
+                   DatasetFingerprint::of(&timed, 2).hash);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::mle::{self, gradient::Linear};
+use crate::mle::types::{Grouping, Retrieval};
+use crate::model::{DatasetFingerprint, Hyperparameters, Model};
+
+use std::io;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Everything the online learner needs besides the weights and hyperparameters of its model, to
+/// continue training from a checkpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OnlineState {
+    /// Maximum number of previously seen retrievals which are replayed with every update
+    pub replay_size: usize,
+    pub seed: u64,
+    /// Number of updates, i.e., of batches of new retrievals
+    pub num_updates: usize,
+    /// Number of gradient steps over all updates
+    pub num_steps: usize,
+    /// Number of retrievals seen so far
+    pub num_retrievals: usize,
+    /// Total weight of all retrievals seen so far, which scales down the steps of later updates
+    pub total_weight: f64,
+    /// Uniform sample of the retrievals seen so far (reservoir sampling)
+    pub replay: Vec<Retrieval>,
+}
+
+impl OnlineState {
+
+    pub fn new(replay_size: usize, seed: u64) -> Self {
+        Self {
+            replay_size,
+            seed,
+            num_updates: 0,
+            num_steps: 0,
+            num_retrievals: 0,
+            total_weight: 0.0,
+            replay: Vec::new(),
+        }
+    }
+
+    pub(crate) fn check_compatibility(&self, corpus_size: usize) -> io::Result<()> {
+        let is_valid = self.replay.len() <= self.replay_size
+            && self.replay.iter().all(|retrieval| {
+                retrieval.retrieved.len() == retrieval.utility_contributions.len()
+                    && retrieval.retrieved.iter().all(|source| *source < corpus_size)
+            });
+        if !is_valid {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "inconsistent online state"));
+        }
+        Ok(())
+    }
+}
+
+/// Updates the weights of a model as new validation questions arrive, instead of re-learning them
+/// from scratch. Every update performs `num_epochs` (see the hyperparameters) gradient steps on
+/// the new retrievals together with a bounded uniform sample of the previously seen retrievals
+/// (none if `replay_size` is zero). The gradient of a batch is normalised by its weight, so the
+/// steps are scaled by the weight of the new retrievals relative to the total weight of all
+/// retrievals seen so far, and a small batch after many large ones barely moves the weights. The
+/// learner can be checkpointed as a model and resumed from it.
+pub struct OnlineLearner {
+    model: Model,
+    optional_grouping: Option<Grouping>,
+    state: OnlineState,
+}
+
+impl OnlineLearner {
+
+    /// Starts with the prior weight for every source. Tied groups are not supported, the weights
+    /// of a group are averaged after every step as in `mle_importance`.
+    pub fn new(
+        source_names: Vec<String>,
+        optional_grouping: Option<(&Grouping, Vec<String>)>,
+        hyperparameters: Hyperparameters,
+        state: OnlineState,
+    ) -> Self {
+        assert!(!hyperparameters.tie_groups, "online learning does not support tied groups");
+
+        let corpus_size = source_names.len();
+        let v = vec![hyperparameters.prior; corpus_size];
+
+        let mut model = Model::new(source_names, v, hyperparameters,
+                                   DatasetFingerprint::empty(corpus_size));
+        if let Some((grouping, group_names)) = &optional_grouping {
+            let v_groups = mle::v_grouped(&model.weights, grouping);
+            model = model.with_grouping(group_names.clone(), grouping, v_groups);
+        }
+
+        Self::resume(model, state)
+    }
+
+    /// Continues training a model, e.g., one learned with `learn_model` or a checkpoint of an
+    /// online learner, whose online state takes precedence over `state`.
+    pub fn from_model(mut model: Model, state: OnlineState) -> io::Result<Self> {
+        model.check_compatibility()?;
+        if model.hyperparameters.tie_groups {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "online learning does not support tied groups"));
+        }
+        let state = model.online.take().unwrap_or(state);
+        Ok(Self::resume(model, state))
+    }
+
+    fn resume(model: Model, state: OnlineState) -> Self {
+        let optional_grouping = model.grouping.as_ref().map(|grouping| {
+            Grouping::new(grouping.group_names.len(), grouping.group_per_source.clone())
+        });
+        Self { model, optional_grouping, state }
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.model.weights
+    }
+
+    pub fn state(&self) -> &OnlineState {
+        &self.state
+    }
+
+    /// Updates the weights on a batch of new retrievals and returns the value of the objective on
+    /// the retrievals the update was computed on.
+    pub fn update(&mut self, retrievals: Vec<Retrieval>, n_jobs: usize) -> f64 {
+        let corpus_size = self.model.weights.len();
+        assert!(retrievals.iter().flat_map(|retrieval| retrieval.retrieved.iter())
+            .all(|source| *source < corpus_size), "retrieved source outside of the corpus");
+
+        self.model.fingerprint.extend(&retrievals);
+        let new_weight: f64 = retrievals.iter().map(|retrieval| retrieval.weight).sum();
+        self.state.total_weight += new_weight;
+        let step_scale = if self.state.total_weight > 0.0 {
+            new_weight / self.state.total_weight
+        } else {
+            0.0
+        };
+
+        let mut batch: Vec<Retrieval> = retrievals.iter()
+            .chain(self.state.replay.iter())
+            .cloned()
+            .collect();
+        if batch.is_empty() {
+            // Nothing to learn from yet, but the update still counts
+            self.model.record_training(0, 0.0);
+            self.state.num_updates += 1;
+            return 0.0;
+        }
+        mle::discretise(&mut batch);
+
+        let hyperparameters = &self.model.hyperparameters;
+        let regularisation = hyperparameters.regularisation(corpus_size);
+
+        let has_weight = batch.iter().any(|retrieval| retrieval.weight > 0.0);
+        let num_steps = if has_weight { hyperparameters.num_epochs } else { 0 };
+
+        let optional_grouping = self.optional_grouping.as_ref();
+        mle::gradient_ascent::<Linear<f64>>(
+            &batch,
+            &mut self.model.weights,
+            regularisation.as_ref(),
+            hyperparameters.k,
+            hyperparameters.learning_rate,
+            num_steps,
+            n_jobs,
+            mle::Reduction::PerJob,
+            |g| g.iter_mut().for_each(|g_i| *g_i *= step_scale),
+            |v| {
+                if let Some(grouping) = optional_grouping {
+                    mle::adjust_for_groups(v, grouping);
+                }
+            }
+        );
+
+        let objective = if has_weight {
+            mle::objective::objective(&batch, &self.model.weights, hyperparameters.k,
+                                      regularisation.as_ref(), n_jobs)
+        } else {
+            0.0
+        };
+
+        if let (Some(grouping), Some(model_grouping)) =
+            (optional_grouping, self.model.grouping.as_mut()) {
+            model_grouping.group_weights = mle::v_grouped(&self.model.weights, grouping);
+        }
+        self.model.record_training(num_steps, objective);
+
+        let num_retrievals = retrievals.len();
+        self.remember(retrievals);
+        self.state.num_retrievals += num_retrievals;
+        self.state.num_updates += 1;
+        self.state.num_steps += num_steps;
+
+        objective
+    }
+
+    // Reservoir sampling, so that the replayed retrievals are a uniform sample of all retrievals
+    // seen so far. The random numbers only depend on the seed and the number of updates, so that a
+    // resumed learner replays the same retrievals as one that was never checkpointed.
+    fn remember(&mut self, retrievals: Vec<Retrieval>) {
+        let mut rng = StdRng::seed_from_u64(self.state.seed ^ self.state.num_updates as u64);
+
+        for (offset, retrieval) in retrievals.into_iter().enumerate() {
+            if self.state.replay.len() < self.state.replay_size {
+                self.state.replay.push(retrieval);
+            } else {
+                let position = rng.gen_range(0..=self.state.num_retrievals + offset);
+                if position < self.state.replay_size {
+                    self.state.replay[position] = retrieval;
+                }
+            }
+        }
+    }
+
+    /// The model with the current weights and the state of the learner.
+    pub fn checkpoint(&self) -> Model {
+        let mut model = self.model.clone();
+        model.online = Some(self.state.clone());
+        model
+    }
+}
@@ -0,0 +1,676 @@
+use crate::mle::types::{Grouping, Retrieval};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+/// How the top-k retrieved sources that survive pruning are turned into the utility of a question.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Mean utility contribution of the top-k sources, which is the utility `mle_importance`
+    /// optimises for
+    Additive,
+    /// Utility contribution of the most common prediction among the top-k sources (ties are
+    /// broken by rank), which requires the predictions of the retrieved sources
+    MajorityVote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningResult {
+    pub achieved_utilities: Vec<f64>,
+    pub best_utility: f64,
+    pub best_threshold: f64,
+    pub best_index: usize,
+}
+
+/// Candidate pruning thresholds, either as absolute weights or as percentiles (in [0, 100]) of the
+/// learned weights, which is more meaningful when the weights are re-learned on different data.
+/// For grouped sources, the percentiles are taken over the weights of the groups (like the Python
+/// tuning), so that large groups do not dominate them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Thresholds {
+    Absolute(Vec<f64>),
+    Percentiles(Vec<f64>),
+}
+
+impl Thresholds {
+    pub fn len(&self) -> usize {
+        match self {
+            Thresholds::Absolute(thresholds) => thresholds.len(),
+            Thresholds::Percentiles(percentiles) => percentiles.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn for_weights(&self, weights: &[f64], optional_grouping: Option<&Grouping>) -> Vec<f64> {
+        match (self, optional_grouping) {
+            (Thresholds::Absolute(thresholds), _) => thresholds.clone(),
+            (Thresholds::Percentiles(percentiles), None) => {
+                percentile_thresholds(weights, percentiles)
+            },
+            (Thresholds::Percentiles(percentiles), Some(grouping)) => {
+                group_percentile_thresholds(weights, grouping, percentiles)
+            },
+        }
+    }
+}
+
+/// Configuration for k-fold cross-validation over the validation questions.
+#[derive(Debug, Clone)]
+pub struct CrossValidation {
+    pub(crate) num_folds: usize,
+    pub(crate) seed: u64,
+}
+
+impl CrossValidation {
+    pub fn new(num_folds: usize, seed: u64) -> Self {
+        assert!(num_folds >= 2, "need at least two folds");
+        Self { num_folds, seed }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidationResult {
+    /// Achieved utility per fold (outer) and threshold (inner) on the held-out questions
+    pub fold_utilities: Vec<Vec<f64>>,
+    /// Thresholds used per fold, which differ between folds for percentile thresholds
+    pub fold_thresholds: Vec<Vec<f64>>,
+    /// Achieved utility per threshold, averaged over all folds
+    pub mean_utilities: Vec<f64>,
+    pub best_index: usize,
+}
+
+/// How many questions and sources survive pruning at a threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    /// (Weighted) fraction of the questions that keep at least k sources after pruning
+    pub question_coverage: f64,
+    /// Lowest question coverage over all groups of questions, which equals the question coverage
+    /// if the questions are not grouped
+    pub min_group_coverage: f64,
+    /// (Weighted) fraction of the questions without any source left after pruning
+    pub empty_questions: f64,
+    /// Fraction of the sources with a weight that are pruned
+    pub corpus_pruned: f64,
+}
+
+/// Constraints on the coverage of a pruning threshold. The minimum question coverage has to hold
+/// for every group of questions if the questions are grouped (e.g., per relation).
+#[derive(Debug, Clone)]
+pub struct CoverageConstraints {
+    pub(crate) min_question_coverage: f64,
+    pub(crate) max_corpus_pruned: f64,
+}
+
+impl CoverageConstraints {
+    pub fn new(min_question_coverage: f64, max_corpus_pruned: f64) -> Self {
+        assert!((0.0..=1.0).contains(&min_question_coverage),
+                "minimum question coverage must be in [0, 1]");
+        assert!((0.0..=1.0).contains(&max_corpus_pruned),
+                "maximum fraction of the corpus to prune must be in [0, 1]");
+        Self { min_question_coverage, max_corpus_pruned }
+    }
+
+    pub fn is_satisfied_by(&self, coverage: &Coverage) -> bool {
+        coverage.min_group_coverage >= self.min_question_coverage
+            && coverage.corpus_pruned <= self.max_corpus_pruned
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstrainedTuningResult {
+    /// Normalised utility per threshold
+    pub achieved_utilities: Vec<f64>,
+    pub coverages: Vec<Coverage>,
+    /// Whether each threshold satisfies the constraints
+    pub feasible: Vec<bool>,
+    /// Best feasible threshold, if there is any
+    pub best_index: Option<usize>,
+    /// Indexes of the thresholds on the Pareto frontier of utility versus question coverage
+    pub pareto_frontier: Vec<usize>,
+}
+
+/// Thresholds at the given percentiles (in [0, 100]) of the weights, computed the same way as
+/// numpy.percentile.
+pub fn percentile_thresholds(weights: &[f64], percentiles: &[f64]) -> Vec<f64> {
+    let mut sorted_weights = weights.to_vec();
+    sorted_weights.sort_by(|a, b| a.total_cmp(b));
+
+    percentiles
+        .iter()
+        .map(|percentile| crate::mle::statistics::percentile(&sorted_weights, percentile / 100.0))
+        .collect()
+}
+
+/// Thresholds at the given percentiles (in [0, 100]) of the weights of the groups, i.e., of the
+/// mean weights of their members. Groups without members are ignored.
+pub fn group_percentile_thresholds(
+    weights: &[f64],
+    grouping: &Grouping,
+    percentiles: &[f64],
+) -> Vec<f64> {
+    let group_weights: Vec<f64> = crate::mle::v_grouped(weights, grouping).into_iter()
+        .filter(|group_weight| !group_weight.is_nan())
+        .collect();
+    percentile_thresholds(&group_weights, percentiles)
+}
+
+// A source is pruned if its weight is below the threshold. Sources without a weight are kept.
+#[inline(always)]
+fn is_kept(source: usize, weights: &[f64], threshold: f64) -> bool {
+    source >= weights.len() || weights[source] >= threshold
+}
+
+fn evaluate_retrieval_pruned(
+    retrieval: &Retrieval,
+    optional_predictions: Option<&[usize]>,
+    weights: &[f64],
+    k: usize,
+    threshold: f64,
+    aggregation: Aggregation,
+) -> f64 {
+
+    let top_k: Vec<usize> = retrieval.retrieved
+        .iter()
+        .enumerate()
+        .filter(|(_, source)| is_kept(**source, weights, threshold))
+        .map(|(position, _)| position)
+        .take(k)
+        .collect();
+
+    utility_of_top_k(retrieval, optional_predictions, &top_k, k, aggregation)
+}
+
+// Utility of a question, given the positions of the (at most k) sources that make it into the top-k
+pub(crate) fn utility_of_top_k(
+    retrieval: &Retrieval,
+    optional_predictions: Option<&[usize]>,
+    top_k: &[usize],
+    k: usize,
+    aggregation: Aggregation,
+) -> f64 {
+
+    if top_k.is_empty() {
+        return 0.0;
+    }
+
+    match aggregation {
+        Aggregation::Additive => {
+            let utility: f64 = top_k.iter()
+                .map(|position| retrieval.utility_contributions[*position])
+                .sum();
+            utility / k as f64
+        },
+        Aggregation::MajorityVote => {
+            let predictions = optional_predictions
+                .expect("majority vote requires the predictions of the retrieved sources");
+
+            // Counts per distinct prediction, in order of first appearance
+            let mut counts: Vec<(usize, usize, usize)> = Vec::with_capacity(top_k.len());
+            for position in top_k {
+                let prediction = predictions[*position];
+                match counts.iter_mut().find(|(p, _, _)| *p == prediction) {
+                    Some((_, count, _)) => *count += 1,
+                    None => counts.push((prediction, 1, *position)),
+                }
+            }
+
+            // max_by_key returns the last maximum, so we reverse to prefer the first one
+            let (_, _, first_position) = counts.iter().rev().max_by_key(|(_, count, _)| *count)
+                .unwrap();
+
+            retrieval.utility_contributions[*first_position]
+        },
+    }
+}
+
+/// Evaluates the (weighted) utility on the retrievals, after pruning all sources whose weight is
+/// below each of the thresholds. All thresholds are evaluated in a single parallel pass over the
+/// retrievals. If `normalize` is set, the utility is divided by the total weight of the
+/// retrievals.
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_pruned(
+    retrievals: &[Retrieval],
+    optional_predictions: Option<&[Vec<usize>]>,
+    weights: &[f64],
+    k: usize,
+    thresholds: &[f64],
+    aggregation: Aggregation,
+    normalize: bool,
+    n_jobs: usize,
+) -> Vec<f64> {
+
+    if let Some(predictions) = optional_predictions {
+        assert_eq!(predictions.len(), retrievals.len(), "need predictions for every retrieval");
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_jobs)
+        .build()
+        .unwrap();
+
+    let mut achieved_utilities = pool.install(|| {
+        retrievals
+            .par_iter()
+            .enumerate()
+            .fold(
+                || vec![0.0_f64; thresholds.len()],
+                |mut utilities, (index, retrieval)| {
+                    let predictions = optional_predictions
+                        .map(|predictions| predictions[index].as_slice());
+                    for (utility, threshold) in utilities.iter_mut().zip(thresholds.iter()) {
+                        *utility += retrieval.weight * evaluate_retrieval_pruned(
+                            retrieval, predictions, weights, k, *threshold, aggregation);
+                    }
+                    utilities
+                })
+            .reduce(
+                || vec![0.0_f64; thresholds.len()],
+                |mut sum, utilities| {
+                    sum.iter_mut().zip(utilities.iter()).for_each(|(s, u)| *s += u);
+                    sum
+                })
+    });
+
+    if normalize {
+        let total_weight: f64 = retrievals.iter().map(|retrieval| retrieval.weight).sum();
+        achieved_utilities.iter_mut().for_each(|utility| *utility /= total_weight);
+    }
+
+    achieved_utilities
+}
+
+/// Finds the threshold with the highest utility after pruning. Ties are resolved in favour of the
+/// later threshold, i.e., the more aggressive one for increasing thresholds.
+#[allow(clippy::too_many_arguments)]
+pub fn tune_pruning_threshold(
+    retrievals: &[Retrieval],
+    optional_predictions: Option<&[Vec<usize>]>,
+    weights: &[f64],
+    k: usize,
+    thresholds: &[f64],
+    aggregation: Aggregation,
+    normalize: bool,
+    n_jobs: usize,
+) -> TuningResult {
+
+    assert!(!thresholds.is_empty(), "need at least one threshold");
+
+    let achieved_utilities = evaluate_pruned(
+        retrievals,
+        optional_predictions,
+        weights,
+        k,
+        thresholds,
+        aggregation,
+        normalize,
+        n_jobs
+    );
+
+    let mut best_index = 0;
+    for (index, utility) in achieved_utilities.iter().enumerate() {
+        if *utility >= achieved_utilities[best_index] {
+            best_index = index;
+        }
+    }
+
+    TuningResult {
+        best_utility: achieved_utilities[best_index],
+        best_threshold: thresholds[best_index],
+        best_index,
+        achieved_utilities,
+    }
+}
+
+/// Computes the coverage after pruning all sources whose weight is below each of the thresholds.
+/// The questions can optionally be grouped, in which case the lowest coverage of any group is
+/// reported as well.
+pub fn evaluate_coverage(
+    retrievals: &[Retrieval],
+    weights: &[f64],
+    k: usize,
+    thresholds: &[f64],
+    optional_question_grouping: Option<&Grouping>,
+    n_jobs: usize,
+) -> Vec<Coverage> {
+
+    let num_groups = match optional_question_grouping {
+        Some(grouping) => {
+            assert_eq!(grouping.group_assignments().len(), retrievals.len(),
+                "every question must be assigned to a group");
+            grouping.num_groups
+        },
+        None => 1,
+    };
+    let group_of = |index: usize| {
+        optional_question_grouping.map(|grouping| grouping.group_assignments()[index]).unwrap_or(0)
+    };
+
+    let mut group_weights = vec![0.0_f64; num_groups];
+    for (index, retrieval) in retrievals.iter().enumerate() {
+        group_weights[group_of(index)] += retrieval.weight;
+    }
+    let total_weight: f64 = group_weights.iter().sum();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_jobs)
+        .build()
+        .unwrap();
+
+    // Covered weight per threshold and group (flattened), and empty weight per threshold
+    let (covered, empty) = pool.install(|| {
+        retrievals
+            .par_iter()
+            .enumerate()
+            .fold(
+                || (vec![0.0_f64; thresholds.len() * num_groups], vec![0.0_f64; thresholds.len()]),
+                |(mut covered, mut empty), (index, retrieval)| {
+                    let group = group_of(index);
+                    for (threshold_index, threshold) in thresholds.iter().enumerate() {
+                        let num_kept = retrieval.retrieved.iter()
+                            .filter(|source| is_kept(**source, weights, *threshold))
+                            .count();
+                        if num_kept >= k {
+                            covered[threshold_index * num_groups + group] += retrieval.weight;
+                        }
+                        if num_kept == 0 {
+                            empty[threshold_index] += retrieval.weight;
+                        }
+                    }
+                    (covered, empty)
+                })
+            .reduce(
+                || (vec![0.0_f64; thresholds.len() * num_groups], vec![0.0_f64; thresholds.len()]),
+                |(mut covered_sum, mut empty_sum), (covered, empty)| {
+                    covered_sum.iter_mut().zip(covered.iter()).for_each(|(s, c)| *s += c);
+                    empty_sum.iter_mut().zip(empty.iter()).for_each(|(s, e)| *s += e);
+                    (covered_sum, empty_sum)
+                })
+    });
+
+    thresholds
+        .iter()
+        .enumerate()
+        .map(|(threshold_index, threshold)| {
+            let covered_per_group =
+                &covered[threshold_index * num_groups..(threshold_index + 1) * num_groups];
+
+            let min_group_coverage = covered_per_group.iter().zip(group_weights.iter())
+                .filter(|(_, group_weight)| **group_weight > 0.0)
+                .map(|(covered, group_weight)| covered / group_weight)
+                .fold(f64::INFINITY, f64::min);
+
+            let num_pruned = weights.iter().filter(|weight| **weight < *threshold).count();
+
+            Coverage {
+                question_coverage: covered_per_group.iter().sum::<f64>() / total_weight,
+                min_group_coverage,
+                empty_questions: empty[threshold_index] / total_weight,
+                corpus_pruned: num_pruned as f64 / weights.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// Finds the threshold with the highest (normalised) utility among the thresholds that satisfy
+/// the coverage constraints, and reports the Pareto frontier of utility versus question coverage
+/// over all thresholds. As for `tune_pruning_threshold`, ties are resolved in favour of the later
+/// threshold.
+#[allow(clippy::too_many_arguments)]
+pub fn tune_pruning_threshold_constrained(
+    retrievals: &[Retrieval],
+    optional_predictions: Option<&[Vec<usize>]>,
+    weights: &[f64],
+    k: usize,
+    thresholds: &[f64],
+    aggregation: Aggregation,
+    constraints: &CoverageConstraints,
+    optional_question_grouping: Option<&Grouping>,
+    n_jobs: usize,
+) -> ConstrainedTuningResult {
+
+    assert!(!thresholds.is_empty(), "need at least one threshold");
+
+    let achieved_utilities = evaluate_pruned(
+        retrievals,
+        optional_predictions,
+        weights,
+        k,
+        thresholds,
+        aggregation,
+        true,
+        n_jobs
+    );
+
+    let coverages = evaluate_coverage(
+        retrievals, weights, k, thresholds, optional_question_grouping, n_jobs);
+
+    let feasible: Vec<bool> = coverages.iter()
+        .map(|coverage| constraints.is_satisfied_by(coverage))
+        .collect();
+
+    let mut best_index: Option<usize> = None;
+    for (index, utility) in achieved_utilities.iter().enumerate() {
+        if feasible[index] && best_index.is_none_or(|best| *utility >= achieved_utilities[best]) {
+            best_index = Some(index);
+        }
+    }
+
+    let pareto_frontier = pareto_frontier(&achieved_utilities, &coverages);
+
+    ConstrainedTuningResult { achieved_utilities, coverages, feasible, best_index, pareto_frontier }
+}
+
+// A threshold is on the frontier if no other threshold is at least as good in both utility and
+// question coverage and strictly better in one of them. Of several thresholds with the same
+// utility and coverage, only the last one is kept.
+fn pareto_frontier(utilities: &[f64], coverages: &[Coverage]) -> Vec<usize> {
+    (0..utilities.len())
+        .filter(|index| {
+            let (utility, coverage) = (utilities[*index], coverages[*index].question_coverage);
+            !(0..utilities.len()).any(|other| {
+                let (other_utility, other_coverage) =
+                    (utilities[other], coverages[other].question_coverage);
+                let at_least_as_good = other_utility >= utility && other_coverage >= coverage;
+                let strictly_better = other_utility > utility || other_coverage > coverage;
+                at_least_as_good && (strictly_better || other > *index)
+            })
+        })
+        .collect()
+}
+
+/// Estimates the utility of pruning on unseen questions via k-fold cross-validation: for each
+/// fold, `learn` computes the weights from the questions in all other folds, and we evaluate the
+/// (normalised) utility after pruning on the held-out questions of the fold. Percentile thresholds
+/// are taken over the weights of the groups if the sources are grouped.
+#[allow(clippy::too_many_arguments)]
+pub fn cross_validate_pruning(
+    retrievals: &[Retrieval],
+    optional_predictions: Option<&[Vec<usize>]>,
+    cross_validation: &CrossValidation,
+    learn: impl Fn(Vec<Retrieval>) -> Vec<f64>,
+    optional_grouping: Option<&Grouping>,
+    k: usize,
+    thresholds: &Thresholds,
+    aggregation: Aggregation,
+    n_jobs: usize,
+) -> CrossValidationResult {
+
+    let num_folds = cross_validation.num_folds;
+    assert!(retrievals.len() >= num_folds, "need at least one question per fold");
+    assert!(!thresholds.is_empty(), "need at least one threshold");
+
+    let mut indexes: Vec<usize> = (0..retrievals.len()).collect();
+    indexes.shuffle(&mut StdRng::seed_from_u64(cross_validation.seed));
+
+    let mut fold_utilities = Vec::with_capacity(num_folds);
+    let mut fold_thresholds = Vec::with_capacity(num_folds);
+
+    for fold in 0..num_folds {
+        let is_held_out = |position: usize| position % num_folds == fold;
+
+        let training_retrievals: Vec<Retrieval> = indexes.iter().enumerate()
+            .filter(|(position, _)| !is_held_out(*position))
+            .map(|(_, index)| retrievals[*index].clone())
+            .collect();
+
+        let held_out_indexes: Vec<usize> = indexes.iter().enumerate()
+            .filter(|(position, _)| is_held_out(*position))
+            .map(|(_, index)| *index)
+            .collect();
+
+        let held_out_retrievals: Vec<Retrieval> = held_out_indexes.iter()
+            .map(|index| retrievals[*index].clone())
+            .collect();
+
+        let held_out_predictions: Option<Vec<Vec<usize>>> = optional_predictions
+            .map(|predictions| {
+                held_out_indexes.iter().map(|index| predictions[*index].clone()).collect()
+            });
+
+        let weights = learn(training_retrievals);
+        let thresholds_of_fold = thresholds.for_weights(&weights, optional_grouping);
+
+        let utilities = evaluate_pruned(
+            &held_out_retrievals,
+            held_out_predictions.as_deref(),
+            &weights,
+            k,
+            &thresholds_of_fold,
+            aggregation,
+            true,
+            n_jobs
+        );
+
+        fold_utilities.push(utilities);
+        fold_thresholds.push(thresholds_of_fold);
+    }
+
+    let mean_utilities: Vec<f64> = (0..thresholds.len())
+        .map(|index| {
+            fold_utilities.iter().map(|utilities| utilities[index]).sum::<f64>() / num_folds as f64
+        })
+        .collect();
+
+    let mut best_index = 0;
+    for (index, utility) in mean_utilities.iter().enumerate() {
+        if *utility >= mean_utilities[best_index] {
+            best_index = index;
+        }
+    }
+
+    CrossValidationResult { fold_utilities, fold_thresholds, mean_utilities, best_index }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn majority_vote_prefers_first_prediction_on_ties() {
+        let retrieval = Retrieval::new(vec![0, 1, 2, 3], vec![0.0, 1.0, 1.0, 0.0]);
+        let predictions = vec![7, 3, 3, 7];
+        let weights = vec![0.5, 0.5, 0.5, 0.5];
+
+        let utility = evaluate_retrieval_pruned(
+            &retrieval, Some(&predictions), &weights, 4, 0.0, Aggregation::MajorityVote);
+        assert_eq!(utility, 0.0);
+
+        let utility = evaluate_retrieval_pruned(
+            &retrieval, Some(&predictions), &weights, 3, 0.0, Aggregation::MajorityVote);
+        assert_eq!(utility, 1.0);
+    }
+
+    #[test]
+    fn pruning_skips_low_weight_sources() {
+        let retrieval = Retrieval::new(vec![0, 1, 2, 3], vec![0.0, 1.0, 0.0, 1.0]);
+        let weights = vec![0.1, 0.9, 0.2];
+
+        // Source 3 has no weight and is always kept
+        let utility = evaluate_retrieval_pruned(
+            &retrieval, None, &weights, 2, 0.5, Aggregation::Additive);
+        assert_eq!(utility, 1.0);
+
+        let utility = evaluate_retrieval_pruned(
+            &retrieval, None, &weights, 2, 0.0, Aggregation::Additive);
+        assert_eq!(utility, 0.5);
+
+        let utility = evaluate_retrieval_pruned(
+            &Retrieval::new(vec![0, 2], vec![1.0, 1.0]), None, &weights, 2, 0.5,
+            Aggregation::Additive);
+        assert_eq!(utility, 0.0);
+    }
+
+    #[test]
+    fn percentiles_like_numpy() {
+        let weights = vec![0.4, 0.1, 0.3, 0.2];
+        let thresholds = percentile_thresholds(&weights, &[0.0, 50.0, 100.0]);
+        assert_eq!(thresholds, vec![0.1, 0.25, 0.4]);
+    }
+
+    #[test]
+    fn tuning_picks_best_threshold() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1, 2], vec![0.0, 1.0, 1.0]),
+            Retrieval::with_weight(vec![3, 0, 1], vec![1.0, 0.0, 1.0], 2.0),
+        ];
+        let weights = vec![0.1, 0.6, 0.8, 0.3];
+        let thresholds = vec![0.0, 0.2, 0.5, 0.7, 0.9];
+
+        let result = tune_pruning_threshold(
+            &retrievals, None, &weights, 1, &thresholds, Aggregation::Additive, true, 2);
+
+        assert_eq!(result.achieved_utilities, vec![2.0 / 3.0, 1.0, 1.0, 1.0 / 3.0, 0.0]);
+        assert_eq!(result.best_index, 2);
+        assert_eq!(result.best_threshold, 0.5);
+        assert_eq!(result.best_utility, 1.0);
+    }
+
+    #[test]
+    fn constrained_tuning_respects_coverage() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![0.0, 1.0]),
+            Retrieval::new(vec![0, 2], vec![0.0, 1.0]),
+            Retrieval::new(vec![0, 3], vec![1.0, 0.0]),
+        ];
+        let weights = vec![0.1, 0.9, 0.6, 0.3];
+        let thresholds = vec![0.0, 0.2, 0.5, 0.7];
+        let question_grouping = Grouping::new(2, vec![0, 0, 1]);
+
+        let coverages = evaluate_coverage(
+            &retrievals, &weights, 1, &thresholds, Some(&question_grouping), 2);
+
+        assert_eq!(coverages[2].question_coverage, 2.0 / 3.0);
+        assert_eq!(coverages[2].min_group_coverage, 0.0);
+        assert_eq!(coverages[2].empty_questions, 1.0 / 3.0);
+        assert_eq!(coverages[2].corpus_pruned, 0.5);
+
+        let unconstrained = tune_pruning_threshold_constrained(&retrievals, None, &weights, 1,
+            &thresholds, Aggregation::Additive, &CoverageConstraints::new(0.0, 1.0), None, 2);
+        assert_eq!(unconstrained.achieved_utilities,
+                   vec![1.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 1.0 / 3.0]);
+        assert_eq!(unconstrained.best_index, Some(2));
+        // Thresholds 0.0 and 0.2 keep every question, 0.2 has the higher utility
+        assert_eq!(unconstrained.pareto_frontier, vec![1]);
+
+        // Every group of questions needs to keep its single source
+        let constrained = tune_pruning_threshold_constrained(&retrievals, None, &weights, 1,
+            &thresholds, Aggregation::Additive, &CoverageConstraints::new(1.0, 1.0),
+            Some(&question_grouping), 2);
+        assert_eq!(constrained.feasible, vec![true, true, false, false]);
+        assert_eq!(constrained.best_index, Some(1));
+
+        // Only the threshold 0.0 prunes nothing
+        let constrained = tune_pruning_threshold_constrained(&retrievals, None, &weights, 1,
+            &thresholds, Aggregation::Additive, &CoverageConstraints::new(0.0, 0.0), None, 2);
+        assert_eq!(constrained.best_index, Some(0));
+
+        let infeasible = tune_pruning_threshold_constrained(&retrievals, None, &weights, 1,
+            &thresholds[1..], Aggregation::Additive, &CoverageConstraints::new(0.0, 0.1), None, 2);
+        assert_eq!(infeasible.best_index, None);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::mle::types::Retrieval;
+use crate::pruning::{utility_of_top_k, Aggregation};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+/// How the retriever scores and the learned importance weights are combined into a new ranking.
+/// Sources without a learned weight are treated as if they had weight 1.0, as they are never
+/// pruned either.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reranking {
+    /// Ranks by score * weight (assumes non-negative scores)
+    Product,
+    /// Ranks by alpha * score + (1 - alpha) * weight, where the scores are min-max normalised
+    /// per retrieval, so that they are on the same scale as the weights
+    Interpolation(f64),
+    /// Samples a ranking without replacement with probabilities proportional to the weights,
+    /// ignoring the scores
+    Sampling(u64),
+}
+
+#[inline(always)]
+fn weight_of(source: usize, weights: &[f64]) -> f64 {
+    if source < weights.len() { weights[source] } else { 1.0 }
+}
+
+/// Re-ranks a single ranked retrieval and returns the positions of the retrieved sources in their
+/// new order. Ties keep the original order. `salt` varies the random draws between retrievals for
+/// sampling.
+pub fn rerank(
+    retrieved: &[usize],
+    scores: &[f64],
+    weights: &[f64],
+    reranking: &Reranking,
+    salt: u64,
+) -> Vec<usize> {
+
+    assert_eq!(retrieved.len(), scores.len(), "need one score per retrieved source");
+
+    let combined: Vec<f64> = match reranking {
+        Reranking::Product => {
+            retrieved.iter().zip(scores.iter())
+                .map(|(source, score)| score * weight_of(*source, weights))
+                .collect()
+        },
+        Reranking::Interpolation(alpha) => {
+            let min_score = scores.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max_score = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let range = max_score - min_score;
+
+            retrieved.iter().zip(scores.iter())
+                .map(|(source, score)| {
+                    let normalised_score =
+                        if range > 0.0 { (score - min_score) / range } else { 1.0 };
+                    alpha * normalised_score + (1.0 - alpha) * weight_of(*source, weights)
+                })
+                .collect()
+        },
+        Reranking::Sampling(seed) => {
+            // Weighted sampling without replacement via the keys u^(1/w) (Efraimidis & Spirakis)
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(salt));
+            retrieved.iter()
+                .map(|source| {
+                    let u: f64 = rng.gen();
+                    let weight = weight_of(*source, weights);
+                    if weight > 0.0 { u.powf(1.0 / weight) } else { 0.0 }
+                })
+                .collect()
+        },
+    };
+
+    let mut positions: Vec<usize> = (0..retrieved.len()).collect();
+    // sort_by is stable, so ties keep their original rank
+    positions.sort_by(|a, b| combined[*b].total_cmp(&combined[*a]));
+    positions
+}
+
+/// Evaluates the (weighted, normalised) top-k utility of the re-ranked retrievals.
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_reranked(
+    retrievals: &[Retrieval],
+    scores: &[Vec<f64>],
+    optional_predictions: Option<&[Vec<usize>]>,
+    weights: &[f64],
+    k: usize,
+    reranking: &Reranking,
+    aggregation: Aggregation,
+    n_jobs: usize,
+) -> f64 {
+
+    assert_eq!(scores.len(), retrievals.len(), "need scores for every retrieval");
+    if let Some(predictions) = optional_predictions {
+        assert_eq!(predictions.len(), retrievals.len(), "need predictions for every retrieval");
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_jobs)
+        .build()
+        .unwrap();
+
+    let total_utility: f64 = pool.install(|| {
+        retrievals
+            .par_iter()
+            .enumerate()
+            .map(|(index, retrieval)| {
+                let positions = rerank(
+                    &retrieval.retrieved, &scores[index], weights, reranking, index as u64);
+                let top_k: Vec<usize> = positions.into_iter().take(k).collect();
+                let predictions = optional_predictions
+                    .map(|predictions| predictions[index].as_slice());
+
+                retrieval.weight * utility_of_top_k(retrieval, predictions, &top_k, k, aggregation)
+            })
+            .sum()
+    });
+
+    let total_weight: f64 = retrievals.iter().map(|retrieval| retrieval.weight).sum();
+
+    total_utility / total_weight
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn product_and_interpolation() {
+        let retrieved = vec![0, 1, 2, 3];
+        let scores = vec![4.0, 3.0, 2.0, 1.0];
+        let weights = vec![0.1, 1.0, 0.9];
+
+        // Source 3 has no weight and counts as 1.0
+        let positions = rerank(&retrieved, &scores, &weights, &Reranking::Product, 0);
+        assert_eq!(positions, vec![1, 2, 3, 0]);
+
+        let positions = rerank(&retrieved, &scores, &weights, &Reranking::Interpolation(1.0), 0);
+        assert_eq!(positions, vec![0, 1, 2, 3]);
+
+        let positions = rerank(&retrieved, &scores, &weights, &Reranking::Interpolation(0.0), 0);
+        assert_eq!(positions, vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn sampling_never_prefers_zero_weights() {
+        let retrieved = vec![0, 1, 2];
+        let scores = vec![3.0, 2.0, 1.0];
+        let weights = vec![0.0, 0.5, 0.0];
+
+        for salt in 0..20 {
+            let positions = rerank(&retrieved, &scores, &weights, &Reranking::Sampling(42), salt);
+            assert_eq!(positions, vec![1, 0, 2]);
+        }
+    }
+
+    #[test]
+    fn reranking_improves_utility() {
+        let retrievals = vec![
+            Retrieval::new(vec![0, 1], vec![0.0, 1.0]),
+            Retrieval::new(vec![0, 2], vec![0.0, 1.0]),
+        ];
+        let scores = vec![vec![2.0, 1.0], vec![2.0, 1.5]];
+        let weights = vec![0.1, 0.9, 0.9];
+
+        let utility = evaluate_reranked(&retrievals, &scores, None, &weights, 1,
+            &Reranking::Interpolation(1.0), Aggregation::Additive, 1);
+        assert_eq!(utility, 0.0);
+
+        let utility = evaluate_reranked(&retrievals, &scores, None, &weights, 1,
+            &Reranking::Product, Aggregation::Additive, 2);
+        assert_eq!(utility, 1.0);
+    }
+}
@@ -0,0 +1,437 @@
+//! A compact columnar binary format for retrievals, which is memory-mapped instead of parsed.
+//!
+//! All numbers are little endian, and every column starts at a multiple of eight bytes:
+//!
+//!   header: "RAGR", version (u32), flags (u32), decimals (i32), number of retrievals R (u64),
+//!           number of retrieved sources E (u64), number of sources S (u64),
+//!           number of groups G (u64)
+//!   offsets: R + 1 x u64, retrieval r retrieved the sources at offsets[r]..offsets[r+1]
+//!   sources: E x u32
+//!   utility contributions: E x i32, quantised as round(u * 10^decimals)
+//!   weights: R x f64
+//!   timestamps: R x i64 (only with timestamps)
+//!   group per source: S x u32 (only with a grouping)
+//!   source names: S + 1 x u64 offsets, followed by the UTF-8 bytes of the names
+//!   group names: G + 1 x u64 offsets, followed by the UTF-8 bytes of the names (only with a
+//!                grouping)
+
+use crate::io::{read_group_json, read_qa_json};
+use crate::mle::streaming::RetrievalStream;
+use crate::mle::types::{Grouping, Retrieval};
+use crate::mle::UTILITY_DECIMALS;
+
+use std::fs::File;
+use std::io::{self, prelude::*, BufWriter};
+use std::ops::Range;
+
+use memmap2::Mmap;
+
+/// Version of the on-disk format, which is increased whenever the layout changes.
+pub const FORMAT_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 4] = b"RAGR";
+const HEADER_LEN: usize = 48;
+const HAS_GROUPING: u32 = 1;
+// Since version 2
+const HAS_TIMESTAMPS: u32 = 2;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn padding(len: usize) -> usize {
+    (8 - len % 8) % 8
+}
+
+/// Writes the retrievals with the names of their sources and an optional grouping (with the names
+/// of the groups). The utility contributions are rounded to `UTILITY_DECIMALS` decimals, like
+/// during training. Either all or none of the retrievals need a timestamp.
+pub fn write_retrieval_log(
+    path: &str,
+    retrievals: &[Retrieval],
+    source_names: &[String],
+    optional_grouping: Option<(&Grouping, &[String])>,
+) -> io::Result<()> {
+
+    let num_sources = source_names.len();
+    let has_timestamps = retrievals.iter().any(|retrieval| retrieval.timestamp.is_some());
+    if has_timestamps && retrievals.iter().any(|retrieval| retrieval.timestamp.is_none()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "either all or none of the retrievals need a timestamp"));
+    }
+    let scale = 10_f64.powi(UTILITY_DECIMALS);
+
+    let mut offsets: Vec<u64> = Vec::with_capacity(retrievals.len() + 1);
+    let mut sources: Vec<u32> = Vec::new();
+    let mut utility_contributions: Vec<i32> = Vec::new();
+
+    offsets.push(0);
+    for retrieval in retrievals {
+        for (source, utility_contribution) in
+            retrieval.retrieved.iter().zip(retrieval.utility_contributions.iter()) {
+            if *source >= num_sources {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("source {source} has no name")));
+            }
+            let quantised = (utility_contribution * scale).round();
+            if !(i32::MIN as f64..=i32::MAX as f64).contains(&quantised) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("utility contribution {utility_contribution} cannot be quantised")));
+            }
+            sources.push(*source as u32);
+            utility_contributions.push(quantised as i32);
+        }
+        offsets.push(sources.len() as u64);
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+
+    let mut write = |bytes: &[u8]| -> io::Result<()> {
+        writer.write_all(bytes)?;
+        written += bytes.len();
+        writer.write_all(&[0_u8; 8][..padding(written)])?;
+        written += padding(written);
+        Ok(())
+    };
+
+    let (mut flags, num_groups) = match optional_grouping {
+        Some((grouping, _)) => (HAS_GROUPING, grouping.num_groups),
+        None => (0, 0),
+    };
+    if has_timestamps {
+        flags |= HAS_TIMESTAMPS;
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&flags.to_le_bytes());
+    header.extend_from_slice(&UTILITY_DECIMALS.to_le_bytes());
+    for count in [retrievals.len(), sources.len(), num_sources, num_groups] {
+        header.extend_from_slice(&(count as u64).to_le_bytes());
+    }
+    write(&header)?;
+
+    write(&offsets.iter().flat_map(|offset| offset.to_le_bytes()).collect::<Vec<u8>>())?;
+    write(&sources.iter().flat_map(|source| source.to_le_bytes()).collect::<Vec<u8>>())?;
+    write(&utility_contributions.iter().flat_map(|u| u.to_le_bytes()).collect::<Vec<u8>>())?;
+    write(&retrievals.iter().flat_map(|r| r.weight.to_le_bytes()).collect::<Vec<u8>>())?;
+    if has_timestamps {
+        write(&retrievals.iter()
+            .flat_map(|r| r.timestamp.unwrap().to_le_bytes())
+            .collect::<Vec<u8>>())?;
+    }
+
+    if let Some((grouping, _)) = optional_grouping {
+        assert_eq!(grouping.group_assignments().len(), num_sources,
+            "every source must be assigned to a group");
+        write(&grouping.group_assignments().iter()
+            .flat_map(|group| (*group as u32).to_le_bytes())
+            .collect::<Vec<u8>>())?;
+    }
+
+    let mut write_names = |names: &[String]| -> io::Result<()> {
+        let mut name_offsets: Vec<u8> = Vec::with_capacity((names.len() + 1) * 8);
+        let mut offset: u64 = 0;
+        name_offsets.extend_from_slice(&offset.to_le_bytes());
+        for name in names {
+            offset += name.len() as u64;
+            name_offsets.extend_from_slice(&offset.to_le_bytes());
+        }
+        write(&name_offsets)?;
+        write(names.concat().as_bytes())
+    };
+
+    write_names(source_names)?;
+    if let Some((grouping, group_names)) = optional_grouping {
+        assert_eq!(group_names.len(), grouping.num_groups, "need one name per group");
+        write_names(group_names)?;
+    }
+
+    writer.flush()
+}
+
+/// Converts a QA retrieval JSONL file (and optionally a group JSONL file) into a retrieval log.
+pub fn convert_qa_json(
+    questions_path: &str,
+    optional_groups_path: Option<&str>,
+    path: &str,
+) -> io::Result<()> {
+
+    let (retrievals, website_indexer) = read_qa_json(questions_path)?;
+
+    let optional_grouping = match optional_groups_path {
+        Some(groups_path) => Some(read_group_json(groups_path, &website_indexer.create_index())?),
+        None => None,
+    };
+
+    let group_names = optional_grouping.as_ref()
+        .map(|(_, group_indexer)| group_indexer.strings());
+
+    write_retrieval_log(
+        path,
+        &retrievals,
+        &website_indexer.strings(),
+        optional_grouping.as_ref()
+            .map(|(grouping, _)| grouping)
+            .zip(group_names.as_deref())
+    )
+}
+
+/// A memory-mapped retrieval log. The file is validated when it is opened, the retrievals are
+/// decoded whenever they are accessed.
+pub struct RetrievalLog {
+    mmap: Mmap,
+    num_retrievals: usize,
+    num_sources: usize,
+    num_groups: Option<usize>,
+    scale: f64,
+    offsets: Range<usize>,
+    sources: Range<usize>,
+    utility_contributions: Range<usize>,
+    weights: Range<usize>,
+    timestamps: Option<Range<usize>>,
+    group_per_source: Option<Range<usize>>,
+    source_names: (Range<usize>, Range<usize>),
+    group_names: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl RetrievalLog {
+
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the file must not be modified while it is mapped, as for every memory-mapped
+        // file. All sections are validated below, so a corrupt file cannot cause reads out of
+        // bounds.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < HEADER_LEN || &mmap[..4] != MAGIC {
+            return Err(invalid_data(format!("{path} is not a retrieval log")));
+        }
+
+        let u32_at = |position: usize| {
+            u32::from_le_bytes(mmap[position..position + 4].try_into().unwrap())
+        };
+        let u64_at = |position: usize| {
+            u64::from_le_bytes(mmap[position..position + 8].try_into().unwrap()) as usize
+        };
+
+        let format_version = u32_at(4);
+        if format_version == 0 || format_version > FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported format version {}, this version supports up to {}",
+                format_version, FORMAT_VERSION)));
+        }
+
+        let flags = u32_at(8);
+        let decimals = u32_at(12) as i32;
+        let num_retrievals = u64_at(16);
+        let num_entries = u64_at(24);
+        let num_sources = u64_at(32);
+        let num_groups = if flags & HAS_GROUPING != 0 { Some(u64_at(40)) } else { None };
+
+        let mut position = HEADER_LEN;
+        let mut take = |num_values: usize, value_len: usize| -> io::Result<Range<usize>> {
+            let len = num_values.checked_mul(value_len)
+                .ok_or_else(|| invalid_data("section too large".to_owned()))?;
+            let start = position;
+            position = start.checked_add(len)
+                .filter(|end| *end <= mmap.len())
+                .ok_or_else(|| invalid_data("retrieval log is truncated".to_owned()))?;
+            position += padding(position);
+            Ok(start..start + len)
+        };
+
+        // The sections of offsets have one more entry than there are retrievals, sources or groups
+        let one_more = |count: usize| {
+            count.checked_add(1).ok_or_else(|| invalid_data("corrupt header".to_owned()))
+        };
+
+        let offsets = take(one_more(num_retrievals)?, 8)?;
+        let sources = take(num_entries, 4)?;
+        let utility_contributions = take(num_entries, 4)?;
+        let weights = take(num_retrievals, 8)?;
+        let timestamps = if flags & HAS_TIMESTAMPS != 0 {
+            Some(take(num_retrievals, 8)?)
+        } else {
+            None
+        };
+        let group_per_source = match num_groups {
+            Some(_) => Some(take(num_sources, 4)?),
+            None => None,
+        };
+
+        let source_name_offsets = take(one_more(num_sources)?, 8)?;
+        let source_name_bytes = take(u64_at(source_name_offsets.end - 8), 1)?;
+        let group_names = match num_groups {
+            Some(num_groups) => {
+                let group_name_offsets = take(one_more(num_groups)?, 8)?;
+                let group_name_bytes = take(u64_at(group_name_offsets.end - 8), 1)?;
+                Some((group_name_offsets, group_name_bytes))
+            },
+            None => None,
+        };
+
+        let log = Self {
+            mmap,
+            num_retrievals,
+            num_sources,
+            num_groups,
+            scale: 10_f64.powi(decimals),
+            offsets,
+            sources,
+            utility_contributions,
+            weights,
+            timestamps,
+            group_per_source,
+            source_names: (source_name_offsets, source_name_bytes),
+            group_names,
+        };
+        log.validate(num_entries)?;
+
+        Ok(log)
+    }
+
+    fn validate(&self, num_entries: usize) -> io::Result<()> {
+        let offsets = self.u64s(&self.offsets);
+        if offsets[0] != 0 || offsets[self.num_retrievals] != num_entries
+            || offsets.windows(2).any(|window| window[0] > window[1]) {
+            return Err(invalid_data("inconsistent retrieval offsets".to_owned()));
+        }
+        if self.u32s(&self.sources).iter().any(|source| *source as usize >= self.num_sources) {
+            return Err(invalid_data("retrieved source outside of the corpus".to_owned()));
+        }
+        if self.f64s(&self.weights).iter().any(|weight| !(*weight >= 0.0 && weight.is_finite())) {
+            return Err(invalid_data("weights must be non-negative and finite".to_owned()));
+        }
+        if let (Some(group_per_source), Some(num_groups)) =
+            (&self.group_per_source, self.num_groups) {
+            if self.u32s(group_per_source).iter().any(|group| *group as usize >= num_groups) {
+                return Err(invalid_data("source assigned to an unknown group".to_owned()));
+            }
+        }
+        self.names(&self.source_names)?;
+        if let Some(group_names) = &self.group_names {
+            self.names(group_names)?;
+        }
+        Ok(())
+    }
+
+    fn u64s(&self, range: &Range<usize>) -> Vec<usize> {
+        self.mmap[range.clone()].chunks_exact(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            .collect()
+    }
+
+    fn u32s(&self, range: &Range<usize>) -> Vec<u32> {
+        self.mmap[range.clone()].chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    fn f64s(&self, range: &Range<usize>) -> Vec<f64> {
+        self.mmap[range.clone()].chunks_exact(8)
+            .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    fn names(&self, (offsets, bytes): &(Range<usize>, Range<usize>)) -> io::Result<Vec<String>> {
+        let bytes = &self.mmap[bytes.clone()];
+        self.u64s(offsets)
+            .windows(2)
+            .map(|window| {
+                bytes.get(window[0]..window[1])
+                    .and_then(|name| std::str::from_utf8(name).ok())
+                    .map(|name| name.to_owned())
+                    .ok_or_else(|| invalid_data("invalid name".to_owned()))
+            })
+            .collect()
+    }
+
+    pub fn num_retrievals(&self) -> usize {
+        self.num_retrievals
+    }
+
+    pub fn corpus_size(&self) -> usize {
+        self.num_sources
+    }
+
+    /// Decodes the retrievals with indexes in `range`.
+    pub fn retrievals_in(&self, range: Range<usize>) -> Vec<Retrieval> {
+        assert!(range.start <= range.end && range.end <= self.num_retrievals);
+
+        let offsets = self.u64s(&(self.offsets.start + range.start * 8
+            ..self.offsets.start + (range.end + 1) * 8));
+        let entries = offsets[0]..offsets[offsets.len() - 1];
+
+        let sources = self.u32s(&(self.sources.start + entries.start * 4
+            ..self.sources.start + entries.end * 4));
+        let utility_contributions = &self.mmap[self.utility_contributions.start
+            + entries.start * 4..self.utility_contributions.start + entries.end * 4];
+        let weights = self.f64s(&(self.weights.start + range.start * 8
+            ..self.weights.start + range.end * 8));
+        let timestamps: Vec<Option<i64>> = match &self.timestamps {
+            Some(timestamps) => {
+                self.mmap[timestamps.start + range.start * 8..timestamps.start + range.end * 8]
+                    .chunks_exact(8)
+                    .map(|bytes| Some(i64::from_le_bytes(bytes.try_into().unwrap())))
+                    .collect()
+            },
+            None => vec![None; range.len()],
+        };
+
+        offsets.windows(2).zip(weights).zip(timestamps)
+            .map(|((window, weight), timestamp)| {
+                let retrieval_entries = (window[0] - entries.start)..(window[1] - entries.start);
+                let retrieved = sources[retrieval_entries.clone()].iter()
+                    .map(|source| *source as usize)
+                    .collect();
+                let utility_contributions = utility_contributions
+                    [retrieval_entries.start * 4..retrieval_entries.end * 4]
+                    .chunks_exact(4)
+                    .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()) as f64 / self.scale)
+                    .collect();
+                let retrieval = Retrieval::with_weight(retrieved, utility_contributions, weight);
+                match timestamp {
+                    Some(timestamp) => retrieval.at(timestamp),
+                    None => retrieval,
+                }
+            })
+            .collect()
+    }
+
+    pub fn retrievals(&self) -> Vec<Retrieval> {
+        self.retrievals_in(0..self.num_retrievals)
+    }
+
+    pub fn source_names(&self) -> Vec<String> {
+        self.names(&self.source_names).unwrap()
+    }
+
+    /// The grouping of the sources and the names of the groups, if the log has a grouping.
+    pub fn grouping(&self) -> Option<(Grouping, Vec<String>)> {
+        let group_per_source = self.group_per_source.as_ref()?;
+        let num_groups = self.num_groups?;
+        let group_names = self.names(self.group_names.as_ref()?).unwrap();
+
+        let group_assignments = self.u32s(group_per_source).into_iter()
+            .map(|group| group as usize)
+            .collect();
+
+        Some((Grouping::new(num_groups, group_assignments), group_names))
+    }
+}
+
+impl RetrievalStream for RetrievalLog {
+    fn for_each_chunk(
+        &self,
+        chunk_size: usize,
+        consume: &mut dyn FnMut(Vec<Retrieval>),
+    ) -> io::Result<()> {
+        for start in (0..self.num_retrievals).step_by(chunk_size) {
+            consume(self.retrievals_in(start..(start + chunk_size).min(self.num_retrievals)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use crate::mle::types::{Grouping, Retrieval};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Planted quality class of a synthetic source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Good,
+    Bad,
+    Noisy,
+}
+
+/// Configuration of a synthetic validation set with planted source quality. Every source belongs
+/// to a quality class, which determines the probability that it yields a correct answer. Sources
+/// are retrieved according to a Zipfian popularity, which is independent of their quality.
+#[derive(Debug, Clone)]
+pub struct Synthetic {
+    pub(crate) num_questions: usize,
+    pub(crate) corpus_size: usize,
+    pub(crate) retrieval_depth: usize,
+    pub(crate) seed: u64,
+    pub(crate) fraction_bad: f64,
+    pub(crate) fraction_noisy: f64,
+    pub(crate) good_quality: f64,
+    pub(crate) bad_quality: f64,
+    pub(crate) noisy_quality: f64,
+    pub(crate) zipf_exponent: f64,
+    pub(crate) label_noise: f64,
+    pub(crate) num_groups: Option<usize>,
+}
+
+impl Synthetic {
+    /// Defaults to 20% bad and 10% noisy sources with qualities 0.8 (good), 0.1 (bad) and 0.5
+    /// (noisy), a Zipf exponent of 1.0, no label noise and no groups.
+    pub fn new(
+        num_questions: usize,
+        corpus_size: usize,
+        retrieval_depth: usize,
+        seed: u64,
+    ) -> Self {
+        assert!(num_questions > 0, "need at least one question");
+        assert!(retrieval_depth > 0 && retrieval_depth <= corpus_size,
+                "retrieval depth must be in [1, corpus size]");
+        Self {
+            num_questions,
+            corpus_size,
+            retrieval_depth,
+            seed,
+            fraction_bad: 0.2,
+            fraction_noisy: 0.1,
+            good_quality: 0.8,
+            bad_quality: 0.1,
+            noisy_quality: 0.5,
+            zipf_exponent: 1.0,
+            label_noise: 0.0,
+            num_groups: None,
+        }
+    }
+
+    pub fn with_quality_mix(mut self, fraction_bad: f64, fraction_noisy: f64) -> Self {
+        assert!(fraction_bad >= 0.0 && fraction_noisy >= 0.0
+                    && fraction_bad + fraction_noisy <= 1.0,
+                "fractions of bad and noisy sources must be non-negative and sum to at most 1");
+        self.fraction_bad = fraction_bad;
+        self.fraction_noisy = fraction_noisy;
+        self
+    }
+
+    /// Probabilities that good, bad and noisy sources yield a correct answer
+    pub fn with_qualities(
+        mut self,
+        good_quality: f64,
+        bad_quality: f64,
+        noisy_quality: f64,
+    ) -> Self {
+        for quality in [good_quality, bad_quality, noisy_quality] {
+            assert!((0.0..=1.0).contains(&quality), "qualities must be in [0, 1]");
+        }
+        self.good_quality = good_quality;
+        self.bad_quality = bad_quality;
+        self.noisy_quality = noisy_quality;
+        self
+    }
+
+    /// The i-th most popular source is retrieved with a probability proportional to 1 / i^s, an
+    /// exponent of 0.0 retrieves all sources uniformly.
+    pub fn with_zipf_exponent(mut self, zipf_exponent: f64) -> Self {
+        assert!(zipf_exponent >= 0.0, "Zipf exponent must be non-negative");
+        self.zipf_exponent = zipf_exponent;
+        self
+    }
+
+    /// Probability that the correctness of a retrieved answer is flipped
+    pub fn with_label_noise(mut self, label_noise: f64) -> Self {
+        assert!((0.0..=1.0).contains(&label_noise), "label noise must be in [0, 1]");
+        self.label_noise = label_noise;
+        self
+    }
+
+    /// Organises the sources in groups (e.g., domains), whose members share their quality class
+    pub fn with_groups(mut self, num_groups: usize) -> Self {
+        assert!(num_groups > 0 && num_groups <= self.corpus_size,
+                "number of groups must be in [1, corpus size]");
+        self.num_groups = Some(num_groups);
+        self
+    }
+
+    pub fn generate(&self) -> SyntheticDataset {
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        // Sources are assigned to groups in contiguous blocks, and the quality classes are assigned
+        // per group, so that all members of a group share their class
+        let num_units = self.num_groups.unwrap_or(self.corpus_size);
+        let unit_of = |source: usize| source * num_units / self.corpus_size;
+
+        let num_bad = (self.fraction_bad * num_units as f64).round() as usize;
+        let num_noisy = ((self.fraction_noisy * num_units as f64).round() as usize)
+            .min(num_units - num_bad);
+
+        let mut unit_kinds: Vec<SourceKind> = (0..num_units)
+            .map(|unit| {
+                if unit < num_bad {
+                    SourceKind::Bad
+                } else if unit < num_bad + num_noisy {
+                    SourceKind::Noisy
+                } else {
+                    SourceKind::Good
+                }
+            })
+            .collect();
+        unit_kinds.shuffle(&mut rng);
+
+        let kinds: Vec<SourceKind> = (0..self.corpus_size)
+            .map(|source| unit_kinds[unit_of(source)])
+            .collect();
+
+        let quality: Vec<f64> = kinds.iter()
+            .map(|kind| match kind {
+                SourceKind::Good => self.good_quality,
+                SourceKind::Bad => self.bad_quality,
+                SourceKind::Noisy => self.noisy_quality,
+            })
+            .collect();
+
+        // Popularity ranks are a random permutation, so that popularity does not depend on quality
+        let mut popularity_ranks: Vec<usize> = (0..self.corpus_size).collect();
+        popularity_ranks.shuffle(&mut rng);
+        let popularity: Vec<f64> = popularity_ranks.iter()
+            .map(|rank| 1.0 / ((rank + 1) as f64).powf(self.zipf_exponent))
+            .collect();
+
+        let retrievals: Vec<Retrieval> = (0..self.num_questions)
+            .map(|_| {
+                // Draws the sources one after another without replacement, as an exponential race:
+                // every source arrives after an exponentially distributed time with its popularity
+                // as rate, and the first arrivals are retrieved in the order of their arrival
+                let mut arrivals: Vec<(f64, usize)> = popularity.iter()
+                    .enumerate()
+                    .map(|(source, rate)| (-(-rng.gen::<f64>()).ln_1p() / rate, source))
+                    .collect();
+                arrivals.select_nth_unstable_by(self.retrieval_depth - 1,
+                                                |a, b| a.0.total_cmp(&b.0));
+                arrivals.truncate(self.retrieval_depth);
+                arrivals.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let retrieved: Vec<usize> = arrivals.iter().map(|(_, source)| *source).collect();
+
+                let utility_contributions: Vec<f64> = retrieved.iter()
+                    .map(|source| {
+                        let is_correct = rng.gen_bool(quality[*source]);
+                        let is_flipped = rng.gen_bool(self.label_noise);
+                        if is_correct != is_flipped { 1.0 } else { 0.0 }
+                    })
+                    .collect();
+
+                Retrieval::new(retrieved, utility_contributions)
+            })
+            .collect();
+
+        let grouping = self.num_groups.map(|num_groups| {
+            Grouping::new(num_groups, (0..self.corpus_size).map(unit_of).collect())
+        });
+
+        SyntheticDataset { retrievals, kinds, quality, grouping }
+    }
+}
+
+/// A generated validation set with its planted ground truth.
+#[derive(Debug, Clone)]
+pub struct SyntheticDataset {
+    pub retrievals: Vec<Retrieval>,
+    /// Quality class per source
+    pub kinds: Vec<SourceKind>,
+    /// Probability that a source yields a correct answer
+    pub quality: Vec<f64>,
+    pub grouping: Option<Grouping>,
+}
+
+impl SyntheticDataset {
+    pub fn is_bad(&self) -> Vec<bool> {
+        self.kinds.iter().map(|kind| *kind == SourceKind::Bad).collect()
+    }
+}
+
+// Ranks starting at 1, where ties get the average of their ranks
+fn average_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let average_rank = (start + end + 1) as f64 / 2.0;
+        for index in &order[start..end] {
+            ranks[*index] = average_rank;
+        }
+        start = end;
+    }
+
+    ranks
+}
+
+/// Area under the ROC curve of the scores for the given labels, i.e., the probability that a
+/// random positive has a higher score than a random negative (ties count half).
+pub fn roc_auc(scores: &[f64], labels: &[bool]) -> f64 {
+    assert_eq!(scores.len(), labels.len(), "need one label per score");
+
+    let num_positives = labels.iter().filter(|label| **label).count();
+    let num_negatives = labels.len() - num_positives;
+    assert!(num_positives > 0 && num_negatives > 0, "need positive and negative labels");
+
+    // Mann-Whitney U statistic from the ranks of the positives
+    let ranks = average_ranks(scores);
+    let positive_rank_sum: f64 = ranks.iter().zip(labels.iter())
+        .filter(|(_, label)| **label)
+        .map(|(rank, _)| rank)
+        .sum();
+    let positive_pairs = positive_rank_sum
+        - (num_positives * (num_positives + 1)) as f64 / 2.0;
+
+    positive_pairs / (num_positives * num_negatives) as f64
+}
+
+/// Spearman rank correlation, i.e., the Pearson correlation of the (average) ranks.
+pub fn spearman_correlation(a: &[f64], b: &[f64]) -> f64 {
+    assert_eq!(a.len(), b.len(), "need the same number of values");
+
+    let ranks_a = average_ranks(a);
+    let ranks_b = average_ranks(b);
+
+    let mean = (a.len() + 1) as f64 / 2.0;
+    let mut covariance = 0.0;
+    let mut variance_a = 0.0;
+    let mut variance_b = 0.0;
+    for (rank_a, rank_b) in ranks_a.iter().zip(ranks_b.iter()) {
+        covariance += (rank_a - mean) * (rank_b - mean);
+        variance_a += (rank_a - mean) * (rank_a - mean);
+        variance_b += (rank_b - mean) * (rank_b - mean);
+    }
+
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics() {
+        let labels = vec![false, true, false, true];
+        assert_eq!(roc_auc(&[0.1, 0.9, 0.2, 0.8], &labels), 1.0);
+        assert_eq!(roc_auc(&[0.9, 0.1, 0.8, 0.2], &labels), 0.0);
+        assert_eq!(roc_auc(&[0.5, 0.5, 0.5, 0.5], &labels), 0.5);
+        assert_eq!(roc_auc(&[0.1, 0.9, 0.8, 0.3], &labels), 0.75);
+
+        assert_eq!(spearman_correlation(&[1.0, 2.0, 3.0], &[10.0, 20.0, 30.0]), 1.0);
+        assert_eq!(spearman_correlation(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), -1.0);
+        assert_eq!(average_ranks(&[0.3, 0.1, 0.3, 0.2]), vec![3.5, 1.0, 3.5, 2.0]);
+    }
+
+    #[test]
+    fn plants_ground_truth() {
+        let dataset = Synthetic::new(200, 100, 10, 42)
+            .with_quality_mix(0.3, 0.1)
+            .with_groups(20)
+            .generate();
+
+        assert_eq!(dataset.retrievals.len(), 200);
+        assert_eq!(dataset.is_bad().iter().filter(|is_bad| **is_bad).count(), 30);
+
+        let grouping = dataset.grouping.as_ref().unwrap();
+        for (source, group) in grouping.group_assignments().iter().enumerate() {
+            assert_eq!(dataset.kinds[source], dataset.kinds[group * 5]);
+        }
+
+        for retrieval in &dataset.retrievals {
+            let mut retrieved = retrieval.retrieved.clone();
+            retrieved.sort();
+            retrieved.dedup();
+            assert_eq!(retrieved.len(), 10);
+        }
+
+        let regenerated = Synthetic::new(200, 100, 10, 42)
+            .with_quality_mix(0.3, 0.1)
+            .with_groups(20)
+            .generate();
+        assert_eq!(regenerated.retrievals, dataset.retrievals);
+    }
+
+    #[test]
+    fn retrieves_unpopular_sources_without_replacement() {
+        // The least popular sources have a vanishing (or zero) probability to be drawn
+        let dataset = Synthetic::new(5, 50, 50, 42)
+            .with_zipf_exponent(200.0)
+            .generate();
+
+        for retrieval in &dataset.retrievals {
+            let mut retrieved = retrieval.retrieved.clone();
+            retrieved.sort();
+            assert_eq!(retrieved, (0..50).collect::<Vec<_>>());
+        }
+    }
+}
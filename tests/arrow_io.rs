@@ -0,0 +1,208 @@
+#![cfg(feature = "arrow")]
+
+use ragbooster::arrow_io::{self, RetrievalColumns};
+use ragbooster::io::{read_group_json, read_qa_json, read_questions, Group};
+use ragbooster::mle::types::Retrieval;
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float64Array, Float64Builder, Int64Array, ListBuilder, RecordBatch, StringArray,
+    StringBuilder, TimestampMillisecondArray,
+};
+
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ragbooster_arrow_{}_{}", std::process::id(), name))
+}
+
+fn string_lists(rows: &[Vec<String>]) -> ArrayRef {
+    let mut builder = ListBuilder::new(StringBuilder::new());
+    for row in rows {
+        builder.append_value(row.iter().map(Some));
+    }
+    Arc::new(builder.finish())
+}
+
+fn float_lists(rows: &[Vec<f64>]) -> ArrayRef {
+    let mut builder = ListBuilder::new(Float64Builder::new());
+    for row in rows {
+        builder.append_value(row.iter().copied().map(Some));
+    }
+    Arc::new(builder.finish())
+}
+
+fn retrieval_batch(sources: &[Vec<String>], utility_contributions: &[Vec<f64>]) -> RecordBatch {
+    RecordBatch::try_from_iter(vec![
+        ("sources", string_lists(sources)),
+        ("utility_contributions", float_lists(utility_contributions)),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn retrievals_from_batches() {
+    let sources = vec![
+        vec!["c.com".to_owned(), "a.com".to_owned()],
+        vec![],
+        vec!["b.com".to_owned(), "c.com".to_owned()],
+    ];
+    let utility_contributions = vec![vec![1.0, 0.0], vec![], vec![0.5, 1.0]];
+    let weights: ArrayRef = Arc::new(Float64Array::from(vec![1.0, 2.0, 0.5]));
+
+    let batch = retrieval_batch(&sources, &utility_contributions);
+    let weighted_batch = RecordBatch::try_from_iter(vec![
+        ("sources", string_lists(&sources)),
+        ("utility_contributions", float_lists(&utility_contributions)),
+        ("weight", weights),
+    ])
+    .unwrap();
+
+    let (retrievals, source_indexer) =
+        arrow_io::retrievals_from_batches(&[weighted_batch], &RetrievalColumns::default()).unwrap();
+
+    assert_eq!(source_indexer.strings(), vec!["a.com", "b.com", "c.com"]);
+    assert_eq!(retrievals, vec![
+        Retrieval::new(vec![2, 0], vec![1.0, 0.0]),
+        Retrieval::with_weight(vec![], vec![], 2.0),
+        Retrieval::with_weight(vec![1, 2], vec![0.5, 1.0], 0.5),
+    ]);
+
+    // Without a weight column, every retrieval has a weight of one
+    let (unweighted, _) = arrow_io::retrievals_from_batches(
+        std::slice::from_ref(&batch), &RetrievalColumns::default()).unwrap();
+    assert!(unweighted.iter().all(|retrieval| retrieval.weight() == 1.0));
+
+    let renamed = RetrievalColumns { sources: "urls".to_owned(), ..RetrievalColumns::default() };
+    assert!(arrow_io::retrievals_from_batches(&[batch], &renamed).is_err());
+}
+
+#[test]
+fn timestamps_from_batches() {
+    let sources = vec![vec!["a.com".to_owned()], vec!["b.com".to_owned()]];
+    let utility_contributions = vec![vec![1.0], vec![0.0]];
+
+    // Arrow timestamps are converted to seconds, integers are taken as seconds
+    let timestamp_columns: [ArrayRef; 2] = [
+        Arc::new(TimestampMillisecondArray::from(vec![1_700_000_000_999, -3_600_000])),
+        Arc::new(Int64Array::from(vec![1_700_000_000, -3600])),
+    ];
+    for timestamps in timestamp_columns {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("sources", string_lists(&sources)),
+            ("utility_contributions", float_lists(&utility_contributions)),
+            ("timestamp", timestamps),
+        ])
+        .unwrap();
+
+        let (retrievals, _) =
+            arrow_io::retrievals_from_batches(&[batch], &RetrievalColumns::default()).unwrap();
+        assert_eq!(retrievals, vec![
+            Retrieval::new(vec![0], vec![1.0]).at(1_700_000_000),
+            Retrieval::new(vec![1], vec![0.0]).at(-3600),
+        ]);
+    }
+}
+
+#[test]
+fn inconsistent_rows_are_rejected() {
+    let batch = retrieval_batch(&[vec!["a.com".to_owned()]], &[vec![1.0, 0.0]]);
+    assert!(arrow_io::retrievals_from_batches(&[batch], &RetrievalColumns::default()).is_err());
+}
+
+#[test]
+fn weights_round_trip() {
+    let file = temp_file("weights.parquet");
+    let path = file.to_str().unwrap();
+
+    let names = vec!["a.com".to_owned(), "b.com".to_owned()];
+    let weights = vec![0.25, 1.0];
+    arrow_io::write_weights_parquet(path, &names, &weights).unwrap();
+
+    assert_eq!(arrow_io::read_weights_parquet(path).unwrap(), (names, weights));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn wikifact_from_parquet() {
+    let questions_file = "test_data/wikifact/currency.jsonl";
+    let groups_file = "test_data/wikifact/currency_websites_by_domain.jsonl";
+
+    let retrievals_parquet = temp_file("currency.parquet");
+    let groups_parquet = temp_file("groups.parquet");
+    let weights_parquet = temp_file("weights_from_parquet.parquet");
+    let weights_json = temp_file("weights_from_json.json");
+
+    // Write the questions and groups as Parquet files, like a Spark job would
+    let questions = read_questions(questions_file).unwrap();
+    let sources: Vec<Vec<String>> = questions.iter()
+        .map(|question| question.retrieved_websites.clone())
+        .collect();
+    let utility_contributions: Vec<Vec<f64>> = questions.iter()
+        .map(|question| {
+            question.retrieved_answers.iter()
+                .map(|answer| if question.correct_answers.contains(answer) { 1.0 } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    arrow_io::write_parquet(retrievals_parquet.to_str().unwrap(),
+                            &retrieval_batch(&sources, &utility_contributions)).unwrap();
+
+    let groups: Vec<Group> = std::fs::read_to_string(groups_file).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let names: ArrayRef = Arc::new(StringArray::from_iter_values(groups.iter().map(|g| &g.name)));
+    let elements = string_lists(&groups.iter().map(|g| g.elements.clone()).collect::<Vec<_>>());
+    let group_batch = RecordBatch::try_from_iter(vec![("name", names), ("elements", elements)])
+        .unwrap();
+    arrow_io::write_parquet(groups_parquet.to_str().unwrap(), &group_batch).unwrap();
+
+    // The library reads the same retrievals and groups as from the JSONL files
+    let (expected_retrievals, website_indexer) = read_qa_json(questions_file).unwrap();
+    let (retrievals, source_indexer) = arrow_io::read_retrievals_parquet(
+        retrievals_parquet.to_str().unwrap(), &RetrievalColumns::default()).unwrap();
+    assert_eq!(retrievals, expected_retrievals);
+    assert_eq!(source_indexer.strings(), website_indexer.strings());
+
+    let website_index = website_indexer.create_index();
+    let (expected_grouping, _) = read_group_json(groups_file, &website_index).unwrap();
+    let (grouping, _) =
+        arrow_io::read_groups_parquet(groups_parquet.to_str().unwrap(), &website_index).unwrap();
+    assert_eq!(grouping.group_assignments(), expected_grouping.group_assignments());
+
+    // Sources without a group are an error instead of a panic
+    let mut ungrouped_index = website_index.clone();
+    ungrouped_index.insert("ungrouped.example.org".to_string(), website_index.len());
+    let result = arrow_io::read_groups_parquet(groups_parquet.to_str().unwrap(), &ungrouped_index);
+    assert!(matches!(result, Err(error) if error.kind() == std::io::ErrorKind::InvalidData));
+
+    // And so does the command-line tool, which can also write the weights as Parquet
+    let learn = |questions: &str, groups: &str, format: &str, output: &PathBuf| {
+        let status = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+            .args(["learn", "--questions", questions, "--groups", groups, "--epochs", "2",
+                   "--format", format, "--output", output.to_str().unwrap()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    learn(retrievals_parquet.to_str().unwrap(), groups_parquet.to_str().unwrap(), "parquet",
+          &weights_parquet);
+    learn(questions_file, groups_file, "json", &weights_json);
+
+    let (names, weights) = arrow_io::read_weights_parquet(weights_parquet.to_str().unwrap())
+        .unwrap();
+    let expected: Vec<serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string(&weights_json).unwrap()).unwrap();
+    assert_eq!(names.len(), expected.len());
+    for ((name, weight), expected) in names.iter().zip(weights.iter()).zip(expected.iter()) {
+        assert_eq!(name, expected["name"].as_str().unwrap());
+        assert_eq!(*weight, expected["weight"].as_f64().unwrap());
+    }
+
+    for file in [retrievals_parquet, groups_parquet, weights_parquet, weights_json] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
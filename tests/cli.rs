@@ -0,0 +1,198 @@
+use std::process::Command;
+
+fn ragbooster(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster")).args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn learn_writes_weights_per_source_and_group() {
+    let output_dir = std::env::temp_dir().join(format!("ragbooster_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let weights_file = output_dir.join("weights.csv");
+    let group_weights_file = output_dir.join("group_weights.csv");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["learn",
+            "--questions", "test_data/wikifact/currency.jsonl",
+            "--groups", "test_data/wikifact/currency_websites_by_domain.jsonl",
+            "--epochs", "2",
+            "--format", "csv",
+            "--output", weights_file.to_str().unwrap(),
+            "--group-output", group_weights_file.to_str().unwrap()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let weights = std::fs::read_to_string(&weights_file).unwrap();
+    let mut lines = weights.lines();
+    assert_eq!(lines.next(), Some("name,weight"));
+    assert_eq!(lines.count(), 10034);
+
+    for line in std::fs::read_to_string(&group_weights_file).unwrap().lines().skip(1) {
+        let weight: f64 = line.rsplit(',').next().unwrap().parse().unwrap();
+        assert!((0.0..=1.0).contains(&weight));
+    }
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn evaluating_pruned_questions_matches_evaluating_with_threshold() {
+    let output_dir = std::env::temp_dir()
+        .join(format!("ragbooster_cli_prune_{}", std::process::id()));
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let weights_file = output_dir.join("weights.json");
+    let pruned_file = output_dir.join("pruned.jsonl");
+    let weights = weights_file.to_str().unwrap();
+    let pruned = pruned_file.to_str().unwrap();
+
+    let questions = "test_data/wikifact/currency.jsonl";
+
+    ragbooster(&["learn", "--questions", questions, "--epochs", "5", "--output", weights]);
+
+    let tuning = ragbooster(&["tune", "--questions", questions, "--weights", weights,
+                              "--percentiles", "0,50,90"]);
+    assert_eq!(tuning.lines().count(), 4);
+    let threshold = tuning.lines().nth(2).unwrap().split(',').nth(1).unwrap();
+
+    ragbooster(&["prune", "--questions", questions, "--weights", weights,
+                 "--threshold", threshold, "--output", pruned]);
+
+    let accuracy = |evaluation: String| -> f64 {
+        evaluation.lines().nth(1).unwrap().split(',').nth(1).unwrap().parse().unwrap()
+    };
+
+    let accuracy_with_threshold = accuracy(ragbooster(&["evaluate", "--questions", questions,
+        "--weights", weights, "--threshold", threshold]));
+    let accuracy_after_pruning = accuracy(ragbooster(&["evaluate", "--questions", pruned,
+        "--weights", weights, "--threshold", "0.0"]));
+
+    assert_eq!(accuracy_with_threshold, accuracy_after_pruning);
+
+    // The groups still name the pruned websites
+    let groups = "test_data/wikifact/currency_websites_by_domain.jsonl";
+    let grouped_accuracy_after_pruning = accuracy(ragbooster(&["evaluate", "--questions", pruned,
+        "--groups", groups, "--weights", weights, "--threshold", "0.0"]));
+    assert_eq!(accuracy_after_pruning, grouped_accuracy_after_pruning);
+
+    let inspection = ragbooster(&["inspect", "--questions", questions, "--weights", weights,
+                                  "--top", "3"]);
+    assert!(inspection.starts_with("Most important websites:"));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn learning_as_of_a_time_ignores_later_questions() {
+    let output_dir = std::env::temp_dir()
+        .join(format!("ragbooster_cli_as_of_{}", std::process::id()));
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let timed_file = output_dir.join("timed.jsonl");
+    let earlier_file = output_dir.join("earlier.jsonl");
+    let as_of_weights_file = output_dir.join("as_of_weights.json");
+    let earlier_weights_file = output_dir.join("earlier_weights.json");
+    let as_of_model_file = output_dir.join("as_of_model.json");
+
+    // One question per hour, the first 1000 are asked until the timestamp 999 * 3600
+    let lines: Vec<String> = std::fs::read_to_string("test_data/wikifact/currency.jsonl").unwrap()
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+    let timed: Vec<String> = lines.iter().enumerate()
+        .map(|(hour, line)| {
+            let mut question: serde_json::Value = serde_json::from_str(line).unwrap();
+            question["timestamp"] = serde_json::json!(hour * 3600);
+            question.to_string()
+        })
+        .collect();
+    std::fs::write(&timed_file, timed.join("\n")).unwrap();
+    std::fs::write(&earlier_file, lines[..1000].join("\n")).unwrap();
+
+    ragbooster(&["learn", "--questions", timed_file.to_str().unwrap(), "--epochs", "3",
+                 "--as-of", "3596400", "--output", as_of_weights_file.to_str().unwrap(),
+                 "--model", as_of_model_file.to_str().unwrap()]);
+    ragbooster(&["learn", "--questions", earlier_file.to_str().unwrap(), "--epochs", "3",
+                 "--output", earlier_weights_file.to_str().unwrap()]);
+
+    let weights_of = |file: &std::path::Path| -> Vec<serde_json::Value> {
+        serde_json::from_str(&std::fs::read_to_string(file).unwrap()).unwrap()
+    };
+    let earlier_weights = weights_of(&earlier_weights_file);
+    let as_of_weights: std::collections::HashMap<String, f64> =
+        weights_of(&as_of_weights_file).iter()
+            .map(|entry| (entry["name"].as_str().unwrap().to_owned(),
+                          entry["weight"].as_f64().unwrap()))
+            .collect();
+
+    for entry in &earlier_weights {
+        let name = entry["name"].as_str().unwrap();
+        assert_eq!(as_of_weights[name], entry["weight"].as_f64().unwrap(), "{name}");
+    }
+
+    // The model records the time decay
+    let model: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&as_of_model_file).unwrap()).unwrap();
+    assert_eq!(model["hyperparameters"]["as_of"], serde_json::json!(3596400));
+    assert!(model["hyperparameters"]["half_life_days"].is_null());
+
+    // A very short half life decays the weights of all questions to zero
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["learn", "--questions", timed_file.to_str().unwrap(), "--half-life-days", "0.0001",
+               "--as-of", "1700000000"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("decayed to zero"));
+
+    // Without timestamps, the weights cannot decay
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["learn", "--questions", earlier_file.to_str().unwrap(), "--half-life-days", "30"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn coverage_constraints_must_be_fractions() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["tune", "--questions", "test_data/wikifact/currency.jsonl",
+               "--max-corpus-pruned", "2"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not in [0, 1]"));
+}
+
+#[test]
+fn percentiles_must_be_in_range() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["tune", "--questions", "test_data/wikifact/currency.jsonl",
+               "--percentiles", "50,150"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not in [0, 100]"));
+}
+
+#[test]
+fn tuning_needs_weights_of_retrieved_websites() {
+    let output_dir = std::env::temp_dir()
+        .join(format!("ragbooster_cli_unknown_weights_{}", std::process::id()));
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let weights = output_dir.join("weights.csv");
+    std::fs::write(&weights, "name,weight\nunknown.example.org,0.5\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["tune", "--questions", "test_data/wikifact/currency.jsonl",
+               "--weights", weights.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no website of the questions"));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
@@ -0,0 +1,106 @@
+use std::cell::RefCell;
+
+use ragbooster::mle as mle;
+use mle::types::{Grouping, Retrieval};
+use ragbooster::pruning::{cross_validate_pruning, Aggregation, CrossValidation, Thresholds};
+
+const CORPUS_SIZE: usize = 3;
+const K: usize = 1;
+
+// Source 1 is ranked first but always wrong, sources 0 and 2 are right.
+fn retrievals() -> Vec<Retrieval> {
+    (0..53)
+        .map(|i| Retrieval::new(vec![1, i % 2 * 2], vec![0.0, 1.0]))
+        .collect()
+}
+
+#[test]
+fn held_out_utility_improves_with_pruning() {
+
+    let retrievals = retrievals();
+    let training_sizes = RefCell::new(Vec::new());
+
+    let result = cross_validate_pruning(
+        &retrievals,
+        None,
+        &CrossValidation::new(5, 42),
+        |training_retrievals| {
+            training_sizes.borrow_mut().push(training_retrievals.len());
+            mle::mle_importance(training_retrievals, CORPUS_SIZE, None, None, K, 1.0, 10, 1)
+        },
+        None,
+        K,
+        &Thresholds::Absolute(vec![0.0, 0.25, 1.1]),
+        Aggregation::Additive,
+        2
+    );
+
+    // Every question is held out exactly once
+    assert_eq!(training_sizes.borrow().iter().sum::<usize>(), 4 * retrievals.len());
+
+    assert_eq!(result.fold_utilities.len(), 5);
+    assert_eq!(result.mean_utilities.len(), 3);
+
+    // Without pruning, the top-1 source is always wrong, and pruning everything leaves nothing
+    assert_eq!(result.mean_utilities[0], 0.0);
+    assert_eq!(result.mean_utilities[1], 1.0);
+    assert_eq!(result.mean_utilities[2], 0.0);
+    assert_eq!(result.best_index, 1);
+}
+
+#[test]
+fn percentile_thresholds_are_computed_per_fold() {
+
+    let retrievals = retrievals();
+
+    let result = cross_validate_pruning(
+        &retrievals,
+        None,
+        &CrossValidation::new(3, 7),
+        |training_retrievals| {
+            mle::mle_importance(training_retrievals, CORPUS_SIZE, None, None, K, 1.0, 10, 1)
+        },
+        None,
+        K,
+        &Thresholds::Percentiles(vec![0.0, 50.0, 100.0]),
+        Aggregation::Additive,
+        1
+    );
+
+    for thresholds in &result.fold_thresholds {
+        assert_eq!(thresholds.len(), 3);
+        assert!(thresholds[0] <= thresholds[1] && thresholds[1] <= thresholds[2]);
+    }
+    assert!(result.mean_utilities[1] > result.mean_utilities[0]);
+}
+
+#[test]
+fn percentiles_of_grouped_sources_are_taken_over_the_groups() {
+
+    let retrievals = retrievals();
+    let grouping = Grouping::new(2, vec![0, 1, 1]);
+    let fold_weights = RefCell::new(Vec::new());
+
+    let result = cross_validate_pruning(
+        &retrievals,
+        None,
+        &CrossValidation::new(3, 7),
+        |training_retrievals| {
+            let weights = mle::mle_importance(
+                training_retrievals, CORPUS_SIZE, Some(&grouping), None, K, 1.0, 10, 1);
+            fold_weights.borrow_mut().push(weights.clone());
+            weights
+        },
+        Some(&grouping),
+        K,
+        &Thresholds::Percentiles(vec![0.0, 50.0, 100.0]),
+        Aggregation::Additive,
+        1
+    );
+
+    // The median of the two groups, instead of the weight of the larger group
+    for (weights, thresholds) in fold_weights.borrow().iter().zip(result.fold_thresholds.iter()) {
+        assert_eq!(weights[1], weights[2]);
+        assert!((thresholds[1] - (weights[0] + weights[1]) / 2.0).abs() < 1e-12);
+    }
+}
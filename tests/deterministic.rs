@@ -0,0 +1,51 @@
+use ragbooster::io::{read_group_json, read_qa_json};
+use ragbooster::mle::{self, Reduction};
+use ragbooster::model::{learn_model, Hyperparameters};
+use ragbooster::synthetic::Synthetic;
+
+#[test]
+fn weights_do_not_depend_on_the_number_of_jobs() {
+    let (retrievals, website_indexer) =
+        read_qa_json("test_data/wikifact/currency.jsonl").unwrap();
+    let corpus_size = website_indexer.num_observed_strings();
+    let (grouping, _) = read_group_json("test_data/wikifact/currency_websites_by_domain.jsonl",
+                                       &website_indexer.create_index()).unwrap();
+
+    let learn = |n_jobs: usize| {
+        mle::mle_importance_deterministic(
+            retrievals.clone(), corpus_size, Some(&grouping), None, 10, 0.1, 5, n_jobs)
+    };
+
+    let v_single_threaded = learn(1);
+    for n_jobs in [2, 3, 8, num_cpus::get()] {
+        assert_eq!(learn(n_jobs), v_single_threaded, "{n_jobs} jobs");
+    }
+
+    // Only the rounding differs from the per-job reduction
+    let v = mle::mle_importance(retrievals, corpus_size, Some(&grouping), None, 10, 0.1, 5, 1);
+    for (a, b) in v.iter().zip(v_single_threaded.iter()) {
+        assert!((a - b).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn tied_models_do_not_depend_on_the_number_of_jobs() {
+    let dataset = Synthetic::new(1000, 300, 10, 13).with_groups(30).generate();
+    let grouping = dataset.grouping.as_ref().unwrap();
+    let names: Vec<String> = (0..300).map(|source| format!("source{source}.com")).collect();
+    let group_names: Vec<String> = (0..30).map(|group| format!("group{group}")).collect();
+    let hyperparameters = Hyperparameters {
+        k: 5, learning_rate: 0.1, num_epochs: 5, l2: 0.1, l1: 0.0, prior: 0.5, tie_groups: true,
+        half_life_days: None, as_of: None, reduction: Reduction::Deterministic,
+    };
+
+    let learn = |n_jobs: usize| {
+        learn_model(dataset.retrievals.clone(), names.clone(),
+                    Some((grouping, group_names.clone())), hyperparameters.clone(), n_jobs)
+    };
+
+    let model = learn(1);
+    for n_jobs in [2, 5, 16] {
+        assert_eq!(learn(n_jobs), model, "{n_jobs} jobs");
+    }
+}
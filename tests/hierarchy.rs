@@ -0,0 +1,76 @@
+use ragbooster::mle as mle;
+use mle::types::{Grouping, Hierarchy, Retrieval};
+
+// Pages 0-3 live on host 0 (of domain 0), pages 4-5 on host 1 and pages 6-7 on host 2 (both of
+// domain 1). Host 0 gives the right answers, hosts 1 and 2 give wrong answers.
+fn retrievals() -> Vec<Retrieval> {
+    let mut retrievals = Vec::new();
+    for _ in 0..20 {
+        retrievals.push(Retrieval::new(vec![4, 0, 6, 1], vec![0.0, 1.0, 0.0, 1.0]));
+        retrievals.push(Retrieval::new(vec![5, 2, 7], vec![0.0, 1.0, 0.0]));
+    }
+    // Page 3 is only seen once
+    retrievals.push(Retrieval::new(vec![3, 0], vec![0.0, 1.0]));
+    retrievals
+}
+
+const CORPUS_SIZE: usize = 8;
+
+fn pages_to_hosts() -> Grouping {
+    Grouping::new(3, vec![0, 0, 0, 0, 1, 1, 2, 2])
+}
+
+fn hosts_to_domains() -> Grouping {
+    Grouping::new(2, vec![0, 1, 1])
+}
+
+fn assert_same(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert!((x - y).abs() < 0.0000001);
+    }
+}
+
+#[test]
+fn single_level_with_full_strength_matches_grouping() {
+    let v_grouped = mle::mle_importance(
+        retrievals(), CORPUS_SIZE, Some(&pages_to_hosts()), None, 1, 0.5, 10, 1);
+
+    let hierarchy = Hierarchy::new(vec![pages_to_hosts()], vec![1.0]);
+    let v_hierarchical = mle::mle_importance_hierarchical(
+        retrievals(), CORPUS_SIZE, &hierarchy, None, 1, 0.5, 10, 1);
+
+    assert_same(&v_grouped, &v_hierarchical);
+}
+
+#[test]
+fn zero_strength_matches_no_grouping() {
+    let v = mle::mle_importance(retrievals(), CORPUS_SIZE, None, None, 1, 0.5, 10, 1);
+
+    let hierarchy = Hierarchy::new(vec![pages_to_hosts(), hosts_to_domains()], vec![0.0, 0.0]);
+    let v_hierarchical = mle::mle_importance_hierarchical(
+        retrievals(), CORPUS_SIZE, &hierarchy, None, 1, 0.5, 10, 1);
+
+    assert_same(&v, &v_hierarchical);
+}
+
+#[test]
+fn pages_are_shrunk_towards_hosts() {
+    let hierarchy = Hierarchy::new(vec![pages_to_hosts(), hosts_to_domains()], vec![0.5, 0.5]);
+    let v = mle::mle_importance_hierarchical(
+        retrievals(), CORPUS_SIZE, &hierarchy, None, 1, 0.5, 20, 2);
+
+    let v_unshrunk = mle::mle_importance(retrievals(), CORPUS_SIZE, None, None, 1, 0.5, 20, 1);
+
+    // The rarely seen page of a good host moves up towards its host
+    assert!(v[3] > v_unshrunk[3]);
+
+    let v_per_level = mle::v_hierarchical(&v, &hierarchy);
+    assert_eq!(v_per_level.len(), 2);
+    assert_eq!(v_per_level[0].len(), 3);
+    assert_eq!(v_per_level[1].len(), 2);
+
+    assert!(v_per_level[0][0] > v_per_level[0][1]);
+    assert!(v_per_level[0][0] > v_per_level[0][2]);
+    assert!(v_per_level[1][0] > v_per_level[1][1]);
+}
@@ -0,0 +1,88 @@
+use ragbooster::io::{
+    encode_groups, encode_questions, read_qa_json, Group, QaJsonStream, QuestionAnswering,
+};
+
+use std::collections::HashMap;
+
+use std::io;
+
+
+fn question(correct_answers: &[&str], websites: &[&str], answers: &[&str]) -> QuestionAnswering {
+    let strings = |strs: &[&str]| strs.iter().map(|a_str| a_str.to_string()).collect();
+    QuestionAnswering {
+        question: "q".to_string(),
+        correct_answers: strings(correct_answers),
+        retrieved_websites: strings(websites),
+        retrieved_answers: strings(answers),
+        timestamp: None,
+    }
+}
+
+fn write_questions(name: &str, questions: &[QuestionAnswering]) -> String {
+    let path = std::env::temp_dir()
+        .join(format!("ragbooster_io_{}_{}.jsonl", name, std::process::id()));
+    let lines: Vec<String> = questions.iter()
+        .map(|question| serde_json::to_string(question).unwrap())
+        .collect();
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+fn assert_invalid_data<T>(result: io::Result<T>, message: &str) {
+    match result {
+        Ok(_) => panic!("expected an error containing '{message}'"),
+        Err(error) => {
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains(message), "{error}");
+        },
+    }
+}
+
+#[test]
+fn malformed_questions_are_invalid_data() {
+
+    let valid = question(&["a"], &["w1", "w2"], &["a", "b"]);
+    let without_answers = question(&[], &["w1"], &["a"]);
+    let inconsistent = question(&["a"], &["w1", "w2"], &["a"]);
+
+    assert_invalid_data(encode_questions(&[valid, without_answers]),
+                        "correct_answers empty in line 2");
+
+    let questions = [question(&["a"], &["w1"], &["a"]), inconsistent];
+    assert_invalid_data(encode_questions(&questions),
+                        "inconsistent number of websites and answers in line 2");
+
+    let path = write_questions("inconsistent", &questions);
+    assert_invalid_data(read_qa_json(&path), "in line 2");
+    assert_invalid_data(QaJsonStream::open(&path), "in line 2");
+    std::fs::remove_file(path).unwrap();
+
+    let path = write_questions("unparseable", &[question(&["a"], &["w1"], &["a"])]);
+    let mut contents = std::fs::read_to_string(&path).unwrap();
+    contents.push_str("\n{\"question\": \"q\"");
+    std::fs::write(&path, contents).unwrap();
+    assert_invalid_data(read_qa_json(&path), "invalid question in line 2");
+    assert_invalid_data(QaJsonStream::open(&path), "invalid question in line 2");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn groups_may_name_unknown_elements_but_must_cover_all_known_ones() {
+
+    let group = |name: &str, elements: &[&str]| Group {
+        name: name.to_string(),
+        elements: elements.iter().map(|element| element.to_string()).collect(),
+    };
+    let element_index: HashMap<String, usize> =
+        HashMap::from([("w1".to_string(), 0), ("w2".to_string(), 1)]);
+
+    // E.g., websites that were pruned from the questions
+    let groups = vec![group("b", &["w1", "pruned"]), group("a", &["w2"]), group("c", &["gone"])];
+    let (grouping, group_indexer) = encode_groups(groups, &element_index).unwrap();
+    assert_eq!(grouping.group_assignments(), &[1, 0]);
+    assert_eq!(group_indexer.strings(), vec!["a", "b", "c"]);
+
+    let groups = vec![group("a", &["w1", "pruned"])];
+    assert_invalid_data(encode_groups(groups, &element_index),
+                        "Group information missing for 1 elements, e.g., w2");
+}
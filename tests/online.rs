@@ -0,0 +1,164 @@
+use ragbooster::mle::{mle_importance, v_grouped, Reduction};
+use ragbooster::model::{learn_model, DatasetFingerprint, Hyperparameters, Model};
+use ragbooster::online::{OnlineLearner, OnlineState};
+use ragbooster::synthetic::{roc_auc, Synthetic};
+
+const CORPUS_SIZE: usize = 200;
+
+fn hyperparameters(num_epochs: usize) -> Hyperparameters {
+    Hyperparameters {
+        k: 5, learning_rate: 0.1, num_epochs, l2: 0.0, l1: 0.0, prior: 0.5, tie_groups: false,
+        half_life_days: None, as_of: None, reduction: Reduction::PerJob,
+    }
+}
+
+fn names() -> Vec<String> {
+    (0..CORPUS_SIZE).map(|source| format!("source{source}.com")).collect()
+}
+
+#[test]
+fn first_update_without_replay_matches_batch_training() {
+    let dataset = Synthetic::new(300, CORPUS_SIZE, 10, 3).generate();
+
+    let mut learner =
+        OnlineLearner::new(names(), None, hyperparameters(20), OnlineState::new(0, 0));
+    learner.update(dataset.retrievals.clone(), 1);
+
+    let v = mle_importance(dataset.retrievals, CORPUS_SIZE, None, None, 5, 0.1, 20, 1);
+
+    assert_eq!(learner.weights(), v.as_slice());
+}
+
+#[test]
+fn first_update_matches_learned_model_for_any_prior() {
+    let dataset = Synthetic::new(300, CORPUS_SIZE, 10, 4).generate();
+    let hyperparameters = Hyperparameters { prior: 0.3, ..hyperparameters(10) };
+
+    let mut learner =
+        OnlineLearner::new(names(), None, hyperparameters.clone(), OnlineState::new(0, 0));
+    learner.update(dataset.retrievals.clone(), 1);
+
+    let model = learn_model(dataset.retrievals, names(), None, hyperparameters, 1);
+
+    assert_eq!(learner.weights(), model.weights.as_slice());
+}
+
+#[test]
+fn resumes_from_checkpoint() {
+    let dataset = Synthetic::new(600, CORPUS_SIZE, 10, 5).with_groups(20).generate();
+    let grouping = dataset.grouping.as_ref().unwrap();
+    let group_names: Vec<String> = (0..20).map(|group| format!("group{group}")).collect();
+    let batches: Vec<_> = dataset.retrievals.chunks(100).map(|batch| batch.to_vec()).collect();
+
+    let new_learner = || OnlineLearner::new(
+        names(), Some((grouping, group_names.clone())), hyperparameters(5),
+        OnlineState::new(150, 42));
+
+    let mut learner = new_learner();
+    for batch in &batches {
+        learner.update(batch.clone(), 1);
+    }
+
+    let mut interrupted = new_learner();
+    let path = std::env::temp_dir().join(format!("ragbooster_online_{}.bin", std::process::id()));
+    for (index, batch) in batches.iter().enumerate() {
+        interrupted.update(batch.clone(), 2);
+        interrupted.checkpoint().save_binary(path.to_str().unwrap()).unwrap();
+        let model = Model::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(model.history.len(), index + 1);
+        interrupted = OnlineLearner::from_model(model, OnlineState::new(0, 0)).unwrap();
+    }
+    std::fs::remove_file(path).unwrap();
+
+    for (a, b) in learner.weights().iter().zip(interrupted.weights().iter()) {
+        assert!((a - b).abs() < 1e-12);
+    }
+    assert_eq!(learner.state().replay, interrupted.state().replay);
+
+    let state = learner.state();
+    assert_eq!(state.num_updates, 6);
+    assert_eq!(state.num_steps, 30);
+    assert_eq!(state.num_retrievals, 600);
+    assert_eq!(state.replay.len(), 150);
+
+    let model = learner.checkpoint();
+    assert_eq!(model.fingerprint, DatasetFingerprint::of(&dataset.retrievals, CORPUS_SIZE));
+    assert_eq!(model.grouping.unwrap().group_weights, v_grouped(learner.weights(), grouping));
+}
+
+#[test]
+fn online_training_recovers_planted_quality() {
+    let dataset = Synthetic::new(2000, CORPUS_SIZE, 20, 7).with_label_noise(0.05).generate();
+    let is_good: Vec<bool> = dataset.is_bad().iter().map(|is_bad| !is_bad).collect();
+
+    let mut learner =
+        OnlineLearner::new(names(), None, hyperparameters(5), OnlineState::new(500, 1));
+    for batch in dataset.retrievals.chunks(50) {
+        learner.update(batch.to_vec(), 1);
+    }
+
+    let auc = roc_auc(learner.weights(), &is_good);
+    assert!(auc > 0.9);
+}
+
+#[test]
+fn continues_a_learned_model() {
+    let dataset = Synthetic::new(400, CORPUS_SIZE, 10, 9).generate();
+    let (first, second) = dataset.retrievals.split_at(200);
+
+    let model = learn_model(first.to_vec(), names(), None, hyperparameters(10), 1);
+    let weights_before = model.weights.clone();
+
+    let mut learner = OnlineLearner::from_model(model, OnlineState::new(100, 0)).unwrap();
+    learner.update(second.to_vec(), 1);
+
+    assert_ne!(learner.weights(), weights_before.as_slice());
+    let model = learner.checkpoint();
+    assert_eq!(model.history.len(), 2);
+    assert_eq!(model.fingerprint, DatasetFingerprint::of(&dataset.retrievals, CORPUS_SIZE));
+}
+
+#[test]
+fn small_batches_after_large_ones_take_small_steps() {
+    let dataset = Synthetic::new(1010, CORPUS_SIZE, 10, 11).generate();
+    let (large, small) = dataset.retrievals.split_at(1000);
+
+    let max_change = |before: &[f64], after: &[f64]| -> f64 {
+        before.iter().zip(after.iter()).map(|(b, a)| (a - b).abs()).fold(0.0, f64::max)
+    };
+
+    let new_learner = || {
+        OnlineLearner::new(names(), None, hyperparameters(5), OnlineState::new(0, 0))
+    };
+
+    let mut learner = new_learner();
+    learner.update(large.to_vec(), 1);
+    let weights_before = learner.weights().to_vec();
+    learner.update(small.to_vec(), 1);
+    let change_after_large = max_change(&weights_before, learner.weights());
+    assert_eq!(learner.state().total_weight, 1010.0);
+
+    // The same batch moves the weights of a new learner with the full step
+    let mut fresh = new_learner();
+    fresh.update(small.to_vec(), 1);
+    let change_of_fresh = max_change(&vec![0.5; CORPUS_SIZE], fresh.weights());
+
+    assert!(change_after_large > 0.0);
+    assert!(change_after_large < change_of_fresh * 0.05,
+            "{change_after_large} vs. {change_of_fresh}");
+}
+
+#[test]
+fn empty_update_without_replay_counts_but_keeps_the_weights() {
+    let mut learner =
+        OnlineLearner::new(names(), None, hyperparameters(5), OnlineState::new(0, 0));
+
+    assert_eq!(learner.update(vec![], 1), 0.0);
+
+    assert_eq!(learner.weights(), vec![0.5; CORPUS_SIZE].as_slice());
+    let state = learner.state();
+    assert_eq!(state.num_updates, 1);
+    assert_eq!(state.num_steps, 0);
+    assert_eq!(state.num_retrievals, 0);
+    assert_eq!(learner.checkpoint().history.len(), 1);
+}
@@ -0,0 +1,62 @@
+use ragbooster::mle as mle;
+use mle::types::{Regularisation, Retrieval};
+
+// Source 0 always gives the right answer, source 1 gives the wrong answer in many questions,
+// source 2 gives the wrong answer in a single question only.
+fn retrievals() -> Vec<Retrieval> {
+    let mut retrievals: Vec<Retrieval> = (0..50)
+        .map(|_| Retrieval::new(vec![1, 0, 3], vec![0.0, 1.0, 1.0]))
+        .collect();
+
+    retrievals.push(Retrieval::new(vec![2, 0, 3], vec![0.0, 1.0, 1.0]));
+
+    retrievals
+}
+
+const CORPUS_SIZE: usize = 4;
+const K: usize = 1;
+const LEARNING_RATE: f64 = 1.0;
+const NUM_EPOCHS: usize = 200;
+
+#[test]
+fn little_evidence_stays_near_prior() {
+
+    let v = mle::mle_importance(
+        retrievals(), CORPUS_SIZE, None, None, K, LEARNING_RATE, NUM_EPOCHS, 1);
+
+    let regularisation = Regularisation::uniform(0.2, 0.0, 0.5, CORPUS_SIZE);
+
+    let v_regularised = mle::mle_importance(
+        retrievals(), CORPUS_SIZE, None, Some(&regularisation), K, LEARNING_RATE, NUM_EPOCHS, 1);
+
+    // The source with lots of evidence is still detected as harmful
+    assert!(v_regularised[1] < 0.1);
+    // The source with a single observation stays near the prior
+    assert!(v_regularised[2] > 0.3);
+    assert!(v_regularised[2] > v[2]);
+}
+
+#[test]
+fn l1_prunes_unused_sources() {
+
+    let regularisation = Regularisation::uniform(0.0, 0.01, 0.5, CORPUS_SIZE + 1);
+
+    let v = mle::mle_importance(retrievals(), CORPUS_SIZE + 1, None, Some(&regularisation), K,
+                                LEARNING_RATE, NUM_EPOCHS, 1);
+
+    // Never retrieved, so only the sparsity term applies
+    assert_eq!(v[CORPUS_SIZE], 0.0);
+    assert!(v[0] > 0.5);
+}
+
+#[test]
+#[should_panic(expected = "priors must be in [0, 1]")]
+fn priors_must_be_probabilities() {
+    Regularisation::uniform(1.0, 0.0, 1.5, CORPUS_SIZE);
+}
+
+#[test]
+#[should_panic(expected = "l2 must be non-negative")]
+fn l2_must_be_non_negative() {
+    Regularisation::uniform(-1.0, 0.0, 0.5, CORPUS_SIZE);
+}
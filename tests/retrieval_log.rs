@@ -0,0 +1,159 @@
+use ragbooster::io::{read_group_json, read_qa_json};
+use ragbooster::mle as mle;
+use ragbooster::retrieval_log::{write_retrieval_log, RetrievalLog};
+use mle::streaming::mle_importance_streaming;
+use mle::types::{Grouping, Retrieval};
+use std::path::PathBuf;
+use std::process::Command;
+
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ragbooster_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn round_trip_with_grouping() {
+    let file = temp_file("round_trip.rlog");
+    let path = file.to_str().unwrap();
+
+    let retrievals = vec![
+        Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 0.25]),
+        Retrieval::with_weight(vec![], vec![], 2.0),
+        Retrieval::with_weight(vec![2, 0], vec![-0.5, 1.75], 0.5),
+    ];
+    let source_names = vec!["a.com".to_owned(), "b.org".to_owned(), "ü.de".to_owned()];
+    let grouping = Grouping::new(2, vec![1, 0, 1]);
+    let group_names = vec!["org".to_owned(), "other".to_owned()];
+
+    write_retrieval_log(path, &retrievals, &source_names, Some((&grouping, &group_names))).unwrap();
+
+    let log = RetrievalLog::open(path).unwrap();
+    assert_eq!(log.num_retrievals(), 3);
+    assert_eq!(log.corpus_size(), 3);
+    assert_eq!(log.retrievals(), retrievals);
+    assert_eq!(log.retrievals_in(1..3), retrievals[1..3]);
+    assert_eq!(log.source_names(), source_names);
+
+    let (decoded_grouping, decoded_group_names) = log.grouping().unwrap();
+    assert_eq!(decoded_grouping.group_assignments(), grouping.group_assignments());
+    assert_eq!(decoded_group_names, group_names);
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn utility_contributions_are_quantised() {
+    let file = temp_file("quantised.rlog");
+    let path = file.to_str().unwrap();
+
+    let retrievals = vec![Retrieval::new(vec![0, 1], vec![0.333333, 0.6789])];
+    write_retrieval_log(path, &retrievals, &["a".to_owned(), "b".to_owned()], None).unwrap();
+
+    let log = RetrievalLog::open(path).unwrap();
+    assert_eq!(log.retrievals(), vec![Retrieval::new(vec![0, 1], vec![0.33, 0.68])]);
+    assert!(log.grouping().is_none());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn timestamps_round_trip() {
+    let file = temp_file("timestamps.rlog");
+    let path = file.to_str().unwrap();
+    let names = ["a".to_owned(), "b".to_owned()];
+
+    let retrievals = vec![
+        Retrieval::new(vec![0, 1], vec![1.0, 0.0]).at(1_700_000_000),
+        Retrieval::with_weight(vec![1], vec![0.5], 2.0).at(-3600),
+    ];
+    write_retrieval_log(path, &retrievals, &names, None).unwrap();
+    assert_eq!(RetrievalLog::open(path).unwrap().retrievals(), retrievals);
+
+    let partially_timed = vec![retrievals[0].clone(), Retrieval::new(vec![0], vec![1.0])];
+    assert!(write_retrieval_log(path, &partially_timed, &names, None).is_err());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn truncated_and_foreign_files_are_rejected() {
+    let file = temp_file("truncated.rlog");
+    let path = file.to_str().unwrap();
+
+    let retrievals = vec![Retrieval::new(vec![0, 1], vec![1.0, 0.0])];
+    write_retrieval_log(path, &retrievals, &["a".to_owned(), "b".to_owned()], None).unwrap();
+
+    let bytes = std::fs::read(&file).unwrap();
+    std::fs::write(&file, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(RetrievalLog::open(path).is_err());
+
+    assert!(RetrievalLog::open("test_data/wikifact/currency.jsonl").is_err());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn corrupt_counts_in_the_header_are_rejected() {
+    let file = temp_file("corrupt.rlog");
+    let path = file.to_str().unwrap();
+
+    let retrievals = vec![Retrieval::new(vec![0, 1], vec![1.0, 0.0])];
+    let grouping = Grouping::new(1, vec![0, 0]);
+    write_retrieval_log(path, &retrievals, &["a".to_owned(), "b".to_owned()],
+                        Some((&grouping, &["g".to_owned()]))).unwrap();
+    let bytes = std::fs::read(&file).unwrap();
+
+    // The number of retrievals, entries, sources and groups
+    for position in [16, 24, 32, 40] {
+        for count in [u64::MAX, u64::MAX / 2] {
+            let mut corrupt = bytes.clone();
+            corrupt[position..position + 8].copy_from_slice(&count.to_le_bytes());
+            std::fs::write(&file, &corrupt).unwrap();
+
+            let error = RetrievalLog::open(path).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn converted_wikifact_trains_like_jsonl() {
+    let questions_file = "test_data/wikifact/currency.jsonl";
+    let groups_file = "test_data/wikifact/currency_websites_by_domain.jsonl";
+    let file = temp_file("currency.rlog");
+    let path = file.to_str().unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_ragbooster"))
+        .args(["convert", "--questions", questions_file, "--groups", groups_file, "--output", path])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let (retrievals, website_indexer) = read_qa_json(questions_file).unwrap();
+    let (grouping, group_indexer) =
+        read_group_json(groups_file, &website_indexer.create_index()).unwrap();
+
+    let log = RetrievalLog::open(path).unwrap();
+    assert_eq!(log.retrievals(), retrievals);
+    assert_eq!(log.source_names(), website_indexer.strings());
+    let (decoded_grouping, group_names) = log.grouping().unwrap();
+    assert_eq!(decoded_grouping.group_assignments(), grouping.group_assignments());
+    assert_eq!(group_names, group_indexer.strings());
+
+    let corpus_size = log.corpus_size();
+    let expected = mle::mle_importance(retrievals, corpus_size, Some(&grouping), None, 10, 0.1,
+                                       2, 1);
+    let v = mle::mle_importance(log.retrievals(), corpus_size, Some(&decoded_grouping), None, 10,
+                                0.1, 2, 1);
+    assert_eq!(expected, v);
+
+    let v_streaming = mle_importance_streaming(&log, corpus_size, Some(&decoded_grouping), None, 10,
+                                               0.1, 2, 1, 1000).unwrap();
+    for (e, s) in expected.iter().zip(v_streaming.iter()) {
+        assert!((e - s).abs() < 1e-12);
+    }
+
+    std::fs::remove_file(&file).unwrap();
+}
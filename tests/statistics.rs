@@ -0,0 +1,50 @@
+use ragbooster::mle::statistics::{Bootstrap, mle_importance_with_statistics};
+use ragbooster::mle::types::Retrieval;
+
+#[test]
+fn more_evidence_gives_narrower_intervals() {
+
+    // Source 0 always gives the right answer, source 1 gives the wrong answer in many questions,
+    // source 2 gives the wrong answer in two questions only.
+    let mut retrievals: Vec<Retrieval> = (0..50)
+        .map(|_| Retrieval::new(vec![1, 0], vec![0.0, 1.0]))
+        .collect();
+    retrievals.push(Retrieval::new(vec![2, 0], vec![0.0, 1.0]));
+    retrievals.push(Retrieval::new(vec![2, 0], vec![0.0, 1.0]));
+
+    let bootstrap = Bootstrap::new(50, 0.9, 42);
+
+    let (v, statistics) = mle_importance_with_statistics(
+        retrievals, 3, None, None, 1, 1.0, 20, Some(&bootstrap), 2);
+
+    assert_eq!(v.len(), 3);
+    assert_eq!(statistics[1].num_retrievals, 50);
+    assert_eq!(statistics[2].num_retrievals, 2);
+    assert_eq!(statistics[2].num_retrievals_in_top_k, 2);
+
+    let (lower_1, upper_1) = statistics[1].confidence_interval.unwrap();
+    let (lower_2, upper_2) = statistics[2].confidence_interval.unwrap();
+
+    assert!(lower_1 <= v[1] && v[1] <= upper_1);
+    assert!(upper_1 - lower_1 < upper_2 - lower_2);
+}
+
+#[test]
+fn resamples_of_weightless_questions_are_drawn_again() {
+
+    // Every resample of the two questions only contains the weightless one with probability 1/4
+    let retrievals = vec![
+        Retrieval::with_weight(vec![0, 1], vec![1.0, 0.0], 0.0),
+        Retrieval::new(vec![1, 0], vec![0.0, 1.0]),
+    ];
+
+    let bootstrap = Bootstrap::new(40, 0.9, 7);
+
+    let (_, statistics) = mle_importance_with_statistics(
+        retrievals, 2, None, None, 1, 1.0, 5, Some(&bootstrap), 2);
+
+    for source_statistics in &statistics {
+        let (lower, upper) = source_statistics.confidence_interval.unwrap();
+        assert!(lower.is_finite() && upper.is_finite() && lower <= upper);
+    }
+}
@@ -0,0 +1,90 @@
+use ragbooster::mle as mle;
+use ragbooster::io::{read_group_json, read_qa_json, QaJsonStream};
+use mle::streaming::mle_importance_streaming;
+use mle::types::Retrieval;
+
+
+const K: usize = 10;
+const LEARNING_RATE: f64 = 0.1;
+const NUM_EPOCHS: usize = 10;
+
+fn assert_close(expected: &[f64], actual: &[f64]) {
+    assert_eq!(expected.len(), actual.len());
+    for (e, a) in expected.iter().zip(actual.iter()) {
+        assert!((e - a).abs() < 1e-12, "{e} != {a}");
+    }
+}
+
+#[test]
+fn streaming_matches_in_memory_training() {
+
+    let retrievals = vec![
+        Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 1.0]),
+        Retrieval::with_weight(vec![3, 1, 0], vec![0.0, 1.0, 0.33], 2.0),
+        Retrieval::new(vec![4, 2], vec![0.5, 0.5]),
+        Retrieval::new(vec![1, 4, 3, 0], vec![0.0, 0.0, 1.0, 0.25]),
+        Retrieval::new(vec![2], vec![1.0]),
+    ];
+
+    let expected = mle::mle_importance(retrievals.clone(), 6, None, None, 2, 0.5, 10, 1);
+
+    for chunk_size in [1, 2, 5, 100] {
+        for n_jobs in [1, 2] {
+            let v = mle_importance_streaming(
+                retrievals.as_slice(), 6, None, None, 2, 0.5, 10, n_jobs, chunk_size).unwrap();
+            assert_close(&expected, &v);
+            // Never retrieved
+            assert_eq!(v[5], 0.5);
+        }
+    }
+}
+
+#[test]
+fn streaming_from_jsonl() {
+    let questions_file = "test_data/wikifact/currency.jsonl";
+    let groups_file = "test_data/wikifact/currency_websites_by_domain.jsonl";
+
+    let (retrievals, website_indexer) = read_qa_json(questions_file).unwrap();
+    let (stream, stream_website_indexer) = QaJsonStream::open(questions_file).unwrap();
+    assert_eq!(website_indexer.strings(), stream_website_indexer.strings());
+
+    let corpus_size = website_indexer.num_observed_strings();
+    let (grouping, _) = read_group_json(groups_file, &website_indexer.create_index()).unwrap();
+
+    let expected = mle::mle_importance(retrievals, corpus_size, Some(&grouping), None, K,
+                                       LEARNING_RATE, NUM_EPOCHS, 1);
+    let v = mle_importance_streaming(&stream, corpus_size, Some(&grouping), None, K,
+                                     LEARNING_RATE, NUM_EPOCHS, 2, 100).unwrap();
+
+    assert_close(&expected, &v);
+}
+
+#[test]
+fn sources_outside_of_the_corpus_are_an_error() {
+    let retrievals = vec![Retrieval::new(vec![0, 3], vec![1.0, 0.0])];
+
+    let result = mle_importance_streaming(retrievals.as_slice(), 3, None, None, 1, 0.1, 1, 1, 10);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn empty_and_weightless_streams_are_an_error() {
+    let empty: Vec<Retrieval> = Vec::new();
+    let weightless = vec![Retrieval::with_weight(vec![0, 1], vec![1.0, 0.0], 0.0)];
+
+    for retrievals in [empty, weightless] {
+        let result =
+            mle_importance_streaming(retrievals.as_slice(), 3, None, None, 1, 0.1, 1, 1, 10);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn empty_chunks_are_an_error() {
+    let retrievals = vec![Retrieval::new(vec![0, 1], vec![1.0, 0.0])];
+
+    let result = mle_importance_streaming(retrievals.as_slice(), 3, None, None, 1, 0.1, 1, 1, 0);
+
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,41 @@
+use ragbooster::mle::{mle_importance, v_grouped};
+use ragbooster::synthetic::{roc_auc, spearman_correlation, Synthetic};
+
+#[test]
+fn recovers_planted_quality() {
+    let dataset = Synthetic::new(2000, 200, 20, 7)
+        .with_label_noise(0.05)
+        .generate();
+
+    let v = mle_importance(dataset.retrievals.clone(), 200, None, None, 10, 0.1, 50, 1);
+
+    let is_good: Vec<bool> = dataset.is_bad().iter().map(|is_bad| !is_bad).collect();
+    let auc = roc_auc(&v, &is_good);
+    let correlation = spearman_correlation(&v, &dataset.quality);
+
+    assert!(auc > 0.9);
+    assert!(correlation > 0.5);
+}
+
+#[test]
+fn groups_help_with_rare_sources() {
+    let dataset = Synthetic::new(300, 500, 10, 11)
+        .with_zipf_exponent(1.2)
+        .with_groups(50)
+        .generate();
+    let grouping = dataset.grouping.as_ref().unwrap();
+
+    let is_good: Vec<bool> = dataset.is_bad().iter().map(|is_bad| !is_bad).collect();
+
+    let v = mle_importance(dataset.retrievals.clone(), 500, None, None, 5, 0.1, 50, 1);
+    let v_with_groups = mle_importance(
+        dataset.retrievals.clone(), 500, Some(grouping), None, 5, 0.1, 50, 1);
+
+    let auc = roc_auc(&v, &is_good);
+    let auc_with_groups = roc_auc(&v_with_groups, &is_good);
+
+    assert!(auc_with_groups > auc);
+
+    let quality_of_groups = v_grouped(&dataset.quality, grouping);
+    assert!(spearman_correlation(&v_grouped(&v_with_groups, grouping), &quality_of_groups) > 0.5);
+}
@@ -0,0 +1,103 @@
+use ragbooster::mle::{mle_importance, mle_importance_as_of};
+use ragbooster::mle::types::{Retrieval, TimeDecay};
+use ragbooster::synthetic::Synthetic;
+
+use std::io::ErrorKind;
+
+const DAY: i64 = 24 * 3600;
+
+#[test]
+fn weights_halve_every_half_life() {
+    let time_decay = TimeDecay::new(10.0 * DAY as f64, 100 * DAY);
+
+    assert_eq!(time_decay.factor(100 * DAY), 1.0);
+    assert!((time_decay.factor(90 * DAY) - 0.5).abs() < 1e-12);
+    assert!((time_decay.factor(70 * DAY) - 0.125).abs() < 1e-12);
+    assert_eq!(time_decay.factor(100 * DAY + 1), 0.0);
+
+    let retrievals = vec![
+        Retrieval::with_weight(vec![0], vec![1.0], 2.0).at(90 * DAY),
+        Retrieval::new(vec![1], vec![0.0]).at(101 * DAY),
+        Retrieval::new(vec![0, 1], vec![1.0, 0.0]).at(100 * DAY),
+    ];
+    let decayed = time_decay.apply(retrievals);
+
+    assert_eq!(decayed.len(), 2);
+    assert!((decayed[0].weight() - 1.0).abs() < 1e-12);
+    assert_eq!(decayed[0].timestamp(), Some(90 * DAY));
+    assert_eq!(decayed[1].weight(), 1.0);
+
+    // Ages beyond the range of i64
+    assert_eq!(TimeDecay::new(10.0, i64::MAX).factor(i64::MIN), 0.0);
+    assert_eq!(TimeDecay::new(f64::INFINITY, i64::MAX).factor(i64::MIN), 1.0);
+}
+
+#[test]
+fn as_of_without_decay_ignores_later_retrievals() {
+    let corpus_size = 100;
+    let dataset = Synthetic::new(400, corpus_size, 10, 11).generate();
+    let timed: Vec<Retrieval> = dataset.retrievals.iter().cloned().enumerate()
+        .map(|(day, retrieval)| retrieval.at(day as i64 * DAY))
+        .collect();
+
+    let as_of = TimeDecay::new(f64::INFINITY, 199 * DAY);
+    let v = mle_importance_as_of(timed, corpus_size, None, None, 5, 0.1, 10, 2, &as_of).unwrap();
+
+    let earlier = dataset.retrievals[..200].to_vec();
+    assert_eq!(v, mle_importance(earlier, corpus_size, None, None, 5, 0.1, 10, 2));
+}
+
+#[test]
+fn recent_evidence_dominates() {
+    // Source 0 is helpful during the first 100 days and harmful afterwards, source 1 the opposite
+    let retrievals: Vec<Retrieval> = (0..200)
+        .map(|day| {
+            let utility_contributions = if day < 100 { vec![1.0, 0.0] } else { vec![0.0, 1.0] };
+            Retrieval::new(vec![0, 1], utility_contributions).at(day * DAY)
+        })
+        .collect();
+
+    let learn = |time_decay: TimeDecay| {
+        mle_importance_as_of(retrievals.clone(), 2, None, None, 1, 0.1, 50, 1, &time_decay)
+            .unwrap()
+    };
+    let half_life = 10.0 * DAY as f64;
+
+    let today = learn(TimeDecay::new(half_life, 199 * DAY));
+    assert!(today[0] < today[1]);
+
+    // Backtesting: as of day 99, only the helpful period of source 0 is known
+    let back_then = learn(TimeDecay::new(half_life, 99 * DAY));
+    assert!(back_then[0] > back_then[1]);
+
+    let latest = TimeDecay::as_of_latest(half_life, &retrievals).unwrap();
+    assert_eq!(latest.as_of(), 199 * DAY);
+    assert!(TimeDecay::as_of_latest(half_life, &[Retrieval::new(vec![0], vec![1.0])]).is_none());
+}
+
+#[test]
+fn nothing_to_learn_from_is_an_error() {
+    let retrievals = vec![
+        Retrieval::new(vec![0, 1], vec![1.0, 0.0]).at(0),
+        Retrieval::new(vec![1], vec![0.0]).at(DAY),
+    ];
+
+    let learn = |time_decay: TimeDecay| {
+        mle_importance_as_of(retrievals.clone(), 2, None, None, 1, 0.1, 5, 1, &time_decay)
+    };
+
+    // All retrievals are asked later
+    let too_early = learn(TimeDecay::new(f64::INFINITY, -DAY));
+    assert_eq!(too_early.unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    // The weights of all retrievals decay to zero
+    let too_short = learn(TimeDecay::new(1.0, 1_000_000 * DAY));
+    assert_eq!(too_short.unwrap_err().kind(), ErrorKind::InvalidInput);
+
+    // A retrieval without a timestamp
+    let mut untimed = retrievals.clone();
+    untimed.push(Retrieval::new(vec![0], vec![1.0]));
+    let no_timestamp = mle_importance_as_of(untimed, 2, None, None, 1, 0.1, 5, 1,
+                                            &TimeDecay::new(f64::INFINITY, DAY));
+    assert_eq!(no_timestamp.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
@@ -1,5 +1,7 @@
+#![allow(clippy::needless_arbitrary_self_type, clippy::needless_return)]
+
 use ragbooster::mle::types::{Grouping, Retrieval};
-use ragbooster::mle::v_grouped;
+use ragbooster::mle::{mle_importance, mle_importance_tied, v_grouped};
 
 
 pub struct ValidationSample<V, L> {
@@ -76,6 +78,7 @@ pub fn mle_importance_from_model<V, S, L: Eq>(
         precomputed_retrieval_results,
         model.corpus_size(),
         optional_grouping,
+        None,
         k,
         0.1,
         num_steps,
@@ -195,3 +198,43 @@ fn toy_example_with_groups() {
     assert!(v_grouped[1] > 0.5);
     assert!(v_grouped[2] < 0.5);
 }
+
+#[test]
+fn toy_example_with_tied_groups() {
+
+    let model = ToyRetrievalModel{};
+    let validation_sample =
+        ValidationSample::new(WhichDBResearcherWonTheTuringAward, vec![MikeStonebraker, JimGray]);
+    let retrievals = vec![model.to_retrieved(model.retrieve(validation_sample))];
+
+    let group_assignments = vec![
+        0, // Wikipedia
+        1, // Bing
+        2, // Fakepedia
+        1, // Google
+        0, // DuckduckGo
+        2, // Liepedia
+    ];
+
+    let grouping = Grouping::new(3, group_assignments.clone());
+
+    let (v_groups, v) = mle_importance_tied(
+        retrievals.clone(), model.corpus_size(), &grouping, None, 3, 0.1, 3, 1);
+
+    assert_eq!(v_groups.len(), 3);
+    assert!(v_groups[0] > 0.5);
+    assert!(v_groups[1] > 0.5);
+    assert!(v_groups[2] < 0.5); // Fakepedia and Liepedia give the wrong answer
+
+    for (source, group) in group_assignments.iter().enumerate() {
+        assert_eq!(v[source], v_groups[*group]);
+    }
+
+    // Tying sources to singleton groups is the same as not grouping at all
+    let singletons = Grouping::new(6, (0..6).collect());
+    let (v_singletons, _) = mle_importance_tied(
+        retrievals.clone(), model.corpus_size(), &singletons, None, 3, 0.1, 3, 1);
+    let v_ungrouped = mle_importance(retrievals, model.corpus_size(), None, None, 3, 0.1, 3, 1);
+
+    assert_eq!(v_singletons, v_ungrouped);
+}
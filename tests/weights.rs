@@ -0,0 +1,42 @@
+use ragbooster::mle as mle;
+use mle::types::Retrieval;
+
+const CORPUS_SIZE: usize = 4;
+
+#[test]
+fn weight_is_equivalent_to_duplication() {
+
+    let duplicated = vec![
+        Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 1.0]),
+        Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 1.0]),
+        Retrieval::new(vec![0, 1, 2], vec![1.0, 0.0, 1.0]),
+        Retrieval::new(vec![3, 1, 0], vec![0.0, 1.0, 0.0]),
+    ];
+
+    let weighted = vec![
+        Retrieval::with_weight(vec![0, 1, 2], vec![1.0, 0.0, 1.0], 3.0),
+        Retrieval::new(vec![3, 1, 0], vec![0.0, 1.0, 0.0]),
+    ];
+
+    let v_duplicated = mle::mle_importance(duplicated, CORPUS_SIZE, None, None, 2, 0.5, 10, 1);
+    let v_weighted = mle::mle_importance(weighted, CORPUS_SIZE, None, None, 2, 0.5, 10, 1);
+
+    for (d, w) in v_duplicated.iter().zip(v_weighted.iter()) {
+        assert!((d - w).abs() < 0.0000001);
+    }
+}
+
+#[test]
+fn zero_weight_is_ignored() {
+
+    let retrievals = vec![
+        Retrieval::new(vec![0, 1], vec![1.0, 1.0]),
+        Retrieval::with_weight(vec![2, 3], vec![0.0, 0.0], 0.0),
+    ];
+
+    let v = mle::mle_importance(retrievals, CORPUS_SIZE, None, None, 1, 0.5, 10, 2);
+
+    assert!(v[0] > 0.5);
+    assert_eq!(v[2], 0.5);
+    assert_eq!(v[3], 0.5);
+}